@@ -0,0 +1,107 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use kzg_ceremony_circuit::halo2_proofs::arithmetic::Field;
+use kzg_ceremony_circuit::halo2_proofs::pairing::bls12_381;
+use kzg_ceremony_circuit::halo2_proofs::pairing::bn256::{self, Bn256, Fr};
+use kzg_ceremony_circuit::halo2_proofs::pairing::group::Curve;
+use kzg_ceremony_circuit::halo2_proofs::poly::commitment::Params;
+use kzg_ceremony_circuit::{circuit_g1_mul, circuit_g2_mul, K};
+
+// Proving benches need params for K and take minutes per sample, so they
+// only run with BENCH_PROVER set.
+const BENCH_PROVER: &str = "BENCH_PROVER";
+
+macro_rules! random_circuit {
+    ($module:ident, $curve:ident) => {{
+        let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+        let tau = bls12_381::Fr::random(&mut rng);
+        let pubkey = (bls12_381::G2Affine::generator() * tau).to_affine();
+        let from_index = 1;
+
+        let old_points = (0..$module::LENGTH)
+            .map(|_| (bls12_381::$curve::generator() * bls12_381::Fr::random(&mut rng)).to_affine())
+            .collect::<Vec<_>>();
+        let mut scalar = tau.pow_vartime(&[from_index as u64, 0, 0, 0]);
+        let mut new_points = vec![];
+        for p in old_points.iter() {
+            new_points.push((p * scalar).to_affine());
+            scalar = scalar * tau;
+        }
+
+        let circuit = $module::Circuit::<Fr> {
+            from_index: Some(from_index),
+            tau: Some(tau),
+            pubkey: Some(pubkey),
+            points: old_points.iter().map(|p| Some(*p)).collect(),
+            new_points: new_points.iter().map(|p| Some(*p)).collect(),
+            ..Default::default()
+        };
+        let instance = $module::generate_instance(&$module::Instance {
+            from_index,
+            pubkey,
+            old_points,
+            new_points,
+        });
+
+        (circuit, instance)
+    }};
+}
+
+fn read_params() -> Params<bn256::G1Affine> {
+    match std::fs::read(format!("params_{}.bin", K)) {
+        Ok(params) => Params::<bn256::G1Affine>::read(&params[..]).expect("Read params failed"),
+        Err(_) => Params::<bn256::G1Affine>::unsafe_setup::<Bn256>(K),
+    }
+}
+
+fn bench_witness(c: &mut Criterion) {
+    let mut group = c.benchmark_group("witness");
+    group.sample_size(10);
+
+    let (g1_circuit, _) = random_circuit!(circuit_g1_mul, G1Affine);
+    group.bench_function("g1_mul", |b| b.iter(|| g1_circuit.assign_records()));
+
+    let (g2_circuit, _) = random_circuit!(circuit_g2_mul, G2Affine);
+    group.bench_function("g2_mul", |b| b.iter(|| g2_circuit.assign_records()));
+
+    group.finish();
+}
+
+macro_rules! bench_prover {
+    ($c:expr, $name:expr, $module:ident, $curve:ident) => {{
+        let params = read_params();
+        let (circuit, instance) = random_circuit!($module, $curve);
+
+        let mut group = $c.benchmark_group($name);
+        group.sample_size(10);
+
+        group.bench_function("keygen", |b| b.iter(|| $module::ProvingKey::build(&params)));
+
+        let pk = $module::ProvingKey::build(&params);
+        let vk = $module::VerifyingKey::build(&params);
+        group.bench_function("prove", |b| {
+            b.iter(|| $module::create_proofs(&params, circuit.clone(), &pk, &instance))
+        });
+
+        let proof = $module::create_proofs(&params, circuit.clone(), &pk, &instance);
+        group.bench_function("verify", |b| {
+            b.iter(|| $module::verify_proof(&params, &vk, &proof, &instance).unwrap())
+        });
+
+        group.finish();
+    }};
+}
+
+fn bench_prover(c: &mut Criterion) {
+    if std::env::var(BENCH_PROVER).is_err() {
+        return;
+    }
+
+    bench_prover!(c, "g1_mul", circuit_g1_mul, G1Affine);
+    bench_prover!(c, "g2_mul", circuit_g2_mul, G2Affine);
+}
+
+criterion_group!(benches, bench_witness, bench_prover);
+criterion_main!(benches);
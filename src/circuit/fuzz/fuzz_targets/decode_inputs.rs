@@ -0,0 +1,71 @@
+#![no_main]
+
+/*
+  Decodes a compressed G1 point and a little-endian scalar from raw bytes
+  with the decompression and canonical scalar gadgets, each under MockProver
+  at the small k of its own rows. The constraints must be satisfiable exactly
+  when the native decoding succeeds: the identity encoding, like every other
+  invalid one, must leave them unsatisfiable instead of panicking the witness
+  generation.
+*/
+
+use libfuzzer_sys::fuzz_target;
+use num_bigint::BigUint;
+
+use kzg_ceremony_circuit::circuit_utils::base_chip::BaseChipOps;
+use kzg_ceremony_circuit::circuit_utils::compress::{CompressChipOps, FQ_BYTES};
+use kzg_ceremony_circuit::circuit_utils::integer_chip::IntegerChipOps;
+use kzg_ceremony_circuit::context::GeneralScalarEccContext;
+use kzg_ceremony_circuit::halo2_proofs::arithmetic::{CurveAffine, Field};
+use kzg_ceremony_circuit::halo2_proofs::pairing::bls12_381::{Fq, Fr as BlsFr, G1Affine};
+use kzg_ceremony_circuit::halo2_proofs::pairing::bn256::Fr;
+use kzg_ceremony_circuit::negative_test::{assign_bytes, NegativeTest};
+use kzg_ceremony_circuit::utils::{bn_to_field, field_to_bn};
+
+const SCALAR_BYTES: usize = 32;
+
+// Native decoding of the zcash format, without the subgroup check the
+// gadget leaves to its callers.
+fn is_valid_g1(bytes: &[u8]) -> bool {
+    let (compression, infinity) = (bytes[0] >> 7, (bytes[0] >> 6) & 1);
+    let mut x = bytes.to_vec();
+    x[0] &= 0x1f;
+    let x = BigUint::from_bytes_be(&x);
+    if compression != 1 || infinity != 0 || x > field_to_bn(&-Fq::one()) {
+        return false;
+    }
+    let x: Fq = bn_to_field(&x);
+    bool::from((x.square() * x + G1Affine::b()).sqrt().is_some())
+}
+
+fn is_valid_scalar(bytes: &[u8]) -> bool {
+    BigUint::from_bytes_le(bytes) <= field_to_bn(&-BlsFr::one())
+}
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < FQ_BYTES + SCALAR_BYTES {
+        return;
+    }
+    let (point, data) = data.split_at(FQ_BYTES);
+    let (scalar, _) = data.split_at(SCALAR_BYTES);
+
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("decompress", |ctx| {
+        let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(ctx.clone());
+        let bits = assign_bytes(&mut ctx.native_ctx.borrow_mut(), point);
+        ctx.ecc_decompress(&bits);
+    });
+    assert_eq!(test.run(&[]).is_empty(), is_valid_g1(point));
+
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("scalar", |ctx| {
+        let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(ctx.clone());
+        let bits = assign_bytes(&mut ctx.native_ctx.borrow_mut(), scalar);
+        let (bits, top) = bits.split_at(SCALAR_BYTES * 8 - 1);
+        ctx.scalar_integer_ctx.int_from_canonical_bits_le(bits);
+        ctx.native_ctx
+            .borrow_mut()
+            .assert_constant(&top[0].0, Fr::zero());
+    });
+    assert_eq!(test.run(&[]).is_empty(), is_valid_scalar(scalar));
+});
@@ -0,0 +1,209 @@
+/*
+  Accumulator convention shared by circuits taking part in proof aggregation.
+
+  A circuit that defers the final KZG pairing check of the proofs it verifies
+  exposes the deferred accumulator `(lhs, rhs)` at the head of its instance
+  column, as the limbs of `lhs.x, lhs.y, rhs.x, rhs.y` in the same limb layout
+  used for every other non-native value in this crate. The settling verifier
+  folds the accumulators of all aggregated proofs and checks
+  `e(lhs, [s]_2) == e(rhs, [1]_2)` once.
+*/
+
+use halo2_proofs::arithmetic::{CurveAffine, Engine, Field};
+use halo2_proofs::pairing::bn256::{Bn256, Fq, Fr, G1Affine, G2Affine};
+use halo2_proofs::pairing::group::{Curve, Group};
+use num_bigint::BigUint;
+
+use crate::circuit_utils::poseidon::PoseidonSpec;
+use crate::circuit_utils::range_chip::{COMMON_RANGE_BITS, OVERFLOW_BITS};
+use crate::error::InstanceError;
+use crate::range_info::RangeInfo;
+use crate::utils::{bn_to_field, field_to_bn};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KzgAccumulator {
+    pub lhs: G1Affine,
+    pub rhs: G1Affine,
+}
+
+impl KzgAccumulator {
+    fn range_info() -> RangeInfo<Fq, Fr> {
+        RangeInfo::new(COMMON_RANGE_BITS, OVERFLOW_BITS)
+    }
+
+    pub fn instance_num() -> usize {
+        4 * Self::range_info().limbs as usize
+    }
+
+    // The identity has no affine coordinates and can't be exposed.
+    pub fn to_instances(&self) -> Result<Vec<Fr>, InstanceError> {
+        let info = Self::range_info();
+        let mut instances = vec![];
+        for (i, p) in [self.lhs, self.rhs].iter().enumerate() {
+            let row = i * 2 * info.limbs as usize;
+            if bool::from(p.is_identity()) {
+                return Err(InstanceError::InvalidPoint { row });
+            }
+            let coordinates = p.coordinates().unwrap();
+            instances.extend(info.bn_to_limb_le_n(&field_to_bn(coordinates.x())));
+            instances.extend(info.bn_to_limb_le_n(&field_to_bn(coordinates.y())));
+        }
+        Ok(instances)
+    }
+
+    // Reads the accumulator at the head of `instances`, rejecting limbs no
+    // circuit could have exposed and points off the curve or at infinity.
+    pub fn from_instances(instances: &[Fr]) -> Result<Self, InstanceError> {
+        let info = Self::range_info();
+        let limbs = info.limbs as usize;
+        let modulus = field_to_bn(&-Fq::one()) + 1u64;
+
+        let coordinate = |row: usize| {
+            let chunk = instances
+                .get(row..row + limbs)
+                .ok_or(InstanceError::UnexpectedEnd { row })?;
+            let mut bn = BigUint::from(0u64);
+            for (i, limb) in chunk.iter().enumerate().rev() {
+                let limb = field_to_bn(limb);
+                if limb.bits() > info.limb_bits {
+                    return Err(InstanceError::NonCanonical { row: row + i });
+                }
+                bn = (bn << info.limb_bits) + limb;
+            }
+            if bn >= modulus {
+                return Err(InstanceError::NonCanonical { row });
+            }
+            Ok(bn_to_field::<Fq>(&bn))
+        };
+        let point = |row: usize| {
+            let (x, y) = (coordinate(row)?, coordinate(row + limbs)?);
+            Option::<G1Affine>::from(G1Affine::from_xy(x, y))
+                .filter(|p| !bool::from(p.is_identity()))
+                .ok_or(InstanceError::InvalidPoint { row })
+        };
+
+        Ok(Self {
+            lhs: point(0)?,
+            rhs: point(2 * limbs)?,
+        })
+    }
+
+    // Random linear combination with a challenge bound to every accumulator.
+    pub fn fold(accumulators: &[Self]) -> Result<Self, InstanceError> {
+        assert!(!accumulators.is_empty());
+
+        let transcript = accumulators
+            .iter()
+            .map(|acc| acc.to_instances())
+            .collect::<Result<Vec<_>, _>>()?
+            .concat();
+        let challenge = PoseidonSpec::<Fr>::new().hash(&transcript);
+
+        let mut lhs = <G1Affine as CurveAffine>::CurveExt::identity();
+        let mut rhs = <G1Affine as CurveAffine>::CurveExt::identity();
+        let mut power = Fr::one();
+        for acc in accumulators {
+            lhs = lhs + acc.lhs * power;
+            rhs = rhs + acc.rhs * power;
+            power = power * challenge;
+        }
+
+        Ok(Self {
+            lhs: lhs.to_affine(),
+            rhs: rhs.to_affine(),
+        })
+    }
+
+    pub fn decide(&self, g2: &G2Affine, s_g2: &G2Affine) -> bool {
+        Bn256::pairing(&self.lhs, s_g2) == Bn256::pairing(&self.rhs, g2)
+    }
+}
+
+#[test]
+fn test_accumulator_instances_round_trip() {
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let random_point =
+        |rng: &mut XorShiftRng| (G1Affine::generator() * Fr::random(rng)).to_affine();
+    let acc = KzgAccumulator {
+        lhs: random_point(&mut rng),
+        rhs: random_point(&mut rng),
+    };
+    let limbs = KzgAccumulator::range_info().limbs as usize;
+
+    let mut instances = acc.to_instances().unwrap();
+    assert_eq!(instances.len(), KzgAccumulator::instance_num());
+    assert_eq!(KzgAccumulator::from_instances(&instances), Ok(acc));
+    // the accumulator is only the head of the instance column
+    instances.push(Fr::one());
+    assert_eq!(KzgAccumulator::from_instances(&instances), Ok(acc));
+
+    assert_eq!(
+        KzgAccumulator::from_instances(&instances[..4 * limbs - 1]),
+        Err(InstanceError::UnexpectedEnd { row: 3 * limbs })
+    );
+    let mut tampered = instances.clone();
+    tampered[2 * limbs] += Fr::one();
+    assert_eq!(
+        KzgAccumulator::from_instances(&tampered),
+        Err(InstanceError::InvalidPoint { row: 2 * limbs })
+    );
+    let mut tampered = instances.clone();
+    tampered[1] = -Fr::one();
+    assert_eq!(
+        KzgAccumulator::from_instances(&tampered),
+        Err(InstanceError::NonCanonical { row: 1 })
+    );
+    // the identity is neither exposed nor read back
+    let identity = KzgAccumulator {
+        lhs: G1Affine::identity(),
+        ..acc
+    };
+    assert_eq!(
+        identity.to_instances(),
+        Err(InstanceError::InvalidPoint { row: 0 })
+    );
+    let mut zeros = instances.clone();
+    zeros[..2 * limbs].iter_mut().for_each(|l| *l = Fr::zero());
+    assert_eq!(
+        KzgAccumulator::from_instances(&zeros),
+        Err(InstanceError::InvalidPoint { row: 0 })
+    );
+}
+
+#[test]
+fn test_accumulator_fold_and_decide() {
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let s = Fr::random(&mut rng);
+    let g2 = G2Affine::generator();
+    let s_g2 = (g2 * s).to_affine();
+
+    // e(P, [s]_2) == e(s * P, [1]_2)
+    let accumulators = (0..3)
+        .map(|_| {
+            let p = G1Affine::generator() * Fr::random(&mut rng);
+            KzgAccumulator {
+                lhs: p.to_affine(),
+                rhs: (p * s).to_affine(),
+            }
+        })
+        .collect::<Vec<_>>();
+    assert!(accumulators.iter().all(|acc| acc.decide(&g2, &s_g2)));
+    let folded = KzgAccumulator::fold(&accumulators).unwrap();
+    assert!(folded.decide(&g2, &s_g2));
+    assert_eq!(
+        KzgAccumulator::from_instances(&folded.to_instances().unwrap()),
+        Ok(folded)
+    );
+
+    // one bad accumulator spoils the fold
+    let mut tampered = accumulators.clone();
+    tampered[1].rhs = (tampered[1].rhs + G1Affine::generator()).to_affine();
+    assert!(!KzgAccumulator::fold(&tampered).unwrap().decide(&g2, &s_g2));
+    assert!(!folded.decide(&g2, &g2));
+}
@@ -44,6 +44,9 @@ pub struct AssignedCurvature<C: CurveAffine, N: FieldExt>(
     pub AssignedCondition<N>,
 );
 
+// `z` flags the identity. The ECC gadgets branch on it instead of on the
+// coordinates, which are meaningless for the identity; `ecc_normalize` pins
+// them to those of `assign_identity`.
 #[derive(Clone, Debug)]
 pub struct AssignedPoint<C: CurveAffine, N: FieldExt> {
     pub x: AssignedInteger<C::Base, N>,
@@ -0,0 +1,31 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::CircuitError;
+
+// Aborts witness generation from another thread, e.g. when the request a
+// proof is made for times out. Clones share the flag. Once the witness is
+// assigned the proof runs to completion inside halo2.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+pub(crate) fn check_cancelled(token: &Option<CancellationToken>) -> Result<(), CircuitError> {
+    match token {
+        Some(token) if token.is_cancelled() => Err(CircuitError::Cancelled),
+        _ => Ok(()),
+    }
+}
@@ -4,8 +4,8 @@ use std::rc::Rc;
 use std::sync::Arc;
 
 use ark_std::{end_timer, start_timer};
-use num_bigint::BigUint;
 use rand::rngs::OsRng;
+use rand::RngCore;
 
 use halo2_proofs::arithmetic::{Field, FieldExt};
 use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner};
@@ -15,13 +15,26 @@ use halo2_proofs::plonk::{self, ConstraintSystem, Error, SingleVerifier};
 use halo2_proofs::poly::commitment::{Params, ParamsVerifier};
 use halo2_proofs::transcript::{Blake2bRead, Blake2bWrite, Challenge255};
 
+use crate::assign::AssignedValue;
+use crate::cancel::{check_cancelled, CancellationToken};
 use crate::circuit_utils::base_chip::{BaseChip, BaseChipConfig};
 use crate::circuit_utils::ecc_chip::{EccChipBaseOps, EccChipScalarOps};
-use crate::circuit_utils::fq2::Fq2ChipOps;
 use crate::circuit_utils::integer_chip::IntegerChipOps;
-use crate::circuit_utils::range_chip::{RangeChip, RangeChipConfig, RangeChipOps};
-use crate::context::{Context, GeneralScalarEccContext};
-use crate::utils::{bn_to_field, field_to_bn, split_g2_point};
+use crate::circuit_utils::range_chip::{RangeChip, RangeChipConfig, RangeChipOps, RangeTable};
+use crate::context::{
+    assigned_cell, exposed_cells, Context, GeneralScalarEccContext, Records, RecordsSnapshot,
+};
+use crate::cost::{CircuitCost, VerifierCost};
+use crate::error::CircuitError;
+use crate::fingerprint::CircuitFingerprint;
+use crate::instance::{commit_assigned_instances, InstanceCommitment};
+use crate::instance_builder::InstanceBuilder;
+use crate::preset::{Preset, PresetCircuit};
+use crate::progress::{report, Progress, ProvingEvent};
+use crate::utils::field_to_bn;
+#[cfg(feature = "zeroize")]
+use crate::utils::zeroize_fields;
+use crate::witness_cache::{assign_pubkey_with_tau, PubkeyWitness, WitnessCache};
 
 pub const LENGTH: usize = 16;
 const INSTANCE_NUM: usize = 1 + 16 + 8 * 2 * 2 * LENGTH;
@@ -39,6 +52,20 @@ pub struct Circuit<N: FieldExt> {
     pub pubkey: Option<bls12_381::G2Affine>,
     pub points: Vec<Option<bls12_381::G2Affine>>,
     pub new_points: Vec<Option<bls12_381::G2Affine>>,
+    pub instance_commitment: InstanceCommitment,
+    pub witness_cache: Option<Arc<WitnessCache<N>>>,
+    // Assign through `Records::assign_all_low_memory`, see there.
+    pub low_memory: bool,
+    // Replays the records of the first synthesize call in the later ones.
+    // Snapshot records are kept, so they are never assigned in low memory.
+    pub snapshot: Option<RecordsSnapshot<N>>,
+    // Loaded instead of generating the range table on every synthesize.
+    pub range_table: Option<Arc<RangeTable<N>>>,
+    // Notified of the proving phases, see `ProvingEvent`.
+    pub progress: Option<Progress>,
+    // Checked during witness generation, which fails with
+    // `CircuitError::Cancelled` once it is cancelled.
+    pub cancel: Option<CancellationToken>,
     pub _mark: PhantomData<N>,
 }
 
@@ -50,36 +77,41 @@ impl<N: FieldExt> Default for Circuit<N> {
             pubkey: None,
             points: vec![None; LENGTH],
             new_points: vec![None; LENGTH],
+            instance_commitment: InstanceCommitment::default(),
+            witness_cache: None,
+            low_memory: false,
+            snapshot: None,
+            range_table: None,
+            progress: None,
+            cancel: None,
             _mark: Default::default(),
         }
     }
 }
 
-impl<N: FieldExt> plonk::Circuit<N> for Circuit<N> {
-    type Config = Config;
-    type FloorPlanner = SimpleFloorPlanner;
-
-    fn without_witnesses(&self) -> Self {
-        Self::default()
+#[cfg(feature = "zeroize")]
+impl<N: FieldExt> zeroize::Zeroize for Circuit<N> {
+    fn zeroize(&mut self) {
+        zeroize_fields(self.tau.as_mut());
+        if let Some(snapshot) = self.snapshot.as_mut() {
+            zeroize::Zeroize::zeroize(snapshot);
+        }
     }
+}
 
-    fn configure(meta: &mut ConstraintSystem<N>) -> Self::Config {
-        Config {
-            base_chip_config: BaseChip::configure(meta),
-            range_chip_config: RangeChip::<N>::configure(meta),
-        }
+impl<N: FieldExt> Circuit<N> {
+    // Records the whole witness without touching a layouter.
+    pub fn assign_records(&self) -> Result<(Records<N>, Vec<AssignedValue<N>>), CircuitError> {
+        self.assign_records_reusing(Records::default())
     }
 
-    fn synthesize(
+    // `assign_records` into the allocations of `buffer`, see
+    // `Context::new_reusing`.
+    pub fn assign_records_reusing(
         &self,
-        config: Self::Config,
-        mut layouter: impl Layouter<N>,
-    ) -> Result<(), Error> {
-        let base_chip = BaseChip::new(config.base_chip_config.clone());
-        let range_chip = RangeChip::<N>::new(config.range_chip_config);
-        range_chip.init_table(&mut layouter)?;
-
-        let ctx = Rc::new(RefCell::new(Context::new()));
+        buffer: Records<N>,
+    ) -> Result<(Records<N>, Vec<AssignedValue<N>>), CircuitError> {
+        let ctx = Rc::new(RefCell::new(Context::new_reusing(buffer)));
         let mut ctx = GeneralScalarEccContext::<bls12_381::G1Affine, N>::new(ctx);
         let mut instances = vec![];
 
@@ -89,32 +121,19 @@ impl<N: FieldExt> plonk::Circuit<N> for Circuit<N> {
             .assign_small_number(self.from_index.unwrap_or_default(), 16);
         instances.push(from_index.clone());
 
-        // load pubkey
-        let pubkey = self.pubkey.unwrap_or(bls12_381::G2Affine::generator());
-        let four = bls12_381::Fq::one().double().double();
-        let b = ctx.fq2_assign_constant((four, four));
-        let pubkey = ctx.assign_non_identity_g2(
-            &((pubkey.x.c0, pubkey.x.c1), (pubkey.y.c0, pubkey.y.c1)),
-            b.clone(),
-        );
+        // load pubkey and tau, check pubkey = tau * G2
+        let PubkeyWitness { b, pubkey, tau } = assign_pubkey_with_tau(
+            &mut ctx,
+            self.witness_cache.as_deref(),
+            &self.pubkey.unwrap_or(bls12_381::G2Affine::generator()),
+            &self.tau.unwrap_or_default(),
+        )?;
 
         instances.extend_from_slice(&pubkey.x.0.limbs_le);
         instances.extend_from_slice(&pubkey.x.1.limbs_le);
         instances.extend_from_slice(&pubkey.y.0.limbs_le);
         instances.extend_from_slice(&pubkey.y.1.limbs_le);
 
-        // load tau and check pubkey
-        let tau = ctx
-            .scalar_integer_ctx
-            .assign_w(&field_to_bn(&self.tau.unwrap_or_default()));
-        let generator = ctx.assign_non_identity_constant_g2({
-            let g = bls12_381::G2Affine::generator();
-            &((g.x.c0, g.x.c1), (g.y.c0, g.y.c1))
-        });
-
-        let expected_pubkey = ctx.ecc_g2_mul(&generator, &tau);
-        ctx.ecc_assert_g2_equal(&pubkey, &expected_pubkey);
-
         // load points
         assert_eq!(self.points.len(), LENGTH);
         let points = self
@@ -207,46 +226,159 @@ impl<N: FieldExt> plonk::Circuit<N> for Circuit<N> {
             .iter()
             .zip(new_points.iter().zip(scalars.into_iter()))
         {
+            check_cancelled(&self.cancel)?;
             let p = ctx.ecc_g2_mul(point, &scalar);
             ctx.ecc_assert_g2_equal(&p, &new_point);
         }
         assert_eq!(instances.len(), INSTANCE_NUM);
+        let instances =
+            commit_assigned_instances(&ctx.native_ctx, self.instance_commitment, instances);
+
+        for (row, instance) in instances.iter().enumerate() {
+            ctx.native_ctx.borrow_mut().expose_public(instance, row)?;
+        }
 
-        let ctx = Context::<N>::from(ctx);
-        let mut records = Arc::try_unwrap(ctx.records).unwrap().into_inner().unwrap();
+        let records = Context::<N>::try_from(ctx)?.into_records()?;
 
-        for instance in instances.iter() {
-            records.enable_permute(&instance.cell);
+        Ok((records, instances))
+    }
+
+    // Takes the memory mode of `preset`, the other parameters are compiled
+    // in and only checked.
+    pub fn with_preset(self, preset: Preset) -> Self {
+        let params = preset.params();
+        assert_eq!(params.circuit, PresetCircuit::G2Mul);
+        assert_eq!(params.batch_size, LENGTH);
+
+        Self {
+            low_memory: params.low_memory,
+            ..self
+        }
+    }
+
+    // Dry-runs witness recording, `Circuit::default()` is enough.
+    pub fn estimate(&self) -> Result<CircuitCost, CircuitError> {
+        let (records, _) = self.assign_records()?;
+        Ok(CircuitCost::from_records(&records))
+    }
+}
+
+impl<N: FieldExt> plonk::Circuit<N> for Circuit<N> {
+    type Config = Config;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            instance_commitment: self.instance_commitment,
+            low_memory: self.low_memory,
+            range_table: self.range_table.clone(),
+            ..Self::default()
         }
+    }
 
+    fn configure(meta: &mut ConstraintSystem<N>) -> Self::Config {
+        Config {
+            base_chip_config: BaseChip::configure(meta),
+            range_chip_config: RangeChip::<N>::configure(meta),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<N>,
+    ) -> Result<(), Error> {
+        let base_chip = BaseChip::new(config.base_chip_config.clone());
+        let range_chip = RangeChip::<N>::new(config.range_chip_config);
+        match &self.range_table {
+            Some(table) => range_chip.load_table(&mut layouter, table)?,
+            None => range_chip.init_table(&mut layouter)?,
+        }
+
+        check_cancelled(&self.cancel)?;
+        report(&self.progress, ProvingEvent::WitnessStarted);
+        let snapshot = match &self.snapshot {
+            Some(snapshot) => Some(snapshot.get_or_record(|| Ok(self.assign_records()?.0))?),
+            None => None,
+        };
+        let mut records = match snapshot {
+            Some(_) => Records::default(),
+            None => self.assign_records()?.0,
+        };
+        let exposed = snapshot.as_deref().unwrap_or(&records).exposed.clone();
+        {
+            let records = snapshot.as_deref().unwrap_or(&records);
+            report(
+                &self.progress,
+                ProvingEvent::WitnessRecorded {
+                    base_rows: records.base_height,
+                    range_rows: records.range_height,
+                    permutations: records.permutations.len(),
+                },
+            );
+        }
+
+        check_cancelled(&self.cancel)?;
         let mut assigned_instance_cells = vec![];
+        // SimpleFloorPlanner runs the closure twice, the first time only to
+        // measure the region. The records are still assigned serially in the
+        // second, halo2's Region can't be shared between threads.
+        let mut is_shape_pass = true;
         layouter.assign_region(
-            || "base",
+            || "g2 mul records",
             |mut region| {
-                let cells = records.assign_all(&mut region, &base_chip, &range_chip)?;
-                assigned_instance_cells = instances
-                    .iter()
-                    .map(|ist| {
-                        let cell = cells[ist.cell.region as usize][ist.cell.col][ist.cell.row]
-                            .clone()
-                            .unwrap();
-
-                        cell.cell()
-                    })
-                    .collect::<Vec<_>>();
+                if is_shape_pass {
+                    is_shape_pass = false;
+                    return snapshot.as_deref().unwrap_or(&records).assign_shape(
+                        &mut region,
+                        &base_chip,
+                        &range_chip,
+                    );
+                }
+
+                if self.low_memory && snapshot.is_none() {
+                    let cells = std::mem::take(&mut records).assign_all_low_memory(
+                        &mut region,
+                        &base_chip,
+                        &range_chip,
+                    )?;
+                    assigned_instance_cells = exposed_cells(&exposed, |cell| {
+                        cells
+                            .get(cell)
+                            .copied()
+                            .ok_or(CircuitError::UnassignedCell(*cell))
+                    })?;
+                    return Ok(());
+                }
+
+                let cells = snapshot.as_deref().unwrap_or(&records).assign_all(
+                    &mut region,
+                    &base_chip,
+                    &range_chip,
+                )?;
+                assigned_instance_cells =
+                    exposed_cells(&exposed, |cell| assigned_cell(&cells, cell))?;
                 Ok(())
             },
         )?;
 
         // Constrain public input
-        for (offset, instance) in assigned_instance_cells.into_iter().enumerate() {
-            layouter.constrain_instance(instance, config.base_chip_config.primary, offset)?;
+        for (instance, row) in assigned_instance_cells.into_iter() {
+            layouter.constrain_instance(instance, config.base_chip_config.primary, row)?;
         }
+        report(&self.progress, ProvingEvent::WitnessAssigned);
 
         Ok(())
     }
 }
 
+// Keys are built from this constraint system, whatever the witness.
+fn constraint_system() -> ConstraintSystem<Fr> {
+    let mut cs = ConstraintSystem::default();
+    <Circuit<Fr> as plonk::Circuit<Fr>>::configure(&mut cs);
+    cs
+}
+
 /// The verifying key for the Orchard Action circuit.
 #[derive(Debug)]
 pub struct VerifyingKey {
@@ -256,12 +388,32 @@ pub struct VerifyingKey {
 impl VerifyingKey {
     /// Builds the verifying key.
     pub fn build(params: &Params<G1Affine>) -> Self {
-        let circuit: Circuit<Fr> = Default::default();
+        Self::build_with_commitment(params, InstanceCommitment::default())
+    }
+
+    /// Builds the verifying key for circuits exposing their inputs under `commitment`.
+    pub fn build_with_commitment(
+        params: &Params<G1Affine>,
+        commitment: InstanceCommitment,
+    ) -> Self {
+        let circuit: Circuit<Fr> = Circuit {
+            instance_commitment: commitment,
+            ..Default::default()
+        };
 
         let vk = plonk::keygen_vk(&params, &circuit).unwrap();
 
         VerifyingKey { vk }
     }
+
+    /// Proof size and verification cost for inputs exposed under `commitment`.
+    pub fn verifier_cost(&self, commitment: InstanceCommitment) -> VerifierCost {
+        VerifierCost::from_cs(&constraint_system(), commitment.instance_num(INSTANCE_NUM))
+    }
+
+    pub fn fingerprint(&self) -> CircuitFingerprint {
+        CircuitFingerprint::new(&constraint_system(), &self.vk)
+    }
 }
 
 #[derive(Debug)]
@@ -272,13 +424,30 @@ pub struct ProvingKey {
 impl ProvingKey {
     /// Builds the proving key.
     pub fn build(params: &Params<G1Affine>) -> Self {
-        let circuit: Circuit<Fr> = Default::default();
+        Self::build_with_commitment(params, InstanceCommitment::default())
+    }
+
+    /// Builds the proving key for circuits exposing their inputs under `commitment`.
+    pub fn build_with_commitment(
+        params: &Params<G1Affine>,
+        commitment: InstanceCommitment,
+    ) -> Self {
+        // keygen of the vk and the pk synthesize the same empty witness
+        let circuit: Circuit<Fr> = Circuit {
+            instance_commitment: commitment,
+            snapshot: Some(RecordsSnapshot::new()),
+            ..Default::default()
+        };
 
         let vk = plonk::keygen_vk(&params, &circuit).unwrap();
         let pk = plonk::keygen_pk(&params, vk, &circuit).unwrap();
 
         ProvingKey { pk }
     }
+
+    pub fn fingerprint(&self) -> CircuitFingerprint {
+        CircuitFingerprint::new(&constraint_system(), self.pk.get_vk())
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -290,20 +459,17 @@ pub struct Instance {
 }
 
 pub fn generate_instance(instance: &Instance) -> Vec<Fr> {
-    let mut halo2_instances = vec![bn_to_field::<Fr>(&BigUint::from(instance.from_index))];
-
-    halo2_instances.extend_from_slice(&split_g2_point(&instance.pubkey));
-
-    let _ = instance
-        .old_points
-        .iter()
-        .chain(instance.new_points.iter())
-        .map(|p| halo2_instances.extend_from_slice(&split_g2_point(&p)))
-        .collect::<Vec<_>>();
+    let mut builder = InstanceBuilder::new();
+    builder
+        .push_u64(instance.from_index as u64)
+        .push_g2(&instance.pubkey);
+    for p in instance.old_points.iter().chain(instance.new_points.iter()) {
+        builder.push_g2(p);
+    }
 
-    assert_eq!(halo2_instances.len(), INSTANCE_NUM);
+    assert_eq!(builder.len(), INSTANCE_NUM);
 
-    halo2_instances
+    builder.build()
 }
 
 pub fn create_proofs(
@@ -312,21 +478,54 @@ pub fn create_proofs(
     pk: &ProvingKey,
     instance: &Vec<Fr>,
 ) -> Vec<u8> {
+    create_proofs_with_rng(params, circuit, pk, instance, OsRng)
+}
+
+// Blinding factors are drawn from `rng`, a seeded one gives reproducible
+// proofs since the witness itself is deterministic.
+pub fn create_proofs_with_rng(
+    params: &Params<G1Affine>,
+    circuit: Circuit<Fr>,
+    pk: &ProvingKey,
+    instance: &Vec<Fr>,
+    rng: impl RngCore,
+) -> Vec<u8> {
+    try_create_proofs_with_rng(params, circuit, pk, instance, rng)
+        .expect("proof generation should not fail")
+}
+
+// Fails with `Error::Synthesis` when the `cancel` token of the circuit is
+// cancelled during witness generation, the witness is dropped by then.
+pub fn try_create_proofs_with_rng(
+    params: &Params<bn256::G1Affine>,
+    circuit: Circuit<Fr>,
+    pk: &ProvingKey,
+    instance: &Vec<Fr>,
+    rng: impl RngCore,
+) -> Result<Vec<u8>, Error> {
     let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+    let progress = circuit.progress.clone();
+
+    // The taus are wiped when the guard drops, on errors and panics too.
+    #[cfg(feature = "zeroize")]
+    let circuits = zeroize::Zeroizing::new([circuit]);
+    #[cfg(not(feature = "zeroize"))]
+    let circuits = [circuit];
 
     let timer = start_timer!(|| "create proof");
     plonk::create_proof(
         &params,
         &pk.pk,
-        &[circuit],
+        &circuits[..],
         &[&[&instance]],
-        OsRng,
+        rng,
         &mut transcript,
-    )
-    .expect("proof generation should not fail");
+    )?;
     end_timer!(timer);
 
-    transcript.finalize()
+    let proof = transcript.finalize();
+    report(&progress, ProvingEvent::ProofCreated { bytes: proof.len() });
+    Ok(proof)
 }
 
 pub fn verify_proof(
@@ -335,7 +534,7 @@ pub fn verify_proof(
     proof: &Vec<u8>,
     instance: &Vec<Fr>,
 ) -> Result<(), Error> {
-    let params_verifier: ParamsVerifier<Bn256> = params.verifier(INSTANCE_NUM).unwrap();
+    let params_verifier: ParamsVerifier<Bn256> = params.verifier(instance.len()).unwrap();
 
     let strategy = SingleVerifier::new(&params_verifier);
     let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
@@ -356,6 +555,8 @@ mod tests {
         create_proofs, generate_instance, verify_proof, Circuit, Instance, ProvingKey,
         VerifyingKey, LENGTH,
     };
+    use crate::instance::InstanceCommitment;
+    use crate::negative_test::assert_input_independent_shape;
     use crate::K;
     use halo2_proofs::arithmetic::Field;
     use halo2_proofs::dev::MockProver;
@@ -393,6 +594,13 @@ mod tests {
             pubkey: Some((bls12_381::G2Affine::generator() * tau).to_affine()),
             points: old_points.iter().map(|p| Some(*p)).collect::<Vec<_>>(),
             new_points: new_points.iter().map(|p| Some(*p)).collect::<Vec<_>>(),
+            instance_commitment: InstanceCommitment::Plain,
+            witness_cache: None,
+            low_memory: false,
+            snapshot: None,
+            range_table: None,
+            progress: None,
+            cancel: None,
             _mark: Default::default(),
         };
 
@@ -416,6 +624,16 @@ mod tests {
         assert_eq!(prover.verify(), Ok(()));
     }
 
+    #[test]
+    fn shape_is_input_independent() {
+        let (circuit, _) = random_circuit();
+        assert_input_independent_shape(
+            [Circuit::<Fr>::default(), circuit.clone(), circuit]
+                .iter()
+                .map(|c| c.assign_records().unwrap().0),
+        );
+    }
+
     #[test]
     fn prover() {
         let (circuit, instance) = random_circuit();
@@ -0,0 +1,170 @@
+use halo2_proofs::arithmetic::{CurveAffine, FieldExt};
+
+use super::base_chip::BaseChipOps;
+use super::ecc_chip::EccChipBaseOps;
+use super::integer_chip::IntegerChipOps;
+use crate::assign::{AssignedCondition, AssignedPoint, AssignedValue};
+use crate::context::GeneralScalarEccContext;
+
+// Bits needed to hold any count up to `n`.
+pub fn count_bits(n: u64) -> usize {
+    usize::max(1, (u64::BITS - n.leading_zeros()) as usize)
+}
+
+// Weights are constrained to this many bits, so weight sums can't wrap.
+pub const WEIGHT_BITS: usize = 64;
+
+pub trait AggregateChipOps<C: CurveAffine, N: FieldExt>: EccChipBaseOps<C, N> {
+    // Sum of the pubkeys whose participation bit is set. The bits are
+    // constrained boolean by select_point.
+    fn aggregate_selected(
+        &mut self,
+        pubkeys: &[AssignedPoint<C, N>],
+        bitfield: &[AssignedValue<N>],
+    ) -> AssignedPoint<C, N> {
+        assert_eq!(pubkeys.len(), bitfield.len());
+
+        let mut acc = self.assign_identity().to_point();
+        for (pk, bit) in pubkeys.iter().zip(bitfield.iter()) {
+            let acc_with_curvature = self.to_point_with_curvature(acc.clone());
+            let sum = self.ecc_add(&acc_with_curvature, pk);
+            acc = self.select_point(bit, &sum, &acc);
+        }
+        acc
+    }
+
+    // The same sum, and the number of set bits.
+    fn aggregate_by_bitfield(
+        &mut self,
+        pubkeys: &[AssignedPoint<C, N>],
+        bitfield: &[AssignedValue<N>],
+    ) -> (AssignedPoint<C, N>, AssignedValue<N>) {
+        let acc = self.aggregate_selected(pubkeys, bitfield);
+        let bits = bitfield
+            .iter()
+            .map(|b| AssignedCondition(*b))
+            .collect::<Vec<_>>();
+
+        let count = self.aggregate_popcount(&bits);
+        (acc, count)
+    }
+
+    fn aggregate_popcount(&mut self, bits: &[AssignedCondition<N>]) -> AssignedValue<N> {
+        let schema = bits.iter().map(|b| (&b.0, N::one())).collect();
+        self.base_integer_chip()
+            .base_chip()
+            .sum_with_constant(schema, None)
+    }
+
+    // Asserts `v >= threshold` for a `v` known to fit in `bits` bits: the
+    // difference wraps around the field when below and fails the range check.
+    fn aggregate_assert_at_least(&mut self, v: &AssignedValue<N>, threshold: u64, bits: usize) {
+        let mut base_chip = self.base_integer_chip().base_chip();
+        let diff = base_chip.sum_with_constant(vec![(v, N::one())], Some(-N::from(threshold)));
+        base_chip.decompose_bits_le(&diff, bits);
+    }
+
+    // Aggregate of the participating pubkeys with the participating and the
+    // total weight, the weights are range checked to WEIGHT_BITS.
+    fn aggregate_by_weight(
+        &mut self,
+        pubkeys: &[AssignedPoint<C, N>],
+        bitfield: &[AssignedValue<N>],
+        weights: &[AssignedValue<N>],
+    ) -> (AssignedPoint<C, N>, AssignedValue<N>, AssignedValue<N>) {
+        assert_eq!(pubkeys.len(), weights.len());
+
+        let aggregate = self.aggregate_selected(pubkeys, bitfield);
+
+        let mut base_chip = self.base_integer_chip().base_chip();
+        let participating = bitfield
+            .iter()
+            .zip(weights.iter())
+            .map(|(bit, w)| {
+                base_chip.decompose_bits_le(w, WEIGHT_BITS);
+                base_chip.mul(bit, w)
+            })
+            .collect::<Vec<_>>();
+        let participating = base_chip
+            .sum_with_constant(participating.iter().map(|w| (w, N::one())).collect(), None);
+        let total =
+            base_chip.sum_with_constant(weights.iter().map(|w| (w, N::one())).collect(), None);
+
+        (aggregate, participating, total)
+    }
+
+    // Asserts 3 * participating > 2 * total, both below 2^bits.
+    fn aggregate_assert_supermajority(
+        &mut self,
+        participating: &AssignedValue<N>,
+        total: &AssignedValue<N>,
+        bits: usize,
+    ) {
+        let mut base_chip = self.base_integer_chip().base_chip();
+        let diff = base_chip.sum_with_constant(
+            vec![(participating, N::from(3u64)), (total, -N::from(2u64))],
+            Some(-N::one()),
+        );
+        base_chip.decompose_bits_le(&diff, bits + 2);
+    }
+}
+
+impl<C: CurveAffine, N: FieldExt, B: IntegerChipOps<C::Base, N>> AggregateChipOps<C, N>
+    for GeneralScalarEccContext<C, N, B>
+{
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_aggregate_by_bitfield() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381;
+    use halo2_proofs::pairing::bn256::Fr;
+    use halo2_proofs::pairing::group::Curve;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::negative_test::NegativeTest;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let points = (0..4)
+        .map(|_| bls12_381::G1Affine::generator() * bls12_381::Fr::random(&mut rng))
+        .collect::<Vec<_>>();
+    let pubkeys = points.iter().map(|p| p.to_affine()).collect::<Vec<_>>();
+    let participation = [true, false, true, true];
+
+    let test = NegativeTest::<Fr>::new();
+    let mut ctx = GeneralScalarEccContext::<bls12_381::G1Affine, Fr>::new(test.ctx.clone());
+    let assigned_pubkeys = pubkeys
+        .iter()
+        .map(|p| ctx.assign_non_zero_point(p))
+        .collect::<Vec<_>>();
+    let bitfield = participation
+        .iter()
+        .map(|b| ctx.native_ctx.borrow_mut().assign(Fr::from(*b as u64)))
+        .collect::<Vec<_>>();
+
+    let (aggregate, count) = ctx.aggregate_by_bitfield(&assigned_pubkeys, &bitfield);
+    ctx.aggregate_assert_at_least(&count, 3, count_bits(pubkeys.len() as u64));
+
+    let expected = (points[0] + points[2] + points[3]).to_affine();
+    let coordinates = expected.coordinates().unwrap();
+    assert_eq!(count.val, Fr::from(3u64));
+    assert_eq!(aggregate.z.0.val, Fr::zero());
+    assert_eq!(ctx.base_integer_ctx.get_w(&aggregate.x), *coordinates.x());
+    assert_eq!(ctx.base_integer_ctx.get_w(&aggregate.y), *coordinates.y());
+
+    let weights = [40u64, 10, 30, 20]
+        .iter()
+        .map(|w| ctx.native_ctx.borrow_mut().assign(Fr::from(*w)))
+        .collect::<Vec<_>>();
+    let (weighted, participating, total) =
+        ctx.aggregate_by_weight(&assigned_pubkeys, &bitfield, &weights);
+    ctx.aggregate_assert_supermajority(&participating, &total, WEIGHT_BITS + 2);
+
+    assert_eq!(participating.val, Fr::from(90u64));
+    assert_eq!(total.val, Fr::from(100u64));
+    assert_eq!(ctx.base_integer_ctx.get_w(&weighted.x), *coordinates.x());
+
+    assert_eq!(test.run(&[]), vec![]);
+}
@@ -8,11 +8,13 @@ use halo2_proofs::{
     plonk::{Advice, Column, ConstraintSystem, Fixed},
     poly::Rotation,
 };
+use num_bigint::BigUint;
 use std::marker::PhantomData;
 
 use crate::assign::{AssignedCondition, AssignedValue, Chip, ValueSchema};
 use crate::context::Context;
 use crate::pair;
+use crate::utils::field_to_bn;
 
 pub const VAR_COLUMNS: usize = 5;
 pub const MUL_COLUMNS: usize = 2;
@@ -87,6 +89,11 @@ impl<N: FieldExt> BaseChip<N> {
     }
 }
 
+// Word of little-endian bits `w` rotated right by `r`.
+pub fn rotate_right<T: Copy>(w: &[T], r: usize) -> Vec<T> {
+    (0..w.len()).map(|i| w[(i + r) % w.len()]).collect()
+}
+
 pub trait BaseChipOps<N: FieldExt> {
     fn var_columns(&mut self) -> usize;
     fn mul_columns(&mut self) -> usize;
@@ -391,6 +398,63 @@ pub trait BaseChipOps<N: FieldExt> {
         );
     }
 
+    // Little-endian bits of `a`, constrained to recompose to `a`.
+    fn decompose_bits_le(
+        &mut self,
+        a: &AssignedValue<N>,
+        bits: usize,
+    ) -> Vec<AssignedCondition<N>> {
+        assert!(bits > 0);
+
+        let v = field_to_bn(&a.val);
+        let bits_le = (0..bits as u64)
+            .map(|i| self.assign_bit(v.bit(i).into()))
+            .collect::<Vec<_>>();
+
+        let mut coeff = N::one();
+        let schema = bits_le
+            .iter()
+            .map(|b| {
+                let c = coeff;
+                coeff = coeff.double();
+                (&b.0, c)
+            })
+            .collect();
+        let sum = self.sum_with_constant(schema, None);
+        self.assert_equal(a, &sum);
+
+        bits_le
+    }
+
+    // Constrains little-endian bits to encode a value at most `bound`, from
+    // the top bit down. A forged encoding makes the constraints
+    // unsatisfiable rather than stopping witness generation as `assert_false`
+    // would.
+    fn assert_bits_le_canonical(&mut self, bits_le: &[AssignedCondition<N>], bound: &BigUint) {
+        assert!(bits_le.len() as u64 >= bound.bits());
+
+        // While the prefix equals the bound's, a bit may only be set where
+        // the bound's is.
+        let mut eq = AssignedCondition(self.assign_constant(N::one()));
+        for (i, b) in bits_le.iter().enumerate().rev() {
+            if bound.bit(i as u64) {
+                eq = self.and(&eq, b);
+            } else {
+                let above = self.and(&eq, b);
+                self.assert_constant(&above.0, N::zero());
+            }
+        }
+    }
+
+    // `decompose_bits_le` over all the bits of the field, where both a and
+    // a + p fit otherwise.
+    fn decompose_canonical_bits_le(&mut self, a: &AssignedValue<N>) -> Vec<AssignedCondition<N>> {
+        let bound = field_to_bn(&-N::one());
+        let bits_le = self.decompose_bits_le(a, bound.bits() as usize);
+        self.assert_bits_le_canonical(&bits_le, &bound);
+        bits_le
+    }
+
     fn and(&mut self, a: &AssignedCondition<N>, b: &AssignedCondition<N>) -> AssignedCondition<N> {
         let res = self.mul(&a.0, &b.0);
 
@@ -445,6 +509,36 @@ pub trait BaseChipOps<N: FieldExt> {
         AssignedCondition(cells.1)
     }
 
+    // Sum of `words` and `constant` modulo 2^word_bits, for little-endian bit
+    // words of one width up to 64 bits. The sum stays far below the native
+    // modulus, so it is decomposed in one go.
+    fn word_add(
+        &mut self,
+        words: &[&[AssignedCondition<N>]],
+        constant: u64,
+    ) -> Vec<AssignedCondition<N>> {
+        let word_bits = words[0].len();
+        assert!(word_bits <= 64 && words.iter().all(|w| w.len() == word_bits));
+        assert!(word_bits == 64 || constant >> word_bits == 0);
+
+        let schema = words
+            .iter()
+            .flat_map(|w| {
+                w.iter()
+                    .enumerate()
+                    .map(|(i, b)| (&b.0, N::from(1u64 << i)))
+            })
+            .collect();
+        let sum = self.sum_with_constant(schema, Some(N::from(constant)));
+
+        // Below terms * 2^word_bits, a constant counting as a term.
+        let terms = words.len() + (constant != 0) as usize;
+        let carry_bits = (usize::BITS - (terms - 1).leading_zeros()) as usize;
+        let bits = self.decompose_bits_le(&sum, word_bits + carry_bits);
+
+        bits[..word_bits].to_vec()
+    }
+
     // if cond then a else b
     fn bisec(
         &mut self,
@@ -478,6 +572,19 @@ impl<N: FieldExt> BaseChipOps<N> for Context<N> {
         VAR_COLUMNS
     }
 
+    // Every constant takes one row per context, later uses copy its cell.
+    fn assign_constant(&mut self, v: N) -> AssignedValue<N> {
+        let key = field_to_bn(&v);
+        if let Some(cell) = self.constants.get(&key) {
+            return *cell;
+        }
+
+        let one = N::one();
+        let cell = self.one_line_add(vec![pair!(v, -one)], Some(v))[0];
+        self.constants.insert(key, cell);
+        cell
+    }
+
     fn mul_columns(&mut self) -> usize {
         MUL_COLUMNS
     }
@@ -575,3 +682,101 @@ impl<N: FieldExt> BaseChipOps<N> for Context<N> {
         }
     }
 }
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_constants_are_assigned_once() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    let mut ctx = Context::<Fr>::new();
+    let a = ctx.assign_constant(Fr::from(7u64));
+    let zero = ctx.assign_constant(Fr::zero());
+    let rows = ctx.base_offset;
+
+    assert_eq!(ctx.assign_constant(Fr::from(7u64)).cell, a.cell);
+    assert_eq!(ctx.assign_constant(Fr::zero()).cell, zero.cell);
+    assert_eq!(ctx.base_offset, rows);
+
+    // uses of the shared cell are copy constrained
+    let sum = ctx.sum_with_constant(vec![(&a, Fr::one()), (&zero, Fr::one())], None);
+    assert_eq!(sum.val, Fr::from(7u64));
+    assert_eq!(ctx.records.lock().unwrap().permutations.len(), 2);
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_canonical_bits_reject_value_plus_modulus() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::NegativeTest;
+
+    let v = field_to_bn(&Fr::from(5u64));
+    let p = field_to_bn(&-Fr::one()) + 1u64;
+    let bits = p.bits();
+
+    for (encoded, valid) in [(v.clone(), true), (v + p, false)] {
+        let mut test = NegativeTest::<Fr>::new();
+        test.gadget("canonical", |ctx| {
+            let mut ctx = ctx.borrow_mut();
+            let bits = (0..bits)
+                .map(|i| ctx.assign_bit(Fr::from(encoded.bit(i) as u64)))
+                .collect::<Vec<_>>();
+            ctx.assert_bits_le_canonical(&bits, &(&p - 1u64));
+        });
+        assert_eq!(test.run(&[]).is_empty(), valid);
+    }
+
+    // the honest decomposition is accepted
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("decompose", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let a = ctx.assign(-Fr::one());
+        ctx.decompose_canonical_bits_le(&a);
+    });
+    assert_eq!(test.run(&[]), vec![]);
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_word_add_wraps_at_the_word_width() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::NegativeTest;
+
+    let value = |bits: &[AssignedCondition<Fr>]| {
+        bits.iter()
+            .rev()
+            .fold(0u128, |acc, b| acc * 2 + (b.0.val == Fr::one()) as u128)
+    };
+
+    for (word_bits, words, constant) in [
+        (8, vec![0xffu64, 0x01, 0x80], 0x7fu64),
+        (32, vec![0xdeadbeef, 0xffffffff], 0),
+        (64, vec![u64::MAX; 4], u64::MAX),
+    ] {
+        let mut test = NegativeTest::<Fr>::new();
+        let sum = test.gadget("word_add", |ctx| {
+            let mut ctx = ctx.borrow_mut();
+            let words = words
+                .iter()
+                .map(|w| {
+                    (0..word_bits)
+                        .map(|i| ctx.assign_bit(Fr::from((w >> i) & 1)))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let words = words.iter().map(|w| &w[..]).collect::<Vec<_>>();
+            ctx.word_add(&words, constant)
+        });
+
+        let expected = words
+            .iter()
+            .chain([constant].iter())
+            .fold(0u128, |acc, w| acc + *w as u128)
+            % (1u128 << word_bits);
+        assert_eq!(sum.len(), word_bits);
+        assert_eq!(value(&sum), expected);
+        assert_eq!(test.run(&[]), vec![]);
+    }
+}
@@ -0,0 +1,151 @@
+/*
+  Boneh-Drijvers-Neven aggregation as kyber's sign/bdn (used by drand): every
+  pubkey is weighted by a coefficient hashed from the whole key set,
+  apk = sum (c_i + 1) * pk_i, so a key chosen after the others can't cancel
+  them out and no proof of possession is needed. The coefficients are read
+  from BLAKE2Xs(pk_1 || ... || pk_n) over the compressed pubkeys, 16 bytes
+  each, little-endian and reduced mod 2^128 - 1. The pubkeys must be in G1,
+  see the subgroup checks.
+*/
+
+use halo2_proofs::arithmetic::{Field, FieldExt};
+use halo2_proofs::pairing::bls12_381::{Fq, Fr, G1Affine};
+
+use super::base_chip::BaseChipOps;
+use super::blake2::Blake2ChipOps;
+use super::compress::CompressChipOps;
+use super::ecc_chip::EccChipScalarOps;
+use super::integer_chip::IntegerChipOps;
+use crate::assign::{AssignedInteger, AssignedPoint};
+use crate::context::GeneralScalarEccContext;
+
+pub const BDN_EXPONENT_BITS: usize = 128;
+
+pub trait BdnChipOps<N: FieldExt>:
+    EccChipScalarOps<G1Affine, N, AssignedScalar = AssignedInteger<Fr, N>>
+{
+    // The weights c_i + 1 of the pubkeys.
+    fn bdn_exponents(
+        &mut self,
+        pubkeys: &[AssignedPoint<G1Affine, N>],
+    ) -> Vec<AssignedInteger<Fr, N>>;
+
+    fn bdn_aggregate(
+        &mut self,
+        pubkeys: &[AssignedPoint<G1Affine, N>],
+    ) -> AssignedPoint<G1Affine, N> {
+        let exponents = self.bdn_exponents(pubkeys);
+        self.msm(&pubkeys.to_vec(), &exponents)
+    }
+}
+
+impl<N: FieldExt, B: IntegerChipOps<Fq, N>> BdnChipOps<N>
+    for GeneralScalarEccContext<G1Affine, N, B>
+{
+    fn bdn_exponents(
+        &mut self,
+        pubkeys: &[AssignedPoint<G1Affine, N>],
+    ) -> Vec<AssignedInteger<Fr, N>> {
+        let key_set = pubkeys
+            .iter()
+            .map(|pk| self.ecc_compress(pk))
+            .collect::<Vec<_>>()
+            .concat();
+        let out = self
+            .native_ctx
+            .borrow_mut()
+            .blake2xs(&key_set, pubkeys.len() * BDN_EXPONENT_BITS / 8);
+        let one = self.scalar_integer_ctx.assign_int_constant(Fr::one());
+
+        // The chip layout is little-endian already. v mod 2^128 - 1 is v but
+        // for v = 2^128 - 1, whose coefficient is 0 and weight 1.
+        out.chunks(BDN_EXPONENT_BITS)
+            .map(|bits| {
+                let v = self.scalar_integer_ctx.int_from_bits_le(bits);
+                let is_max = {
+                    let mut native = self.native_ctx.borrow_mut();
+                    bits[1..]
+                        .iter()
+                        .fold(bits[0], |acc, bit| native.and(&acc, bit))
+                };
+                let t = self.scalar_integer_ctx.int_add(&v, &one);
+                self.scalar_integer_ctx.bisec_int(&is_max, &one, &t)
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_bdn_aggregate_matches_kyber() {
+    use halo2_proofs::arithmetic::CurveAffine;
+    use halo2_proofs::pairing::bn256::Fr as BnFr;
+    use halo2_proofs::pairing::group::Curve;
+    use num_bigint::BigUint;
+
+    use super::ecc_chip::EccChipBaseOps;
+    use crate::negative_test::NegativeTest;
+    use crate::utils::{bn_to_field, field_to_bn};
+
+    // Pubkeys k * G for k = 1, 2, 3, with the BLAKE2Xs words v_i and the
+    // compressed apk of kyber's bdn.AggregatePublicKeys, computed with a
+    // standalone BLAKE2Xs and BLS12-381 implementation.
+    let v: [u128; 3] = [
+        0x1ad6a4986b95997561882f038ff08bd6,
+        0xa067df38969dce8d54a4d9f35ac504a0,
+        0x1607c6dcb5e0c6dbee30b1a2edb61a1c,
+    ];
+    let expected_apk = "823c2c5e536dc393bec982c57a7c4c070a508aa23d673466553486d918a7288b\
+                        a80368854174dcda90d700754ef9d84b";
+
+    let pubkeys = (1..=3u64)
+        .map(|k| (G1Affine::generator() * Fr::from(k)).to_affine())
+        .collect::<Vec<_>>();
+    let weights = v
+        .iter()
+        .map(|v| bn_to_field(&BigUint::from(v % u128::MAX + 1)))
+        .collect::<Vec<_>>();
+
+    let mut test = NegativeTest::<BnFr>::new();
+    let (exponents, apk) = test.gadget("bdn_aggregate", |ctx| {
+        let mut ctx = GeneralScalarEccContext::<G1Affine, BnFr>::new(ctx.clone());
+        let assigned = pubkeys
+            .iter()
+            .map(|pk| ctx.assign_non_zero_point(pk))
+            .collect::<Vec<_>>();
+        let exponents = ctx
+            .bdn_exponents(&assigned)
+            .iter()
+            .map(|t| ctx.scalar_integer_ctx.get_w(t))
+            .collect::<Vec<_>>();
+        let apk = ctx.bdn_aggregate(&assigned);
+        assert_eq!(apk.z.0.val, BnFr::zero());
+        let apk = (
+            ctx.base_integer_ctx.get_w(&apk.x),
+            ctx.base_integer_ctx.get_w(&apk.y),
+        );
+        (exponents, apk)
+    });
+    assert_eq!(exponents, weights);
+    for t in exponents.iter() {
+        assert!(field_to_bn(t).bits() <= BDN_EXPONENT_BITS as u64);
+    }
+
+    let expected = pubkeys
+        .iter()
+        .zip(weights.iter())
+        .fold(G1Affine::generator() * Fr::zero(), |acc, (pk, t)| {
+            acc + pk * t
+        })
+        .to_affine();
+    let compressed = expected
+        .to_compressed()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    assert_eq!(compressed, expected_apk);
+    let coordinates = expected.coordinates().unwrap();
+    assert_eq!(apk, (*coordinates.x(), *coordinates.y()));
+
+    assert_eq!(test.run(&[]), vec![]);
+}
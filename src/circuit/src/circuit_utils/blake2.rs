@@ -0,0 +1,324 @@
+use halo2_proofs::arithmetic::FieldExt;
+
+use super::base_chip::{rotate_right, BaseChipOps};
+use crate::assign::AssignedCondition;
+use crate::context::Context;
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+// (a, b, c, d) of the column steps, then of the diagonal steps
+const G_LANES: [[usize; 4]; 8] = [
+    [0, 4, 8, 12],
+    [1, 5, 9, 13],
+    [2, 6, 10, 14],
+    [3, 7, 11, 15],
+    [0, 5, 10, 15],
+    [1, 6, 11, 12],
+    [2, 7, 8, 13],
+    [3, 4, 9, 14],
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Blake2Params {
+    pub word_bits: usize,
+    pub rounds: usize,
+    pub rotations: [usize; 4],
+    pub iv: [u64; 8],
+}
+
+impl Blake2Params {
+    pub fn block_bytes(&self) -> usize {
+        self.word_bits * 2
+    }
+}
+
+pub const BLAKE2B: Blake2Params = Blake2Params {
+    word_bits: 64,
+    rounds: 12,
+    rotations: [32, 24, 16, 63],
+    iv: [
+        0x6a09e667f3bcc908,
+        0xbb67ae8584caa73b,
+        0x3c6ef372fe94f82b,
+        0xa54ff53a5f1d36f1,
+        0x510e527fade682d1,
+        0x9b05688c2b3e6c1f,
+        0x1f83d9abfb41bd6b,
+        0x5be0cd19137e2179,
+    ],
+};
+
+pub const BLAKE2S: Blake2Params = Blake2Params {
+    word_bits: 32,
+    rounds: 10,
+    rotations: [16, 12, 8, 7],
+    iv: [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ],
+};
+
+// Words are little-endian bit vectors. BLAKE2 reads words little-endian from
+// the message, so with the keccak chip layout (bits little-endian inside each
+// byte, bytes in message order) word bit i is simply message bit i of the
+// word's slice.
+pub trait Blake2ChipOps<N: FieldExt>: BaseChipOps<N> {
+    fn blake2_xor(
+        &mut self,
+        a: &[AssignedCondition<N>],
+        b: &[AssignedCondition<N>],
+    ) -> Vec<AssignedCondition<N>> {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| self.xor(a, b))
+            .collect()
+    }
+
+    fn blake2_g(
+        &mut self,
+        params: &Blake2Params,
+        v: &mut [Vec<AssignedCondition<N>>],
+        [a, b, c, d]: [usize; 4],
+        x: &[AssignedCondition<N>],
+        y: &[AssignedCondition<N>],
+    ) {
+        let [r1, r2, r3, r4] = params.rotations;
+
+        v[a] = self.word_add(&[&v[a][..], &v[b][..], x], 0);
+        v[d] = rotate_right(&self.blake2_xor(&v[d], &v[a]), r1);
+        v[c] = self.word_add(&[&v[c][..], &v[d][..]], 0);
+        v[b] = rotate_right(&self.blake2_xor(&v[b], &v[c]), r2);
+        v[a] = self.word_add(&[&v[a][..], &v[b][..], y], 0);
+        v[d] = rotate_right(&self.blake2_xor(&v[d], &v[a]), r3);
+        v[c] = self.word_add(&[&v[c][..], &v[d][..]], 0);
+        v[b] = rotate_right(&self.blake2_xor(&v[b], &v[c]), r4);
+    }
+
+    // `iv` is the lower half of the work vector, with the byte counter and
+    // the final block flag already mixed in.
+    fn blake2_compress(
+        &mut self,
+        params: &Blake2Params,
+        h: &[Vec<AssignedCondition<N>>],
+        block: &[AssignedCondition<N>],
+        iv: Vec<Vec<AssignedCondition<N>>>,
+    ) -> Vec<Vec<AssignedCondition<N>>> {
+        assert_eq!(h.len(), 8);
+        assert_eq!(iv.len(), 8);
+        assert_eq!(block.len(), params.block_bytes() * 8);
+
+        let m = block
+            .chunks(params.word_bits)
+            .map(|w| w.to_vec())
+            .collect::<Vec<_>>();
+
+        let mut v = h.to_vec();
+        v.extend(iv);
+        for r in 0..params.rounds {
+            let s = &SIGMA[r % 10];
+            for (i, lanes) in G_LANES.iter().enumerate() {
+                self.blake2_g(params, &mut v, *lanes, &m[s[2 * i]], &m[s[2 * i + 1]]);
+            }
+        }
+
+        (0..8)
+            .map(|i| {
+                let t = self.blake2_xor(&h[i], &v[i]);
+                self.blake2_xor(&t, &v[i + 8])
+            })
+            .collect()
+    }
+
+    // Unkeyed BLAKE2 of a byte aligned bit string, returns `out_bytes` of
+    // digest in the same layout.
+    fn blake2(
+        &mut self,
+        params: &Blake2Params,
+        message: &[AssignedCondition<N>],
+        out_bytes: usize,
+    ) -> Vec<AssignedCondition<N>> {
+        // sequential mode, fanout and depth 1
+        let mut param_block = [0; 8];
+        param_block[0] = 0x01010000 ^ out_bytes as u64;
+        self.blake2_node(params, param_block, message, out_bytes)
+    }
+
+    // Same, with the parameter block given as words, for tree and XOF modes.
+    fn blake2_node(
+        &mut self,
+        params: &Blake2Params,
+        param_block: [u64; 8],
+        message: &[AssignedCondition<N>],
+        out_bytes: usize,
+    ) -> Vec<AssignedCondition<N>> {
+        assert_eq!(message.len() % 8, 0);
+        assert!(out_bytes > 0 && out_bytes <= params.word_bits);
+
+        let zero = AssignedCondition(self.assign_constant(N::zero()));
+        let one = AssignedCondition(self.assign_constant(N::one()));
+        let constant_word = |v: u64| {
+            (0..params.word_bits)
+                .map(|i| if (v >> i) & 1 == 1 { one } else { zero })
+                .collect::<Vec<_>>()
+        };
+        let mask = u64::MAX >> (64 - params.word_bits);
+
+        let message_bytes = message.len() / 8;
+        let block_bytes = params.block_bytes();
+        let blocks = usize::max(1, (message_bytes + block_bytes - 1) / block_bytes);
+        let mut padded = message.to_vec();
+        padded.resize(blocks * block_bytes * 8, zero);
+
+        let mut h = params
+            .iv
+            .iter()
+            .zip(param_block.iter())
+            .map(|(w, p)| constant_word(w ^ p))
+            .collect::<Vec<_>>();
+
+        for (k, block) in padded.chunks(block_bytes * 8).enumerate() {
+            let counter = usize::min((k + 1) * block_bytes, message_bytes) as u128;
+            let is_last = k == blocks - 1;
+
+            let iv = params
+                .iv
+                .iter()
+                .enumerate()
+                .map(|(i, w)| match i {
+                    4 => constant_word(w ^ (counter as u64 & mask)),
+                    5 => constant_word(w ^ ((counter >> params.word_bits) as u64 & mask)),
+                    6 if is_last => constant_word(w ^ mask),
+                    _ => constant_word(*w),
+                })
+                .collect();
+
+            h = self.blake2_compress(params, &h, block, iv);
+        }
+
+        h.concat()[..out_bytes * 8].to_vec()
+    }
+
+    fn blake2b(
+        &mut self,
+        message: &[AssignedCondition<N>],
+        out_bytes: usize,
+    ) -> Vec<AssignedCondition<N>> {
+        self.blake2(&BLAKE2B, message, out_bytes)
+    }
+
+    fn blake2s(
+        &mut self,
+        message: &[AssignedCondition<N>],
+        out_bytes: usize,
+    ) -> Vec<AssignedCondition<N>> {
+        self.blake2(&BLAKE2S, message, out_bytes)
+    }
+
+    // BLAKE2Xs with the output length left unknown (0xffff), as Go's
+    // blake2s.NewXOF(blake2s.OutputLengthUnknown, nil): a root BLAKE2s of
+    // the message, then BLAKE2s(root) at node offsets 0, 1, ... for every 32
+    // bytes of output.
+    fn blake2xs(
+        &mut self,
+        message: &[AssignedCondition<N>],
+        out_bytes: usize,
+    ) -> Vec<AssignedCondition<N>> {
+        const XOF_LENGTH_UNKNOWN: u64 = 0xffff;
+
+        let mut root_params = [0; 8];
+        root_params[0] = 0x01010020;
+        root_params[3] = XOF_LENGTH_UNKNOWN;
+        let root = self.blake2_node(&BLAKE2S, root_params, message, 32);
+
+        // digest and leaf length 32, fanout and depth 0, inner length 32
+        let mut node_params = [0; 8];
+        node_params[0] = 0x20;
+        node_params[1] = 0x20;
+        node_params[3] = XOF_LENGTH_UNKNOWN | 0x20 << 24;
+
+        let mut out = vec![];
+        for i in 0..(out_bytes + 31) / 32 {
+            node_params[2] = i as u64;
+            out.extend(self.blake2_node(&BLAKE2S, node_params, &root, 32));
+        }
+        out.truncate(out_bytes * 8);
+        out
+    }
+}
+
+impl<N: FieldExt> Blake2ChipOps<N> for Context<N> {}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_blake2_chip_matches_test_vectors() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::{assign_bytes, to_bytes, NegativeTest};
+
+    let vectors = [
+        (
+            BLAKE2B,
+            64,
+            "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+             7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923",
+        ),
+        (
+            BLAKE2B,
+            32,
+            "bddd813c634239723171ef3fee98579b94964e3bb1cb3e427262c8c068d52319",
+        ),
+        (
+            BLAKE2S,
+            32,
+            "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982",
+        ),
+    ];
+
+    for (params, out_bytes, expected) in vectors {
+        let mut test = NegativeTest::<Fr>::new();
+        let digest = test.gadget("blake2", |ctx| {
+            let mut ctx = ctx.borrow_mut();
+            let bits = assign_bytes(&mut ctx, b"abc");
+            ctx.blake2(&params, &bits, out_bytes)
+        });
+        let hex = to_bytes(&digest)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        assert_eq!(hex, expected);
+
+        assert_eq!(test.run(&[]), vec![]);
+        let flipped = Fr::one() - digest[0].0.val;
+        assert!(!test.run(&[(digest[0].0.cell, flipped)]).is_empty());
+    }
+
+    // two output nodes, computed with a standalone BLAKE2Xs
+    let mut test = NegativeTest::<Fr>::new();
+    let digest = test.gadget("blake2xs", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let bits = assign_bytes(&mut ctx, b"abc");
+        ctx.blake2xs(&bits, 64)
+    });
+    let hex = to_bytes(&digest)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    assert_eq!(
+        hex,
+        "bf5c4f309fde8a62195bc8364ceea81e84eb9330579270c5737b9300085b6149\
+         5576fef12a5cfa717343bff2bb2461d733fc71c0c51a60392e4d2f84218b1351"
+    );
+    assert_eq!(test.run(&[]), vec![]);
+}
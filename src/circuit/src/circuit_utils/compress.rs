@@ -0,0 +1,354 @@
+/*
+  Compressed encodings of BLS12-381 points (the zcash format used by the BLS
+  signature standard): big-endian x, with the compression, infinity and sign
+  flags in the three top bits of the first byte. The sign is set when y is the
+  lexicographically largest of y and -y. G2 points write x.c1 then x.c0.
+  Outputs are bytes in the hash chip bit layout, so they can feed the hashes.
+
+  Decompression only accepts canonical encodings of non-identity points: a
+  cleared compression flag, a set infinity flag, flags on x.c0, x >= p or an
+  x off the curve leave the constraints unsatisfiable.
+*/
+
+use halo2_proofs::arithmetic::{CurveAffine, Field, FieldExt};
+use halo2_proofs::pairing::bls12_381::{Fq, Fq2, G1Affine};
+
+use super::base_chip::BaseChipOps;
+use super::ecc_chip::EccChipBaseOps;
+use super::fq2::Fq2ChipOps;
+use super::integer_chip::IntegerChipOps;
+use crate::assign::{
+    AssignedCondition, AssignedFq2, AssignedG2Affine, AssignedInteger, AssignedPoint,
+};
+use crate::context::GeneralScalarEccContext;
+use crate::utils::{bn_to_field, field_to_bn};
+
+pub const FQ_BYTES: usize = 48;
+pub const FQ_BITS: usize = 381;
+
+// y > (p - 1) / 2
+fn is_lexicographically_largest(y: &Fq) -> bool {
+    field_to_bn(y) > field_to_bn(&-Fq::one()) >> 1
+}
+
+fn is_fq2_lexicographically_largest(y: &Fq2) -> bool {
+    is_lexicographically_largest(&y.c1)
+        || (y.c1 == Fq::zero() && is_lexicographically_largest(&y.c0))
+}
+
+pub trait CompressChipOps<N: FieldExt>: EccChipBaseOps<G1Affine, N> {
+    // a > (p - 1) / 2
+    fn fq_lexicographically_largest(&mut self, a: &AssignedInteger<Fq, N>) -> AssignedCondition<N> {
+        let half = bn_to_field(&(field_to_bn(&-Fq::one()) >> 1));
+        let half = self.base_integer_chip().assign_int_constant(half);
+        let a = self.base_integer_chip().int_canonical(a);
+        self.base_integer_chip().int_lt(&half, &a)
+    }
+
+    // c1 first, then c0 when c1 is zero.
+    fn fq2_lexicographically_largest(&mut self, a: &AssignedFq2<Fq, N>) -> AssignedCondition<N> {
+        let c0_largest = self.fq_lexicographically_largest(&a.0);
+        let c1_largest = self.fq_lexicographically_largest(&a.1);
+        let c1_is_zero = self.base_integer_chip().is_int_zero(&a.1);
+
+        let mut base_chip = self.base_integer_chip().base_chip();
+        let by_c0 = base_chip.and(&c1_is_zero, &c0_largest);
+        base_chip.or(&c1_largest, &by_c0)
+    }
+
+    // Little-endian bits of the canonical `a`, padded to FQ_BYTES bytes.
+    fn fq_to_padded_bits_le(&mut self, a: &AssignedInteger<Fq, N>) -> Vec<AssignedCondition<N>> {
+        let zero = AssignedCondition(
+            self.base_integer_chip()
+                .base_chip()
+                .assign_constant(N::zero()),
+        );
+        let mut bits = self.base_integer_chip().int_to_bits_le(a);
+        bits.resize(FQ_BYTES * 8, zero);
+        bits
+    }
+
+    // Big-endian bytes of the padded bits, with the flags set on the first.
+    fn flagged_be_bytes(
+        &mut self,
+        mut bits: Vec<AssignedCondition<N>>,
+        sign: &AssignedCondition<N>,
+    ) -> Vec<AssignedCondition<N>> {
+        let one = AssignedCondition(
+            self.base_integer_chip()
+                .base_chip()
+                .assign_constant(N::one()),
+        );
+        let len = bits.len();
+        // compression, infinity stays clear, sign
+        bits[len - 1] = one;
+        bits[len - 3] = *sign;
+
+        bits.chunks(8).rev().flat_map(|b| b.to_vec()).collect()
+    }
+
+    // The 48 bytes of a non-identity point.
+    fn ecc_compress(&mut self, a: &AssignedPoint<G1Affine, N>) -> Vec<AssignedCondition<N>> {
+        self.base_integer_chip().base_chip().assert_false(&a.z);
+
+        let sign = self.fq_lexicographically_largest(&a.y);
+        let bits = self.fq_to_padded_bits_le(&a.x);
+        self.flagged_be_bytes(bits, &sign)
+    }
+
+    // The 96 bytes of a non-identity point, y is ordered by c1 first.
+    fn ecc_g2_compress(&mut self, a: &AssignedG2Affine<G1Affine, N>) -> Vec<AssignedCondition<N>> {
+        self.base_integer_chip().base_chip().assert_false(&a.z);
+
+        let sign = self.fq2_lexicographically_largest(&a.y);
+        let bits = self.fq_to_padded_bits_le(&a.x.1);
+        let mut bytes = self.flagged_be_bytes(bits, &sign);
+        let bits = self.fq_to_padded_bits_le(&a.x.0);
+        bytes.extend(bits.chunks(8).rev().flat_map(|b| b.to_vec()));
+        bytes
+    }
+
+    // x from FQ_BYTES big-endian bytes, constrained below p, with the sign,
+    // infinity and compression bits above it.
+    fn fq_from_be_bytes(
+        &mut self,
+        bytes: &[AssignedCondition<N>],
+    ) -> (AssignedInteger<Fq, N>, [AssignedCondition<N>; 3]) {
+        assert_eq!(bytes.len(), FQ_BYTES * 8);
+
+        let bits = bytes
+            .chunks(8)
+            .rev()
+            .flat_map(|b| b.to_vec())
+            .collect::<Vec<_>>();
+        let x = self
+            .base_integer_chip()
+            .int_from_canonical_bits_le(&bits[..FQ_BITS]);
+        (x, [bits[FQ_BITS], bits[FQ_BITS + 1], bits[FQ_BITS + 2]])
+    }
+
+    // Compression set, infinity clear. Not assert_false, a bad encoding must
+    // leave the constraints unsatisfiable rather than panic the prover.
+    fn assert_compressed_flags(&mut self, flags: &[AssignedCondition<N>; 3]) {
+        let mut base_chip = self.base_integer_chip().base_chip();
+        base_chip.assert_constant(&flags[1].0, N::zero());
+        base_chip.assert_true(&flags[2]);
+    }
+
+    fn ecc_decompress(&mut self, bytes: &[AssignedCondition<N>]) -> AssignedPoint<G1Affine, N> {
+        let (x, flags) = self.fq_from_be_bytes(bytes);
+        self.assert_compressed_flags(&flags);
+
+        // y is a witness, pinned by the curve equation and the sign
+        let y = {
+            let x = self.base_integer_chip().get_w(&x);
+            let y = Option::from((x.square() * x + G1Affine::b()).sqrt()).unwrap_or(Fq::zero());
+            let y = if is_lexicographically_largest(&y) == (flags[0].0.val == N::one()) {
+                y
+            } else {
+                -y
+            };
+            self.base_integer_chip().assign_w(&field_to_bn(&y))
+        };
+        let largest = self.fq_lexicographically_largest(&y);
+        self.base_integer_chip()
+            .base_chip()
+            .assert_equal(&largest.0, &flags[0].0);
+
+        let b = self.base_integer_chip().assign_int_constant(G1Affine::b());
+        let y2 = self.base_integer_chip().int_square(&y);
+        let x2 = self.base_integer_chip().int_square(&x);
+        let x3 = self.base_integer_chip().int_mul(&x2, &x);
+        let right = self.base_integer_chip().int_add(&x3, &b);
+        self.base_integer_chip().assert_int_equal(&y2, &right);
+
+        let z = self
+            .base_integer_chip()
+            .base_chip()
+            .assign_constant(N::zero());
+        AssignedPoint::new(x, y, AssignedCondition(z))
+    }
+
+    fn ecc_g2_decompress(
+        &mut self,
+        bytes: &[AssignedCondition<N>],
+    ) -> AssignedG2Affine<G1Affine, N> {
+        assert_eq!(bytes.len(), 2 * FQ_BYTES * 8);
+
+        let (x1, flags) = self.fq_from_be_bytes(&bytes[..FQ_BYTES * 8]);
+        self.assert_compressed_flags(&flags);
+        let (x0, no_flags) = self.fq_from_be_bytes(&bytes[FQ_BYTES * 8..]);
+        for flag in no_flags.iter() {
+            self.base_integer_chip()
+                .base_chip()
+                .assert_constant(&flag.0, N::zero());
+        }
+        let x = (x0, x1);
+
+        // E': y^2 = x^3 + 4(u + 1)
+        let b = Fq2 {
+            c0: Fq::from(4),
+            c1: Fq::from(4),
+        };
+        let y = {
+            let x = Fq2 {
+                c0: self.base_integer_chip().get_w(&x.0),
+                c1: self.base_integer_chip().get_w(&x.1),
+            };
+            let y = Option::from((x.square() * x + b).sqrt()).unwrap_or(Fq2::zero());
+            let y = if is_fq2_lexicographically_largest(&y) == (flags[0].0.val == N::one()) {
+                y
+            } else {
+                -y
+            };
+            self.fq2_assign((y.c0, y.c1))
+        };
+        let largest = self.fq2_lexicographically_largest(&y);
+        self.base_integer_chip()
+            .base_chip()
+            .assert_equal(&largest.0, &flags[0].0);
+
+        let b = self.fq2_assign_constant((b.c0, b.c1));
+        let y2 = self.fq2_square(&y);
+        let x2 = self.fq2_square(&x);
+        let x3 = self.fq2_mul(&x2, &x);
+        let right = self.fq2_add(&x3, &b);
+        self.fq2_assert_equal(&y2, &right);
+
+        let z = self
+            .base_integer_chip()
+            .base_chip()
+            .assign_constant(N::zero());
+        AssignedG2Affine::new(x, y, AssignedCondition(z))
+    }
+}
+
+impl<N: FieldExt, B: IntegerChipOps<Fq, N>> CompressChipOps<N>
+    for GeneralScalarEccContext<G1Affine, N, B>
+{
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_compress_matches_native() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use halo2_proofs::pairing::bls12_381::{Fr as BlsFr, G2Affine};
+    use halo2_proofs::pairing::bn256::Fr;
+    use halo2_proofs::pairing::group::Curve;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::context::Context;
+    use crate::negative_test::to_bytes;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let ctx = Rc::new(RefCell::new(Context::new()));
+    let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(ctx);
+
+    // a point and its negation cover both signs
+    let g1 = (G1Affine::generator() * BlsFr::random(&mut rng)).to_affine();
+    for p in [g1, -g1] {
+        let assigned = ctx.assign_non_zero_point(&p);
+        let bits = ctx.ecc_compress(&assigned);
+        assert_eq!(to_bytes(&bits), p.to_compressed().to_vec());
+    }
+
+    let g2 = (G2Affine::generator() * BlsFr::random(&mut rng)).to_affine();
+    for q in [g2, -g2] {
+        let assigned = ctx.assign_non_identity_constant_g2(&((q.x.c0, q.x.c1), (q.y.c0, q.y.c1)));
+        let bits = ctx.ecc_g2_compress(&assigned);
+        assert_eq!(to_bytes(&bits), q.to_compressed().to_vec());
+    }
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_decompress_rejects_non_canonical_encodings() {
+    use halo2_proofs::pairing::bls12_381::{Fr as BlsFr, G2Affine};
+    use halo2_proofs::pairing::bn256::Fr;
+    use halo2_proofs::pairing::group::Curve;
+    use num_bigint::BigUint;
+
+    use crate::negative_test::{assign_bytes, NegativeTest};
+
+    let p = field_to_bn(&-Fq::one()) + 1u64;
+    let r = field_to_bn(&-BlsFr::one()) + 1u64;
+    // multiples of the generators whose x + p still fits in FQ_BITS
+    let fits = |x: &Fq| field_to_bn(x) + &p < BigUint::from(1u64) << FQ_BITS;
+    let g1 = (1u64..)
+        .map(|k| (G1Affine::generator() * BlsFr::from(k)).to_affine())
+        .find(|g| fits(&g.x))
+        .unwrap();
+    let g2 = (1u64..)
+        .map(|k| (G2Affine::generator() * BlsFr::from(k)).to_affine())
+        .find(|g| fits(&g.x.c0))
+        .unwrap();
+
+    // Each encoding in its own test, witness bits in the hash chip layout.
+    let decompress = |bytes: Vec<u8>| {
+        let mut test = NegativeTest::<Fr>::new();
+        test.gadget("decompress", |ctx| {
+            let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(ctx.clone());
+            let bits = assign_bytes(&mut ctx.native_ctx.borrow_mut(), &bytes);
+            if bits.len() == FQ_BYTES * 8 {
+                ctx.ecc_decompress(&bits);
+            } else {
+                ctx.ecc_g2_decompress(&bits);
+            }
+        });
+        test.run(&[]).is_empty()
+    };
+    // big-endian x + p
+    let shifted = |x: &[u8]| {
+        let x = BigUint::from_bytes_be(x) + &p;
+        let mut bytes = x.to_bytes_be();
+        while bytes.len() < FQ_BYTES {
+            bytes.insert(0, 0);
+        }
+        bytes
+    };
+
+    let canonical = g1.to_compressed().to_vec();
+    assert!(decompress(canonical.clone()));
+    assert!(decompress((-g1).to_compressed().to_vec()));
+    let mut x = canonical.clone();
+    x[0] &= 0x1f;
+    let mut bytes = shifted(&x);
+    bytes[0] |= canonical[0] & 0xe0;
+    assert!(!decompress(bytes));
+    let mut bytes = canonical.clone();
+    bytes[0] &= 0x7f;
+    assert!(!decompress(bytes));
+    let mut bytes = canonical.clone();
+    bytes[0] |= 0x40;
+    assert!(!decompress(bytes));
+
+    let canonical = g2.to_compressed().to_vec();
+    assert!(decompress(canonical.clone()));
+    let mut bytes = canonical.clone();
+    bytes[FQ_BYTES] |= 0x80;
+    assert!(!decompress(bytes));
+    let mut bytes = canonical.clone();
+    bytes.splice(FQ_BYTES.., shifted(&canonical[FQ_BYTES..]));
+    assert!(!decompress(bytes));
+
+    // scalars given as bits are not reduced either
+    for (s, valid) in [(&r - 1u64, true), (&r + 1u64, false)] {
+        let mut test = NegativeTest::<Fr>::new();
+        test.gadget("scalar", |ctx| {
+            let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(ctx.clone());
+            let bits = (0..256)
+                .map(|i| {
+                    ctx.native_ctx
+                        .borrow_mut()
+                        .assign_bit(Fr::from(s.bit(i) as u64))
+                })
+                .collect::<Vec<_>>();
+            ctx.scalar_integer_ctx
+                .int_from_canonical_bits_le(&bits[..255]);
+        });
+        assert_eq!(test.run(&[]).is_empty(), valid);
+    }
+}
@@ -7,7 +7,7 @@ use halo2_proofs::arithmetic::{BaseExt, CurveAffine, Field, FieldExt};
 use super::integer_chip::IntegerChipOps;
 use crate::assign::{
     AssignedCondition, AssignedCurvature, AssignedExtCurvature, AssignedFq2, AssignedG2Affine,
-    AssignedG2WithCurvature, AssignedPoint, AssignedPointWithCurvature,
+    AssignedG2WithCurvature, AssignedPoint, AssignedPointWithCurvature, AssignedValue,
 };
 use crate::circuit_utils::fq2::Fq2ChipOps;
 use crate::utils::field_to_bn;
@@ -18,6 +18,12 @@ pub trait EccChipScalarOps<C: CurveAffine, N: FieldExt>: EccChipBaseOps<C, N> {
         &mut self,
         s: &Self::AssignedScalar,
     ) -> Vec<[AssignedCondition<N>; WINDOW_SIZE]>;
+    // Same layout as decompose_scalar, but the bits are those of the
+    // canonical representative, constrained below the group order.
+    fn decompose_scalar_canonical<const WINDOW_SIZE: usize>(
+        &mut self,
+        s: &Self::AssignedScalar,
+    ) -> Vec<[AssignedCondition<N>; WINDOW_SIZE]>;
     // like pippenger
     fn msm_batch_on_group(
         &mut self,
@@ -106,7 +112,16 @@ pub trait EccChipScalarOps<C: CurveAffine, N: FieldExt>: EccChipBaseOps<C, N> {
         points: &Vec<AssignedPoint<C, N>>,
         scalars: &Vec<Self::AssignedScalar>,
     ) -> AssignedPoint<C, N> {
-        const WINDOW_SIZE: usize = 4;
+        self.msm_windowed::<4>(points, scalars)
+    }
+
+    // Interleaved windowed double-and-add, each point gets a table of its
+    // 2^WINDOW_SIZE small multiples and each window one table pick.
+    fn msm_windowed<const WINDOW_SIZE: usize>(
+        &mut self,
+        points: &Vec<AssignedPoint<C, N>>,
+        scalars: &Vec<Self::AssignedScalar>,
+    ) -> AssignedPoint<C, N> {
         assert!(points.len() == scalars.len());
 
         // TODO: can be parallel
@@ -184,6 +199,59 @@ pub trait EccChipScalarOps<C: CurveAffine, N: FieldExt>: EccChipBaseOps<C, N> {
         acc.unwrap()
     }
 
+    // a * p + b * q with a joint table of i * p + j * q for the window
+    // digits i, j, so that each window costs one addition for both scalars.
+    fn ecc_shamir_mul<const WINDOW_SIZE: usize>(
+        &mut self,
+        p: &AssignedPoint<C, N>,
+        a: &Self::AssignedScalar,
+        q: &AssignedPoint<C, N>,
+        b: &Self::AssignedScalar,
+    ) -> AssignedPoint<C, N> {
+        let a_windows_in_be = self.decompose_scalar::<WINDOW_SIZE>(a);
+        let b_windows_in_be = self.decompose_scalar::<WINDOW_SIZE>(b);
+
+        // candidates[i + (j << WINDOW_SIZE)] = i * p + j * q
+        let mut candidates = vec![self.assign_identity()];
+        for j in 0..1 << WINDOW_SIZE {
+            for i in 0..1 << WINDOW_SIZE {
+                let c = match (i, j) {
+                    (0, 0) => continue,
+                    (0, _) => self.ecc_add(&candidates[(j - 1) << WINDOW_SIZE], q),
+                    _ => self.ecc_add(&candidates[i - 1 + (j << WINDOW_SIZE)], p),
+                };
+                let c = self.to_point_with_curvature(c);
+                candidates.push(c);
+            }
+        }
+
+        let mut acc: Option<AssignedPoint<C, N>> = None;
+        for (a_bits, b_bits) in a_windows_in_be.iter().zip(b_windows_in_be.iter()) {
+            let mut curr_candidates = candidates.clone();
+            for bit in a_bits.iter().chain(b_bits.iter()) {
+                curr_candidates = curr_candidates
+                    .chunks(2)
+                    .map(|it| self.bisec_point_with_curvature(bit, &it[1], &it[0]))
+                    .collect();
+            }
+            assert_eq!(curr_candidates.len(), 1);
+            let ci = curr_candidates.pop().unwrap();
+
+            acc = Some(match acc {
+                None => ci.to_point(),
+                Some(mut _acc) => {
+                    for _ in 0..WINDOW_SIZE {
+                        let t = self.to_point_with_curvature(_acc);
+                        _acc = self.ecc_double(&t);
+                    }
+                    self.ecc_add(&ci, &_acc)
+                }
+            });
+        }
+
+        acc.unwrap()
+    }
+
     fn msm(
         &mut self,
         points: &Vec<AssignedPoint<C, N>>,
@@ -200,13 +268,27 @@ pub trait EccChipScalarOps<C: CurveAffine, N: FieldExt>: EccChipBaseOps<C, N> {
         self.msm(&vec![a.clone()], &vec![s.clone()])
     }
 
+    fn ecc_mul_windowed<const WINDOW_SIZE: usize>(
+        &mut self,
+        a: &AssignedPoint<C, N>,
+        s: &Self::AssignedScalar,
+    ) -> AssignedPoint<C, N> {
+        self.msm_windowed::<WINDOW_SIZE>(&vec![a.clone()], &vec![s.clone()])
+    }
+
     fn ecc_g2_mul(
         &mut self,
         point: &AssignedG2Affine<C, N>,
         scalar: &Self::AssignedScalar,
     ) -> AssignedG2Affine<C, N> {
-        const WINDOW_SIZE: usize = 4;
+        self.ecc_g2_mul_windowed::<4>(point, scalar)
+    }
 
+    fn ecc_g2_mul_windowed<const WINDOW_SIZE: usize>(
+        &mut self,
+        point: &AssignedG2Affine<C, N>,
+        scalar: &Self::AssignedScalar,
+    ) -> AssignedG2Affine<C, N> {
         // TODO: can be parallel
         let windows_in_be = self.decompose_scalar::<WINDOW_SIZE>(scalar);
 
@@ -247,31 +329,19 @@ pub trait EccChipScalarOps<C: CurveAffine, N: FieldExt>: EccChipBaseOps<C, N> {
         let mut acc = None;
 
         // for each window
-        for wi in 0..windows_in_be.len() {
-            let mut inner_acc = None;
+        for bits in windows_in_be.iter() {
+            let ci = pick_candidate(self, bits);
 
-            // TODO: can be parallel
-            let ci = pick_candidate(self, &windows_in_be[wi]);
-            match inner_acc {
-                None => inner_acc = Some(ci.to_point()),
-                Some(_inner_acc) => {
-                    let p = self.ecc_g2_add(&ci, &_inner_acc);
-                    inner_acc = Some(p);
-                }
-            }
-
-            match acc {
-                None => acc = inner_acc,
+            acc = Some(match acc {
+                None => ci.to_point(),
                 Some(mut _acc) => {
                     for _ in 0..WINDOW_SIZE {
                         let p = self.to_g2_point_with_curvature(_acc);
                         _acc = self.ecc_g2_double(&p);
                     }
-                    let p = self.to_g2_point_with_curvature(inner_acc.unwrap());
-                    _acc = self.ecc_g2_add(&p, &_acc);
-                    acc = Some(_acc);
+                    self.ecc_g2_add(&ci, &_acc)
                 }
-            }
+            });
         }
 
         acc.unwrap()
@@ -330,6 +400,57 @@ pub trait EccChipBaseOps<C: CurveAffine, N: FieldExt>: Fq2ChipOps<C::Base, N> {
         AssignedPoint::new(x, y, AssignedCondition(z))
     }
 
+    // Any point of the curve, the identity included. The flag is a
+    // constrained bit and the curve equation is only enforced without it.
+    fn assign_point(&mut self, c: &C) -> AssignedPoint<C, N> {
+        let coordinates = c.coordinates();
+        let t: Option<_> = coordinates.map(|v| (v.x().clone(), v.y().clone())).into();
+        let (x, y) = t.unwrap_or((C::Base::zero(), C::Base::zero()));
+        let is_identity = bool::from(c.is_identity());
+
+        let x = self
+            .base_integer_chip()
+            .assign_w(&field_to_bn::<C::Base>(&x));
+        let y = self
+            .base_integer_chip()
+            .assign_w(&field_to_bn::<C::Base>(&y));
+        let z = self
+            .base_integer_chip()
+            .base_chip()
+            .assign_bit(N::from(is_identity as u64));
+
+        let b = self.base_integer_chip().assign_int_constant(C::b());
+        let y2 = self.base_integer_chip().int_square(&y);
+        let x2 = self.base_integer_chip().int_square(&x);
+        let x3 = self.base_integer_chip().int_mul(&x2, &x);
+        let right = self.base_integer_chip().int_add(&x3, &b);
+
+        let eq = self.base_integer_chip().is_int_equal(&y2, &right);
+        let ok = self.base_integer_chip().base_chip().or(&eq, &z);
+        self.base_integer_chip().base_chip().assert_true(&ok);
+
+        self.ecc_normalize(&AssignedPoint::new(x, y, z))
+    }
+
+    // Replaces the coordinates of a flagged identity by the canonical ones,
+    // so that they can be exposed or hashed.
+    fn ecc_normalize(&mut self, a: &AssignedPoint<C, N>) -> AssignedPoint<C, N> {
+        let identity = self.assign_identity().to_point();
+        self.bisec_point(&a.z, &identity, a)
+    }
+
+    // Canonical limbs of x then y, the layout `InstanceBuilder::push_g1`
+    // writes with the default limb size. The layout has no room for the
+    // identity, which is rejected.
+    fn ecc_point_to_limbs(&mut self, a: &AssignedPoint<C, N>) -> Vec<AssignedValue<N>> {
+        self.base_integer_chip().base_chip().assert_false(&a.z);
+
+        [&a.x, &a.y]
+            .iter()
+            .flat_map(|c| self.base_integer_chip().int_canonical(c).limbs_le)
+            .collect()
+    }
+
     fn assign_identity(&mut self) -> AssignedPointWithCurvature<C, N> {
         let zero = self
             .base_integer_chip()
@@ -363,6 +484,17 @@ pub trait EccChipBaseOps<C: CurveAffine, N: FieldExt>: Fq2ChipOps<C::Base, N> {
         AssignedPoint::new(x, y, z)
     }
 
+    // cond ? a : b for a raw cell, constrained to be a bit here.
+    fn select_point(
+        &mut self,
+        cond: &AssignedValue<N>,
+        a: &AssignedPoint<C, N>,
+        b: &AssignedPoint<C, N>,
+    ) -> AssignedPoint<C, N> {
+        self.base_integer_chip().base_chip().assert_bit(cond);
+        self.bisec_point(&AssignedCondition(*cond), a, b)
+    }
+
     fn bisec_curvature(
         &mut self,
         cond: &AssignedCondition<N>,
@@ -457,7 +589,16 @@ pub trait EccChipBaseOps<C: CurveAffine, N: FieldExt>: Fq2ChipOps<C::Base, N> {
         p
     }
 
-    fn ecc_assert_equal(&mut self, a: &AssignedPoint<C, N>, b: &AssignedPoint<C, N>) {
+    // The coordinates of the identity are not constrained, only its flag.
+    fn ecc_is_identity(&mut self, a: &AssignedPoint<C, N>) -> AssignedCondition<N> {
+        a.z
+    }
+
+    fn ecc_points_equal(
+        &mut self,
+        a: &AssignedPoint<C, N>,
+        b: &AssignedPoint<C, N>,
+    ) -> AssignedCondition<N> {
         let eq_x = self.base_integer_chip().is_int_equal(&a.x, &b.x);
         let eq_y = self.base_integer_chip().is_int_equal(&a.y, &b.y);
         let eq_z = self.base_integer_chip().base_chip().xnor(&a.z, &b.z);
@@ -465,14 +606,37 @@ pub trait EccChipBaseOps<C: CurveAffine, N: FieldExt>: Fq2ChipOps<C::Base, N> {
         let eq_xyz = self.base_integer_chip().base_chip().and(&eq_xy, &eq_z);
 
         let is_both_identity = self.base_integer_chip().base_chip().and(&a.z, &b.z);
-        let eq = self
-            .base_integer_chip()
+        self.base_integer_chip()
             .base_chip()
-            .or(&eq_xyz, &is_both_identity);
+            .or(&eq_xyz, &is_both_identity)
+    }
 
+    fn ecc_assert_equal(&mut self, a: &AssignedPoint<C, N>, b: &AssignedPoint<C, N>) {
+        let eq = self.ecc_points_equal(a, b);
         self.base_integer_chip().base_chip().assert_true(&eq)
     }
 
+    fn ecc_neg(&mut self, a: &AssignedPoint<C, N>) -> AssignedPoint<C, N> {
+        let y = self.base_integer_chip().int_neg(&a.y);
+        AssignedPoint::new(a.x.clone(), y, a.z)
+    }
+
+    // k * a by double-and-add over the bits of the constant.
+    fn ecc_mul_small_constant(&mut self, a: &AssignedPoint<C, N>, k: u64) -> AssignedPoint<C, N> {
+        assert!(k > 0);
+
+        let mut acc = a.clone();
+        for i in (0..63 - k.leading_zeros()).rev() {
+            let t = self.to_point_with_curvature(acc);
+            acc = self.ecc_double(&t);
+            if (k >> i) & 1 == 1 {
+                let t = self.to_point_with_curvature(acc);
+                acc = self.ecc_add(&t, a);
+            }
+        }
+        acc
+    }
+
     fn to_point_with_curvature(
         &mut self,
         a: AssignedPoint<C, N>,
@@ -527,6 +691,50 @@ pub trait EccChipBaseOps<C: CurveAffine, N: FieldExt>: Fq2ChipOps<C::Base, N> {
         AssignedG2Affine::new(x, y, AssignedCondition(z))
     }
 
+    // As assign_point, `None` is the identity.
+    fn assign_g2_point(
+        &mut self,
+        c: &Option<((C::Base, C::Base), (C::Base, C::Base))>,
+        b: AssignedFq2<C::Base, N>,
+    ) -> AssignedG2Affine<C, N> {
+        let zero = (C::Base::zero(), C::Base::zero());
+        let (x, y) = c.unwrap_or((zero, zero));
+
+        let x = self.fq2_assign(x);
+        let y = self.fq2_assign(y);
+        let z = self
+            .base_integer_chip()
+            .base_chip()
+            .assign_bit(N::from(c.is_none() as u64));
+
+        let y2 = self.fq2_mul(&y, &y);
+        let x2 = self.fq2_mul(&x, &x);
+        let x3 = self.fq2_mul(&x2, &x);
+        let right = self.fq2_add(&x3, &b);
+
+        let diff = self.fq2_sub(&y2, &right);
+        let eq = self.fq2_is_zero(&diff);
+        let ok = self.base_integer_chip().base_chip().or(&eq, &z);
+        self.base_integer_chip().base_chip().assert_true(&ok);
+
+        self.ecc_g2_normalize(&AssignedG2Affine::new(x, y, z))
+    }
+
+    fn ecc_g2_normalize(&mut self, a: &AssignedG2Affine<C, N>) -> AssignedG2Affine<C, N> {
+        let identity = self.assign_g2_identity().to_point();
+        self.bisec_g2_point(&a.z, &identity, a)
+    }
+
+    // x.c0, x.c1, y.c0, y.c1 as in `ecc_point_to_limbs`.
+    fn ecc_g2_point_to_limbs(&mut self, a: &AssignedG2Affine<C, N>) -> Vec<AssignedValue<N>> {
+        self.base_integer_chip().base_chip().assert_false(&a.z);
+
+        [&a.x.0, &a.x.1, &a.y.0, &a.y.1]
+            .iter()
+            .flat_map(|c| self.base_integer_chip().int_canonical(c).limbs_le)
+            .collect()
+    }
+
     fn assign_g2_identity(&mut self) -> AssignedG2WithCurvature<C, N> {
         let zero = self.fq2_assign_zero();
         let one = self.fq2_assign_one();
@@ -559,6 +767,16 @@ pub trait EccChipBaseOps<C: CurveAffine, N: FieldExt>: Fq2ChipOps<C::Base, N> {
         AssignedG2Affine::new(x, y, z)
     }
 
+    fn select_g2_point(
+        &mut self,
+        cond: &AssignedValue<N>,
+        a: &AssignedG2Affine<C, N>,
+        b: &AssignedG2Affine<C, N>,
+    ) -> AssignedG2Affine<C, N> {
+        self.base_integer_chip().base_chip().assert_bit(cond);
+        self.bisec_g2_point(&AssignedCondition(*cond), a, b)
+    }
+
     fn bisec_ext_curvature(
         &mut self,
         cond: &AssignedCondition<N>,
@@ -653,7 +871,15 @@ pub trait EccChipBaseOps<C: CurveAffine, N: FieldExt>: Fq2ChipOps<C::Base, N> {
         p
     }
 
-    fn ecc_assert_g2_equal(&mut self, a: &AssignedG2Affine<C, N>, b: &AssignedG2Affine<C, N>) {
+    fn ecc_g2_is_identity(&mut self, a: &AssignedG2Affine<C, N>) -> AssignedCondition<N> {
+        a.z
+    }
+
+    fn ecc_g2_points_equal(
+        &mut self,
+        a: &AssignedG2Affine<C, N>,
+        b: &AssignedG2Affine<C, N>,
+    ) -> AssignedCondition<N> {
         let eq_x_c0 = self.base_integer_chip().is_int_equal(&a.x.0, &b.x.0);
         let eq_x_c1 = self.base_integer_chip().is_int_equal(&a.x.1, &b.x.1);
         let eq_y_c0 = self.base_integer_chip().is_int_equal(&a.y.0, &b.y.0);
@@ -666,14 +892,40 @@ pub trait EccChipBaseOps<C: CurveAffine, N: FieldExt>: Fq2ChipOps<C::Base, N> {
         let eq_xyz = self.base_integer_chip().base_chip().and(&eq_xy, &eq_z);
 
         let is_both_identity = self.base_integer_chip().base_chip().and(&a.z, &b.z);
-        let eq = self
-            .base_integer_chip()
+        self.base_integer_chip()
             .base_chip()
-            .or(&eq_xyz, &is_both_identity);
+            .or(&eq_xyz, &is_both_identity)
+    }
 
+    fn ecc_assert_g2_equal(&mut self, a: &AssignedG2Affine<C, N>, b: &AssignedG2Affine<C, N>) {
+        let eq = self.ecc_g2_points_equal(a, b);
         self.base_integer_chip().base_chip().assert_true(&eq)
     }
 
+    fn ecc_g2_neg(&mut self, a: &AssignedG2Affine<C, N>) -> AssignedG2Affine<C, N> {
+        let y = self.fq2_neg(&a.y);
+        AssignedG2Affine::new(a.x.clone(), y, a.z)
+    }
+
+    fn ecc_g2_mul_small_constant(
+        &mut self,
+        a: &AssignedG2Affine<C, N>,
+        k: u64,
+    ) -> AssignedG2Affine<C, N> {
+        assert!(k > 0);
+
+        let mut acc = a.clone();
+        for i in (0..63 - k.leading_zeros()).rev() {
+            let t = self.to_g2_point_with_curvature(acc);
+            acc = self.ecc_g2_double(&t);
+            if (k >> i) & 1 == 1 {
+                let t = self.to_g2_point_with_curvature(acc);
+                acc = self.ecc_g2_add(&t, a);
+            }
+        }
+        acc
+    }
+
     fn to_g2_point_with_curvature(
         &mut self,
         a: AssignedG2Affine<C, N>,
@@ -688,3 +940,257 @@ pub trait EccChipBaseOps<C: CurveAffine, N: FieldExt>: Fq2ChipOps<C::Base, N> {
         AssignedG2WithCurvature::new(a.x, a.y, a.z, AssignedExtCurvature(v, z))
     }
 }
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_windowed_and_shamir_mul_match_native() {
+    use halo2_proofs::pairing::bls12_381::{Fr as BlsFr, G1Affine, G2Affine};
+    use halo2_proofs::pairing::bn256::Fr;
+    use halo2_proofs::pairing::group::Curve;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::context::GeneralScalarEccContext;
+    use crate::negative_test::NegativeTest;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let test = NegativeTest::<Fr>::new();
+    let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(test.ctx.clone());
+
+    let s = BlsFr::random(&mut rng);
+    let assigned_s = ctx.scalar_integer_ctx.assign_w(&field_to_bn(&s));
+
+    let expected = (G1Affine::generator() * s).to_affine();
+    let expected = expected.coordinates().unwrap();
+    let p = ctx.assign_non_zero_point(&G1Affine::generator());
+    for q in [
+        ctx.ecc_mul_windowed::<1>(&p, &assigned_s),
+        ctx.ecc_mul_windowed::<3>(&p, &assigned_s),
+        ctx.ecc_mul_windowed::<5>(&p, &assigned_s),
+    ] {
+        assert_eq!(ctx.base_integer_ctx.get_w(&q.x), *expected.x());
+        assert_eq!(ctx.base_integer_ctx.get_w(&q.y), *expected.y());
+    }
+
+    let t = BlsFr::random(&mut rng);
+    let assigned_t = ctx.scalar_integer_ctx.assign_w(&field_to_bn(&t));
+    let h = (G1Affine::generator() * BlsFr::random(&mut rng)).to_affine();
+    let expected = (G1Affine::generator() * s + h * t).to_affine();
+    let expected = expected.coordinates().unwrap();
+    let q = ctx.assign_non_zero_point(&h);
+    let r = ctx.ecc_shamir_mul::<2>(&p, &assigned_s, &q, &assigned_t);
+    assert_eq!(ctx.base_integer_ctx.get_w(&r.x), *expected.x());
+    assert_eq!(ctx.base_integer_ctx.get_w(&r.y), *expected.y());
+
+    // three points take the grouped path, two the windowed one
+    let u = BlsFr::random(&mut rng);
+    let assigned_u = ctx.scalar_integer_ctx.assign_w(&field_to_bn(&u));
+    let k = (G1Affine::generator() * BlsFr::random(&mut rng)).to_affine();
+    let expected = (G1Affine::generator() * s + h * t + k * u).to_affine();
+    let expected = expected.coordinates().unwrap();
+    let w = ctx.assign_non_zero_point(&k);
+    let r = ctx.msm(
+        &vec![p.clone(), q.clone(), w],
+        &vec![assigned_s.clone(), assigned_t.clone(), assigned_u],
+    );
+    assert_eq!(ctx.base_integer_ctx.get_w(&r.x), *expected.x());
+    assert_eq!(ctx.base_integer_ctx.get_w(&r.y), *expected.y());
+    let expected = (G1Affine::generator() * s + h * t).to_affine();
+    let expected = expected.coordinates().unwrap();
+    let r = ctx.msm(&vec![p, q], &vec![assigned_s.clone(), assigned_t]);
+    assert_eq!(ctx.base_integer_ctx.get_w(&r.x), *expected.x());
+    assert_eq!(ctx.base_integer_ctx.get_w(&r.y), *expected.y());
+
+    let g = G2Affine::generator();
+    let expected = (g * s).to_affine();
+    let p = ctx.assign_non_identity_constant_g2(&((g.x.c0, g.x.c1), (g.y.c0, g.y.c1)));
+    let q = ctx.ecc_g2_mul_windowed::<3>(&p, &assigned_s);
+    assert_eq!(ctx.base_integer_ctx.get_w(&q.x.0), expected.x.c0);
+    assert_eq!(ctx.base_integer_ctx.get_w(&q.y.1), expected.y.c1);
+
+    assert_eq!(test.run(&[]), vec![]);
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_points_equal_and_is_identity() {
+    use halo2_proofs::pairing::bls12_381::{Fr as BlsFr, G1Affine};
+    use halo2_proofs::pairing::bn256::Fr;
+    use halo2_proofs::pairing::group::Curve;
+
+    use crate::context::GeneralScalarEccContext;
+    use crate::negative_test::NegativeTest;
+
+    let test = NegativeTest::<Fr>::new();
+    let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(test.ctx.clone());
+
+    let g = ctx.assign_non_zero_point(&G1Affine::generator());
+    let g2 = ctx.assign_non_zero_point(&(G1Affine::generator() * BlsFr::from(2u64)).to_affine());
+    let identity = ctx.assign_identity().to_point();
+    let doubled = {
+        let t = ctx.to_point_with_curvature(g.clone());
+        ctx.ecc_double(&t)
+    };
+    let sum = {
+        let t = ctx.to_point_with_curvature(identity.clone());
+        ctx.ecc_add(&t, &g)
+    };
+
+    let cases = [
+        (&doubled, &g2, true),
+        (&g, &g2, false),
+        (&sum, &g, true),
+        (&identity, &g, false),
+        (&identity, &identity, true),
+    ];
+    for (a, b, expected) in cases {
+        let eq = ctx.ecc_points_equal(a, b);
+        assert_eq!(eq.0.val, Fr::from(expected as u64));
+    }
+
+    let is_identity = ctx.ecc_is_identity(&identity);
+    assert_eq!(is_identity.0.val, Fr::one());
+    let is_identity = ctx.ecc_is_identity(&g);
+    assert_eq!(is_identity.0.val, Fr::zero());
+
+    assert_eq!(test.run(&[]), vec![]);
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_assign_point_with_identity() {
+    use halo2_proofs::pairing::bls12_381::{Fq, G1Affine, G2Affine};
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::context::GeneralScalarEccContext;
+    use crate::negative_test::NegativeTest;
+
+    let test = NegativeTest::<Fr>::new();
+    let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(test.ctx.clone());
+
+    let identity = ctx.assign_point(&G1Affine::identity());
+    let g = ctx.assign_point(&G1Affine::generator());
+    assert_eq!(identity.z.0.val, Fr::one());
+    assert_eq!(g.z.0.val, Fr::zero());
+
+    let t = ctx.to_point_with_curvature(identity.clone());
+    let sum = ctx.ecc_add(&t, &g);
+    let eq = ctx.ecc_points_equal(&sum, &g);
+    assert_eq!(eq.0.val, Fr::one());
+
+    // g + (-g) is flagged, and normalizes to the canonical coordinates.
+    let t = ctx.to_point_with_curvature(g.clone());
+    let neg = ctx.ecc_neg(&g);
+    let sum = ctx.ecc_add(&t, &neg);
+    let sum = ctx.ecc_normalize(&sum);
+    assert_eq!(sum.z.0.val, Fr::one());
+    assert_eq!(ctx.base_integer_ctx.get_w(&sum.x), Fq::zero());
+    assert_eq!(ctx.base_integer_ctx.get_w(&sum.y), Fq::zero());
+
+    let four = Fq::one().double().double();
+    let b = ctx.fq2_assign_constant((four, four));
+    let h = G2Affine::generator();
+    let h = ctx.assign_g2_point(&Some(((h.x.c0, h.x.c1), (h.y.c0, h.y.c1))), b.clone());
+    let identity = ctx.assign_g2_point(&None, b);
+    assert_eq!(h.z.0.val, Fr::zero());
+    assert_eq!(identity.z.0.val, Fr::one());
+
+    assert_eq!(test.run(&[]), vec![]);
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_g2_ops_match_native() {
+    use halo2_proofs::pairing::bls12_381::{Fr as BlsFr, G1Affine, G2Affine};
+    use halo2_proofs::pairing::bn256::Fr;
+    use halo2_proofs::pairing::group::Curve;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::context::GeneralScalarEccContext;
+    use crate::negative_test::NegativeTest;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let test = NegativeTest::<Fr>::new();
+    let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(test.ctx.clone());
+    let assign = |ctx: &mut GeneralScalarEccContext<G1Affine, Fr>, p: G2Affine| {
+        ctx.assign_non_identity_constant_g2(&((p.x.c0, p.x.c1), (p.y.c0, p.y.c1)))
+    };
+
+    let g = G2Affine::generator();
+    let h = (g * BlsFr::random(&mut rng)).to_affine();
+    let a = assign(&mut ctx, g);
+    let b = assign(&mut ctx, h);
+
+    let t = ctx.to_g2_point_with_curvature(a.clone());
+    let cases = [
+        (ctx.ecc_g2_add(&t, &b), (g + h).to_affine()),
+        (ctx.ecc_g2_double(&t), (g + g).to_affine()),
+        (ctx.ecc_g2_neg(&b), -h),
+        (
+            ctx.ecc_g2_mul_small_constant(&b, 5),
+            (h * BlsFr::from(5u64)).to_affine(),
+        ),
+    ];
+    for (p, expected) in cases {
+        let expected = assign(&mut ctx, expected);
+        let eq = ctx.ecc_g2_points_equal(&p, &expected);
+        assert_eq!(eq.0.val, Fr::one());
+    }
+
+    // a + (-a) is the identity
+    let neg = ctx.ecc_g2_neg(&a);
+    let sum = ctx.ecc_g2_add(&t, &neg);
+    let is_identity = ctx.ecc_g2_is_identity(&sum);
+    assert_eq!(is_identity.0.val, Fr::one());
+
+    assert_eq!(test.run(&[]), vec![]);
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_point_to_limbs_matches_instance_builder() {
+    use halo2_proofs::pairing::bls12_381::{Fr as BlsFr, G1Affine, G2Affine};
+    use halo2_proofs::pairing::bn256::Fr;
+    use halo2_proofs::pairing::group::Curve;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::context::GeneralScalarEccContext;
+    use crate::instance_builder::{InstanceBuilder, InstanceReader};
+    use crate::negative_test::NegativeTest;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let test = NegativeTest::<Fr>::new();
+    let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(test.ctx.clone());
+
+    let g1 = (G1Affine::generator() * BlsFr::random(&mut rng)).to_affine();
+    let g2 = (G2Affine::generator() * BlsFr::random(&mut rng)).to_affine();
+
+    // limbs coming out of arithmetic, not fresh assignments
+    let p = ctx.assign_non_zero_point(&g1);
+    let p = ctx.ecc_neg(&p);
+    let p = ctx.ecc_neg(&p);
+    let q = ctx.assign_non_identity_constant_g2(&((g2.x.c0, g2.x.c1), (g2.y.c0, g2.y.c1)));
+    let q = ctx.ecc_g2_neg(&q);
+    let q = ctx.ecc_g2_neg(&q);
+
+    let limbs = ctx
+        .ecc_point_to_limbs(&p)
+        .into_iter()
+        .chain(ctx.ecc_g2_point_to_limbs(&q))
+        .map(|l| l.val)
+        .collect::<Vec<_>>();
+
+    let mut builder = InstanceBuilder::new();
+    builder.push_g1(&g1).push_g2(&g2);
+    let instances = builder.build();
+    assert_eq!(limbs, instances);
+
+    let mut reader = InstanceReader::new(&limbs);
+    assert_eq!(reader.read_g1(), Ok(g1));
+    assert_eq!(reader.read_g2(), Ok(g2));
+    assert_eq!(reader.finish(), Ok(()));
+
+    assert_eq!(test.run(&[]), vec![]);
+}
@@ -102,6 +102,15 @@ pub trait Fq2ChipOps<W: BaseExt, N: FieldExt>: EccBaseIntegerChipWrapper<W, N> {
         (a.0.clone(), self.base_integer_chip().int_neg(&a.1))
     }
 
+    // a^(p^power), conjugation for odd powers.
+    fn fq2_frobenius_map(&mut self, a: &AssignedFq2<W, N>, power: usize) -> AssignedFq2<W, N> {
+        if power % 2 == 1 {
+            self.fq2_conjugate(a)
+        } else {
+            a.clone()
+        }
+    }
+
     fn fq2_unsafe_invert(&mut self, x: &AssignedFq2<W, N>) -> AssignedFq2<W, N> {
         let t0 = self.base_integer_chip().int_square(&x.0);
         let t1 = self.base_integer_chip().int_square(&x.1);
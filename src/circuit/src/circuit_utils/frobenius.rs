@@ -0,0 +1,117 @@
+/*
+  Frobenius maps on BLS12-381 G2. psi = untwist o frobenius o twist acts on
+  the twist E2: y^2 = x^3 + 4(1 + i) coordinate-wise,
+  psi^k(x, y) = (x^(p^k) * c_x[k], y^(p^k) * c_y[k]) with
+  c_x[k] = (1 + i)^((1 - p^k) / 3) and c_y[k] = (1 + i)^((1 - p^k) / 2).
+  On G2 psi is the multiplication by p = x mod r.
+*/
+
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::pairing::bls12_381::{Fq, G1Affine};
+
+use super::ecc_chip::EccChipBaseOps;
+use super::fq2::Fq2ChipOps;
+use super::integer_chip::IntegerChipOps;
+use super::isogeny::fq;
+use crate::assign::AssignedG2Affine;
+use crate::context::GeneralScalarEccContext;
+
+// (c0, c1) of c_x[k] and c_y[k] for k = 1, 2, 3.
+const PSI_COEFF_X: [(&str, &str); 3] = [
+    (
+        "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        "1a0111ea397fe699ec02408663d4de85aa0d857d89759ad4897d29650fb85f9b409427eb4f49fffd8bfd00000000aaad",
+    ),
+    (
+        "1a0111ea397fe699ec02408663d4de85aa0d857d89759ad4897d29650fb85f9b409427eb4f49fffd8bfd00000000aaac",
+        "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+    ),
+    (
+        "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaaaa",
+    ),
+];
+const PSI_COEFF_Y: [(&str, &str); 3] = [
+    (
+        "135203e60180a68ee2e9c448d77a2cd91c3dedd930b1cf60ef396489f61eb45e304466cf3e67fa0af1ee7b04121bdea2",
+        "06af0e0437ff400b6831e36d6bd17ffe48395dabc2d3435e77f76e17009241c5ee67992f72ec05f4c81084fbede3cc09",
+    ),
+    (
+        "1a0111ea397fe69a4b1ba7b6434bacd764774b84f38512bf6730d2a0f6b0f6241eabfffeb153ffffb9feffffffffaaaa",
+        "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+    ),
+    (
+        "06af0e0437ff400b6831e36d6bd17ffe48395dabc2d3435e77f76e17009241c5ee67992f72ec05f4c81084fbede3cc09",
+        "135203e60180a68ee2e9c448d77a2cd91c3dedd930b1cf60ef396489f61eb45e304466cf3e67fa0af1ee7b04121bdea2",
+    ),
+];
+
+pub trait FrobeniusChipOps<N: FieldExt>: EccChipBaseOps<G1Affine, N> {
+    // psi^power for power in 1..=3, the identity flag is kept.
+    fn g2_frobenius_map(
+        &mut self,
+        a: &AssignedG2Affine<G1Affine, N>,
+        power: usize,
+    ) -> AssignedG2Affine<G1Affine, N> {
+        assert!(power >= 1 && power <= 3);
+
+        let (cx0, cx1) = PSI_COEFF_X[power - 1];
+        let (cy0, cy1) = PSI_COEFF_Y[power - 1];
+        let cx = self.fq2_assign_constant((fq(cx0), fq(cx1)));
+        let cy = self.fq2_assign_constant((fq(cy0), fq(cy1)));
+
+        let x = self.fq2_frobenius_map(&a.x, power);
+        let x = self.fq2_mul(&x, &cx);
+        let y = self.fq2_frobenius_map(&a.y, power);
+        let y = self.fq2_mul(&y, &cy);
+
+        AssignedG2Affine::new(x, y, a.z)
+    }
+
+    fn g2_psi(&mut self, a: &AssignedG2Affine<G1Affine, N>) -> AssignedG2Affine<G1Affine, N> {
+        self.g2_frobenius_map(a, 1)
+    }
+}
+
+impl<N: FieldExt, B: IntegerChipOps<Fq, N>> FrobeniusChipOps<N>
+    for GeneralScalarEccContext<G1Affine, N, B>
+{
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_g2_psi_is_mul_by_x() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381::{Fr as BlsFr, G2Affine};
+    use halo2_proofs::pairing::bn256::Fr;
+    use halo2_proofs::pairing::group::Curve;
+
+    use crate::negative_test::NegativeTest;
+
+    let test = NegativeTest::<Fr>::new();
+    let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(test.ctx.clone());
+
+    // x = -0xd201000000010000
+    let x = -BlsFr::from(0xd201000000010000u64);
+    let g = G2Affine::generator();
+    let a = ctx.assign_non_identity_constant_g2(&((g.x.c0, g.x.c1), (g.y.c0, g.y.c1)));
+
+    let mut expected = g;
+    for power in 1..=3 {
+        expected = (expected * x).to_affine();
+        let q = ctx.g2_frobenius_map(&a, power);
+
+        assert_eq!(q.z.0.val, Fr::zero());
+        assert_eq!(
+            (
+                ctx.base_integer_ctx.get_w(&q.x.0),
+                ctx.base_integer_ctx.get_w(&q.x.1),
+                ctx.base_integer_ctx.get_w(&q.y.0),
+                ctx.base_integer_ctx.get_w(&q.y.1),
+            ),
+            (expected.x.c0, expected.x.c1, expected.y.c0, expected.y.c1)
+        );
+    }
+
+    assert_eq!(test.run(&[]), vec![]);
+}
@@ -13,18 +13,26 @@ use crate::context::GeneralScalarEccContext;
 use crate::pair;
 use crate::utils::field_to_bn;
 
-impl<C: CurveAffine, N: FieldExt> EccBaseIntegerChipWrapper<C::Base, N>
-    for GeneralScalarEccContext<C, N>
+impl<C: CurveAffine, N: FieldExt, B: IntegerChipOps<C::Base, N>>
+    EccBaseIntegerChipWrapper<C::Base, N> for GeneralScalarEccContext<C, N, B>
 {
     fn base_integer_chip(&mut self) -> &mut dyn IntegerChipOps<C::Base, N> {
         &mut self.base_integer_ctx
     }
 }
 
-impl<C: CurveAffine, N: FieldExt> EccChipBaseOps<C, N> for GeneralScalarEccContext<C, N> {}
-impl<C: CurveAffine, N: FieldExt> Fq2ChipOps<C::Base, N> for GeneralScalarEccContext<C, N> {}
+impl<C: CurveAffine, N: FieldExt, B: IntegerChipOps<C::Base, N>> EccChipBaseOps<C, N>
+    for GeneralScalarEccContext<C, N, B>
+{
+}
+impl<C: CurveAffine, N: FieldExt, B: IntegerChipOps<C::Base, N>> Fq2ChipOps<C::Base, N>
+    for GeneralScalarEccContext<C, N, B>
+{
+}
 
-impl<C: CurveAffine, N: FieldExt> EccChipScalarOps<C, N> for GeneralScalarEccContext<C, N> {
+impl<C: CurveAffine, N: FieldExt, B: IntegerChipOps<C::Base, N>> EccChipScalarOps<C, N>
+    for GeneralScalarEccContext<C, N, B>
+{
     type AssignedScalar = AssignedInteger<C::Scalar, N>;
 
     fn decompose_scalar<const WINDOW_SIZE: usize>(
@@ -64,7 +72,7 @@ impl<C: CurveAffine, N: FieldExt> EccChipScalarOps<C, N> for GeneralScalarEccCon
         let padding = bits.len() % WINDOW_SIZE;
         if padding != 0 {
             let zero = self.native_ctx.borrow_mut().assign_constant(zero);
-            for _ in 0..padding {
+            for _ in 0..WINDOW_SIZE - padding {
                 bits.push(AssignedCondition(zero));
             }
         }
@@ -78,4 +86,101 @@ impl<C: CurveAffine, N: FieldExt> EccChipScalarOps<C, N> for GeneralScalarEccCon
 
         res
     }
+
+    fn decompose_scalar_canonical<const WINDOW_SIZE: usize>(
+        &mut self,
+        s: &Self::AssignedScalar,
+    ) -> Vec<[AssignedCondition<N>; WINDOW_SIZE]> {
+        let mut bits = self.scalar_integer_ctx.int_to_bits_le(s);
+
+        if bits.len() % WINDOW_SIZE != 0 {
+            let zero = self.native_ctx.borrow_mut().assign_constant(N::zero());
+            let len = bits.len() + WINDOW_SIZE - bits.len() % WINDOW_SIZE;
+            bits.resize(len, AssignedCondition(zero));
+        }
+
+        let mut res = bits
+            .chunks(WINDOW_SIZE)
+            .map(|x| Vec::from(x).try_into().unwrap())
+            .collect::<Vec<_>>();
+
+        res.reverse();
+
+        res
+    }
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_decompose_scalar_canonical() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381;
+    use halo2_proofs::pairing::bn256::Fr;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::context::Context;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let ctx = Rc::new(RefCell::new(Context::new()));
+    let mut ctx = GeneralScalarEccContext::<bls12_381::G1Affine, Fr>::new(ctx);
+
+    for v in [-bls12_381::Fr::one(), bls12_381::Fr::random(&mut rng)] {
+        let s = ctx.scalar_integer_ctx.assign_w(&field_to_bn(&v));
+        let windows = ctx.decompose_scalar_canonical::<4>(&s);
+
+        let bits = windows
+            .iter()
+            .rev()
+            .flat_map(|w| w.iter())
+            .map(|b| b.0.val == Fr::one())
+            .collect::<Vec<_>>();
+        let expected = field_to_bn(&v);
+        assert_eq!(bits.len(), 256);
+        for (i, b) in bits.iter().enumerate() {
+            assert_eq!(*b, expected.bit(i as u64));
+        }
+    }
+}
+
+// BN254 group arithmetic inside a BLS12-381 native circuit.
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_bn254_g1_over_bls12_381_native() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381::Fr as BlsFr;
+    use halo2_proofs::pairing::bn256::{Fq, Fr, G1Affine};
+    use halo2_proofs::pairing::group::Curve;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::negative_test::NegativeTest;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let s = Fr::random(&mut rng);
+    let x = Fq::random(&mut rng);
+    let y = Fq::random(&mut rng);
+
+    let mut test = NegativeTest::<BlsFr>::new();
+    test.gadget("bn254 g1 mul", |ctx| {
+        let mut ctx = GeneralScalarEccContext::<G1Affine, BlsFr>::new(ctx.clone());
+
+        let a = ctx.base_integer_ctx.assign_w(&field_to_bn(&x));
+        let b = ctx.base_integer_ctx.assign_w(&field_to_bn(&y));
+        let c = ctx.base_integer_ctx.int_mul(&a, &b);
+        assert_eq!(ctx.base_integer_ctx.get_w(&c), x * y);
+
+        let assigned_s = ctx.scalar_integer_ctx.assign_w(&field_to_bn(&s));
+        let p = ctx.assign_non_zero_point(&G1Affine::generator());
+        let q = ctx.ecc_mul_windowed::<3>(&p, &assigned_s);
+
+        let expected = (G1Affine::generator() * s).to_affine();
+        let expected = expected.coordinates().unwrap();
+        assert_eq!(ctx.base_integer_ctx.get_w(&q.x), *expected.x());
+        assert_eq!(ctx.base_integer_ctx.get_w(&q.y), *expected.y());
+    });
+    assert_eq!(test.run(&[]), vec![]);
 }
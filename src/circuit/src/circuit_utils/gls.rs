@@ -0,0 +1,200 @@
+/*
+  G2 scalar multiplication on BLS12-381 through the 4-dimensional GLS
+  decomposition. psi acts as the multiplication by x on G2 and
+  r = x^4 - x^2 + 1, so any s < r is s = sum d_i * |x|^i with 64-bit digits,
+  and s * P = d0 * P - d1 * psi(P) + d2 * psi^2(P) - d3 * psi^3(P) as x is
+  negative. The ladder is then 64 doublings long instead of 255. The points
+  must be in G2.
+*/
+
+use halo2_proofs::arithmetic::{Field, FieldExt};
+use halo2_proofs::pairing::bls12_381::{Fq, Fr, G1Affine};
+use num_bigint::BigUint;
+
+use super::base_chip::BaseChipOps;
+use super::ecc_chip::EccChipBaseOps;
+use super::frobenius::FrobeniusChipOps;
+use super::integer_chip::IntegerChipOps;
+use super::subgroup::BLS_X;
+use crate::assign::{AssignedCondition, AssignedG2Affine, AssignedInteger};
+use crate::context::GeneralScalarEccContext;
+use crate::utils::field_to_bn;
+
+pub const GLS_DIGITS: usize = 4;
+pub const GLS_DIGIT_BITS: usize = 64;
+
+pub trait GlsChipOps<N: FieldExt>: FrobeniusChipOps<N> {
+    // Little-endian bits of the base |x| digits of `s`, constrained to
+    // recompose `s` modulo r.
+    fn g2_gls_decompose(
+        &mut self,
+        s: &AssignedInteger<Fr, N>,
+    ) -> [Vec<AssignedCondition<N>>; GLS_DIGITS];
+
+    fn ecc_g2_mul_gls<const WINDOW_SIZE: usize>(
+        &mut self,
+        point: &AssignedG2Affine<G1Affine, N>,
+        s: &AssignedInteger<Fr, N>,
+    ) -> AssignedG2Affine<G1Affine, N> {
+        let digits = self.g2_gls_decompose(s);
+
+        // (-1)^i * psi^i(P)
+        let mut bases = vec![point.clone()];
+        for power in 1..GLS_DIGITS {
+            let q = self.g2_frobenius_map(point, power);
+            bases.push(if power % 2 == 1 {
+                self.ecc_g2_neg(&q)
+            } else {
+                q
+            });
+        }
+
+        // {0, Q, 2Q, ...} for each base
+        let identity = self.assign_g2_identity();
+        let tables = bases
+            .iter()
+            .map(|q| {
+                let mut candidates =
+                    vec![identity.clone(), self.to_g2_point_with_curvature(q.clone())];
+                for i in 2..(1 << WINDOW_SIZE) {
+                    let c = self.ecc_g2_add(&candidates[i - 1], q);
+                    let c = self.to_g2_point_with_curvature(c);
+                    candidates.push(c);
+                }
+                candidates
+            })
+            .collect::<Vec<_>>();
+
+        let zero = AssignedCondition(
+            self.base_integer_chip()
+                .base_chip()
+                .assign_constant(N::zero()),
+        );
+        let windows_in_be = digits
+            .iter()
+            .map(|bits| {
+                let mut bits = bits.clone();
+                let len = (bits.len() + WINDOW_SIZE - 1) / WINDOW_SIZE * WINDOW_SIZE;
+                bits.resize(len, zero);
+                bits.chunks(WINDOW_SIZE)
+                    .rev()
+                    .map(|w| w.to_vec())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut acc: Option<AssignedG2Affine<G1Affine, N>> = None;
+        for wi in 0..windows_in_be[0].len() {
+            if let Some(mut a) = acc.take() {
+                for _ in 0..WINDOW_SIZE {
+                    let t = self.to_g2_point_with_curvature(a);
+                    a = self.ecc_g2_double(&t);
+                }
+                acc = Some(a);
+            }
+
+            for (table, windows) in tables.iter().zip(windows_in_be.iter()) {
+                let mut candidates = table.clone();
+                for bit in windows[wi].iter() {
+                    candidates = candidates
+                        .chunks(2)
+                        .map(|c| self.bisec_g2_with_curvature(bit, &c[1], &c[0]))
+                        .collect();
+                }
+                assert_eq!(candidates.len(), 1);
+
+                let c = candidates.pop().unwrap();
+                acc = Some(match acc {
+                    None => c.to_point(),
+                    Some(a) => self.ecc_g2_add(&c, &a),
+                });
+            }
+        }
+
+        acc.unwrap()
+    }
+}
+
+impl<N: FieldExt, B: IntegerChipOps<Fq, N>> GlsChipOps<N>
+    for GeneralScalarEccContext<G1Affine, N, B>
+{
+    fn g2_gls_decompose(
+        &mut self,
+        s: &AssignedInteger<Fr, N>,
+    ) -> [Vec<AssignedCondition<N>>; GLS_DIGITS] {
+        let x = BigUint::from(BLS_X);
+        let mut v = field_to_bn(&self.scalar_integer_ctx.get_w(s));
+
+        let digits = {
+            let mut ctx = self.native_ctx.borrow_mut();
+            [(); GLS_DIGITS].map(|_| {
+                let d = &v % &x;
+                v = &v / &x;
+                (0..GLS_DIGIT_BITS)
+                    .map(|i| ctx.assign_bit(N::from(d.bit(i as u64) as u64)))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        let mut sum = self.scalar_integer_ctx.int_from_bits_le(&digits[0]);
+        let mut coeff = Fr::one();
+        for bits in digits[1..].iter() {
+            coeff = coeff * Fr::from(BLS_X);
+            let d = self.scalar_integer_ctx.int_from_bits_le(bits);
+            let c = self.scalar_integer_ctx.assign_int_constant(coeff);
+            let t = self.scalar_integer_ctx.int_mul(&d, &c);
+            sum = self.scalar_integer_ctx.int_add(&sum, &t);
+        }
+        self.scalar_integer_ctx.assert_int_equal(&sum, s);
+
+        digits
+    }
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_g2_mul_gls_matches_native() {
+    use halo2_proofs::pairing::bls12_381::G2Affine;
+    use halo2_proofs::pairing::bn256::Fr as BnFr;
+    use halo2_proofs::pairing::group::Curve;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use super::ecc_chip::EccChipScalarOps;
+    use crate::negative_test::NegativeTest;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let test = NegativeTest::<BnFr>::new();
+    let mut ctx = GeneralScalarEccContext::<G1Affine, BnFr>::new(test.ctx.clone());
+
+    let g = (G2Affine::generator() * Fr::random(&mut rng)).to_affine();
+    let p = ctx.assign_non_identity_constant_g2(&((g.x.c0, g.x.c1), (g.y.c0, g.y.c1)));
+
+    for s in [Fr::zero(), -Fr::one(), Fr::random(&mut rng)] {
+        let expected = (g * s).to_affine();
+        let assigned_s = ctx.scalar_integer_ctx.assign_w(&field_to_bn(&s));
+
+        let start = ctx.native_ctx.borrow().base_offset;
+        let q = ctx.ecc_g2_mul_gls::<3>(&p, &assigned_s);
+        let gls_rows = ctx.native_ctx.borrow().base_offset - start;
+
+        assert_eq!(q.z.0.val == BnFr::one(), s == Fr::zero());
+        if s != Fr::zero() {
+            assert_eq!(
+                (
+                    ctx.base_integer_ctx.get_w(&q.x.0),
+                    ctx.base_integer_ctx.get_w(&q.x.1),
+                    ctx.base_integer_ctx.get_w(&q.y.0),
+                    ctx.base_integer_ctx.get_w(&q.y.1),
+                ),
+                (expected.x.c0, expected.x.c1, expected.y.c0, expected.y.c1)
+            );
+        }
+
+        let start = ctx.native_ctx.borrow().base_offset;
+        ctx.ecc_g2_mul_windowed::<3>(&p, &assigned_s);
+        assert!(gls_rows < ctx.native_ctx.borrow().base_offset - start);
+    }
+
+    assert_eq!(test.run(&[]), vec![]);
+}
@@ -0,0 +1,273 @@
+/*
+  hash_to_curve for BLS12-381 G1 (RFC 9380, BLS12381G1_XMD:SHA-256_SSWU_RO_):
+  expand_message_xmd, simplified SWU onto the 11-isogenous curve E', the
+  11-isogeny back to E and cofactor clearing by h_eff.
+
+  With message augmentation (AugSchemeMPL) the compressed pubkey is hashed
+  in front of the message, so the signature is bound to the key.
+*/
+
+use std::cell::RefMut;
+
+use halo2_proofs::arithmetic::{Field, FieldExt};
+use halo2_proofs::pairing::bls12_381::{Fq, G1Affine};
+use num_bigint::BigUint;
+
+use super::base_chip::BaseChipOps;
+use super::compress::CompressChipOps;
+use super::ecc_chip::EccChipBaseOps;
+use super::integer_chip::IntegerChipOps;
+use super::isogeny::{fq, IsogenyChipOps};
+use crate::assign::{AssignedCondition, AssignedG2Affine, AssignedInteger, AssignedPoint};
+use crate::context::{Context, GeneralScalarEccContext};
+use crate::dst::{BlsScheme, Dst, DstChipOps};
+use crate::utils::{bn_to_field, field_to_bn};
+
+// Bytes of uniform output per field element, ceil((381 + 128) / 8).
+pub const HASH_TO_FIELD_L: usize = 64;
+// 1 - x for the BLS parameter x = -0xd201000000010000.
+pub const H_EFF_G1: u64 = 0xd201000000010001;
+
+// E': y^2 = x^3 + A'x + B'
+const SSWU_A: &str = "00144698a3b8e9433d693a02c96d4982b0ea985383ee66a8d8e8981aefd881ac98936f8da0e0f97f5cf428082d584c1d";
+const SSWU_B: &str = "12e2908d11688030018b12e8753eee3b2016c1f0f24f4070a0b9c14fcef35ef55a23215a316ceaa5d1cc48e98e172be0";
+const SSWU_Z: u64 = 11;
+
+pub trait HashToCurveG1ChipOps<N: FieldExt>: IsogenyChipOps<N> + CompressChipOps<N> {
+    fn native_chip(&mut self) -> RefMut<'_, Context<N>>;
+
+    // hash_to_field with count 2 over a byte aligned message.
+    fn hash_to_field_g1(
+        &mut self,
+        message: &[AssignedCondition<N>],
+        dst: &Dst,
+    ) -> [AssignedInteger<Fq, N>; 2] {
+        let uniform_bytes =
+            self.native_chip()
+                .expand_message_xmd(message, dst, 2 * HASH_TO_FIELD_L);
+        let shift = self
+            .base_integer_chip()
+            .assign_int_constant(bn_to_field(&(BigUint::from(1u64) << 256)));
+
+        uniform_bytes
+            .chunks(HASH_TO_FIELD_L * 8)
+            .map(|tv| {
+                // OS2IP reads the bytes big-endian.
+                let bits = tv
+                    .chunks(8)
+                    .rev()
+                    .flat_map(|byte| byte.iter().copied())
+                    .collect::<Vec<_>>();
+                let lo = self.base_integer_chip().int_from_bits_le(&bits[..256]);
+                let hi = self.base_integer_chip().int_from_bits_le(&bits[256..]);
+                let hi = self.base_integer_chip().int_mul(&hi, &shift);
+                self.base_integer_chip().int_add(&hi, &lo)
+            })
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap()
+    }
+
+    // Simplified SWU onto E', returns the affine (x, y). The square root is
+    // a witness: Z is a non-square so exactly one of g(x1), g(x2) = Z^3 u^6
+    // g(x1) is a square, and the sign is pinned by sgn0(y) = sgn0(u).
+    fn map_to_curve_sswu_g1(
+        &mut self,
+        u: &AssignedInteger<Fq, N>,
+    ) -> (AssignedInteger<Fq, N>, AssignedInteger<Fq, N>) {
+        let a = fq(SSWU_A);
+        let b = fq(SSWU_B);
+        let z = Fq::from(SSWU_Z);
+
+        let g = |ops: &mut Self, x: &AssignedInteger<Fq, N>| {
+            let a = ops.base_integer_chip().assign_int_constant(a);
+            let b = ops.base_integer_chip().assign_int_constant(b);
+            let x2 = ops.base_integer_chip().int_square(x);
+            let t = ops.base_integer_chip().int_add(&x2, &a);
+            let t = ops.base_integer_chip().int_mul(&t, x);
+            ops.base_integer_chip().int_add(&t, &b)
+        };
+
+        // tv1 = 1 / (Z^2 u^4 + Z u^2), or 0
+        let u2 = self.base_integer_chip().int_square(u);
+        let z_u2 = self.base_integer_chip().int_mul_small_constant(&u2, SSWU_Z);
+        let one = self.base_integer_chip().assign_int_constant(Fq::one());
+        let t = self.base_integer_chip().int_add(&z_u2, &one);
+        let tv = self.base_integer_chip().int_mul(&z_u2, &t);
+        let (tv_is_zero, tv1) = self.base_integer_chip().int_div(&one, &tv);
+
+        // x1 = -B / A * (1 + tv1), or B / (Z * A) when tv1 = 0
+        let minus_b_over_a = self
+            .base_integer_chip()
+            .assign_int_constant(-(b * a.invert().unwrap()));
+        let b_over_za = self
+            .base_integer_chip()
+            .assign_int_constant(b * (z * a).invert().unwrap());
+        let t = self.base_integer_chip().int_add(&one, &tv1);
+        let x1 = self.base_integer_chip().int_mul(&minus_b_over_a, &t);
+        let x1 = self
+            .base_integer_chip()
+            .bisec_int(&tv_is_zero, &b_over_za, &x1);
+        let gx1 = g(self, &x1);
+
+        let x2 = self.base_integer_chip().int_mul(&z_u2, &x1);
+        let gx2 = g(self, &x2);
+
+        let gx1_is_square = self.base_integer_chip().get_w(&gx1).sqrt().is_some();
+        let e = self
+            .base_integer_chip()
+            .base_chip()
+            .assign_bit(N::from(bool::from(gx1_is_square) as u64));
+        let x = self.base_integer_chip().bisec_int(&e, &x1, &x2);
+        let gx = self.base_integer_chip().bisec_int(&e, &gx1, &gx2);
+
+        let u_bits = self.base_integer_chip().int_to_bits_le(u);
+        let y = {
+            let y = self.base_integer_chip().get_w(&gx).sqrt().unwrap();
+            let sign = field_to_bn(&y).bit(0) != (u_bits[0].0.val == N::one());
+            let y = if sign { -y } else { y };
+            self.base_integer_chip().assign_w(&field_to_bn(&y))
+        };
+        let y2 = self.base_integer_chip().int_square(&y);
+        self.base_integer_chip().assert_int_equal(&y2, &gx);
+
+        let y_bits = self.base_integer_chip().int_to_bits_le(&y);
+        self.base_integer_chip()
+            .base_chip()
+            .assert_equal(&u_bits[0].0, &y_bits[0].0);
+
+        (x, y)
+    }
+
+    fn clear_cofactor_g1(&mut self, p: &AssignedPoint<G1Affine, N>) -> AssignedPoint<G1Affine, N> {
+        self.ecc_mul_small_constant(p, H_EFF_G1)
+    }
+
+    fn hash_to_curve_g1(
+        &mut self,
+        message: &[AssignedCondition<N>],
+        dst: &Dst,
+    ) -> AssignedPoint<G1Affine, N> {
+        let [u0, u1] = self.hash_to_field_g1(message, dst);
+
+        let (x, y) = self.map_to_curve_sswu_g1(&u0);
+        let q0 = self.iso_map_g1(&x, &y);
+        let (x, y) = self.map_to_curve_sswu_g1(&u1);
+        let q1 = self.iso_map_g1(&x, &y);
+
+        let q0 = self.to_point_with_curvature(q0);
+        let r = self.ecc_add(&q0, &q1);
+        self.clear_cofactor_g1(&r)
+    }
+
+    // hash_to_curve of pubkey || message, with the G2 pubkey compressed in
+    // circuit. Pair with `AUG_G1_DST` or the deployment's own tag.
+    fn hash_to_curve_g1_aug(
+        &mut self,
+        pubkey: &AssignedG2Affine<G1Affine, N>,
+        message: &[AssignedCondition<N>],
+        dst: &Dst,
+    ) -> AssignedPoint<G1Affine, N> {
+        let mut augmented = self.ecc_g2_compress(pubkey);
+        augmented.extend_from_slice(message);
+        self.hash_to_curve_g1(&augmented, dst)
+    }
+
+    // Message points of an aggregate under `scheme`. Basic aggregates are
+    // only sound over distinct messages, so their points must differ.
+    fn hash_messages_g1(
+        &mut self,
+        scheme: BlsScheme,
+        pubkeys: &[AssignedG2Affine<G1Affine, N>],
+        messages: &[&[AssignedCondition<N>]],
+    ) -> Vec<AssignedPoint<G1Affine, N>> {
+        assert_eq!(pubkeys.len(), messages.len());
+
+        let dst = scheme.dst_g1();
+        let points = pubkeys
+            .iter()
+            .zip(messages.iter())
+            .map(|(pubkey, message)| {
+                if scheme.augments_message() {
+                    self.hash_to_curve_g1_aug(pubkey, message, &dst)
+                } else {
+                    self.hash_to_curve_g1(message, &dst)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if scheme.requires_distinct_messages() {
+            for (i, a) in points.iter().enumerate() {
+                for b in points[i + 1..].iter() {
+                    let eq = self.ecc_points_equal(a, b);
+                    // unsatisfiable rather than a prover panic on equal messages
+                    self.base_integer_chip()
+                        .base_chip()
+                        .assert_constant(&eq.0, N::zero());
+                }
+            }
+        }
+
+        points
+    }
+}
+
+impl<N: FieldExt, B: IntegerChipOps<Fq, N>> HashToCurveG1ChipOps<N>
+    for GeneralScalarEccContext<G1Affine, N, B>
+{
+    fn native_chip(&mut self) -> RefMut<'_, Context<N>> {
+        self.native_ctx.borrow_mut()
+    }
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_hash_to_curve_g1_matches_rfc_vector() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::NegativeTest;
+
+    // the whole SSWU, isogeny and cofactor clearing path in one circuit
+    let test = NegativeTest::<Fr>::new();
+    let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(test.ctx.clone());
+    let dst = Dst::new(b"QUUX-V01-CS02-with-BLS12381G1_XMD:SHA-256_SSWU_RO_");
+    let p = ctx.hash_to_curve_g1(&[], &dst);
+
+    assert_eq!(p.z.0.val, Fr::zero());
+    assert_eq!(
+        ctx.base_integer_ctx.get_w(&p.x),
+        fq("052926add2207b76ca4fa57a8734416c8dc95e24501772c814278700eed6d1e4e8cf62d9c09db0fac349612b759e79a1")
+    );
+    assert_eq!(
+        ctx.base_integer_ctx.get_w(&p.y),
+        fq("08ba738453bfed09cb546dbb0783dbb3a5f1f566ed67bb6be0e8c67e2e81a4cc68ee29813bb7994998f3eae0c9c6a265")
+    );
+
+    assert_eq!(test.run(&[]), vec![]);
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_hash_messages_g1_rejects_repeated_basic_messages() {
+    use halo2_proofs::pairing::bls12_381::G2Affine;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::{assign_bytes, NegativeTest};
+
+    let g2 = G2Affine::generator();
+    let g2 = ((g2.x.c0, g2.x.c1), (g2.y.c0, g2.y.c1));
+    // repeated messages leave the constraints unsatisfiable, without panicking
+    for (messages, satisfied) in [([b"a", b"b"], true), ([b"a", b"a"], false)] {
+        let test = NegativeTest::<Fr>::new();
+        let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(test.ctx.clone());
+        let pubkeys = vec![ctx.assign_non_identity_constant_g2(&g2); 2];
+        let messages = messages
+            .iter()
+            .map(|m| assign_bytes(&mut ctx.native_ctx.borrow_mut(), *m))
+            .collect::<Vec<_>>();
+        let messages = messages.iter().map(|m| &m[..]).collect::<Vec<_>>();
+        ctx.hash_messages_g1(BlsScheme::Basic, &pubkeys, &messages);
+
+        assert_eq!(test.run(&[]).is_empty(), satisfied);
+    }
+}
@@ -12,6 +12,7 @@ use super::{base_chip::BaseChipOps, range_chip::RangeChipOps};
 use crate::assign::{AssignedCondition, AssignedInteger, AssignedValue};
 use crate::context::IntegerContext;
 use crate::pair;
+use crate::range_info::MulSchedule;
 use crate::utils::{bn_to_field, field_to_bn};
 
 pub trait IntegerChipOps<W: BaseExt, N: FieldExt> {
@@ -69,22 +70,136 @@ pub trait IntegerChipOps<W: BaseExt, N: FieldExt> {
         b: &AssignedInteger<W, N>,
     ) -> AssignedInteger<W, N>;
     fn get_w(&self, a: &AssignedInteger<W, N>) -> W;
+    // Comparisons of the integers the limbs represent, not of their residues.
+    // Limbs must be range checked, i.e. `times == 1`.
+    fn int_lt(
+        &mut self,
+        a: &AssignedInteger<W, N>,
+        b: &AssignedInteger<W, N>,
+    ) -> AssignedCondition<N>;
+    fn int_le(
+        &mut self,
+        a: &AssignedInteger<W, N>,
+        b: &AssignedInteger<W, N>,
+    ) -> AssignedCondition<N>;
+    fn assert_int_less_than_modulus(&mut self, a: &AssignedInteger<W, N>);
+    // The representative of `a` in [0, w). `reduce` alone only bounds it by
+    // w_ceil, which leaves both r and r + w for small residues.
+    fn int_canonical(&mut self, a: &AssignedInteger<W, N>) -> AssignedInteger<W, N> {
+        let a = self.reduce(a);
+        self.assert_int_less_than_modulus(&a);
+        a
+    }
+    // Limb-wise equality of the canonical representatives, for callers that
+    // rely on the limbs themselves, e.g. to expose or hash them.
+    fn assert_int_equal_canonical(&mut self, a: &AssignedInteger<W, N>, b: &AssignedInteger<W, N>) {
+        let a = self.int_canonical(a);
+        let b = self.int_canonical(b);
+
+        let mut base_chip = self.base_chip();
+        for (a, b) in a.limbs_le.iter().zip(b.limbs_le.iter()) {
+            base_chip.assert_equal(a, b);
+        }
+    }
+    // Integer from little-endian bits, at most as many as the modulus has.
+    fn int_from_bits_le(&mut self, bits: &[AssignedCondition<N>]) -> AssignedInteger<W, N> {
+        let info = self.range_chip().info();
+        assert!(bits.len() as u64 <= info.w_ceil_bits);
+
+        let mut base_chip = self.base_chip();
+        let zero = AssignedCondition(base_chip.assign_constant(N::zero()));
+        let mut bits = bits.to_vec();
+        bits.resize((info.limbs * info.limb_bits) as usize, zero);
+
+        let limbs = bits
+            .chunks(info.limb_bits as usize)
+            .map(|chunk| {
+                let schema = chunk
+                    .iter()
+                    .enumerate()
+                    .map(|(i, b)| (&b.0, bn_to_field(&(BigUint::from(1u64) << i))))
+                    .collect();
+                base_chip.sum_with_constant(schema, None)
+            })
+            .collect::<Vec<_>>();
+        let native = base_chip.sum_with_constant(
+            limbs.iter().zip(info.limb_coeffs.iter().cloned()).collect(),
+            None,
+        );
+
+        AssignedInteger::new(limbs, native, 1)
+    }
+    // Same, for encodings that must be canonical: bits of a value >= w make
+    // the constraints unsatisfiable instead of being reduced.
+    fn int_from_canonical_bits_le(
+        &mut self,
+        bits: &[AssignedCondition<N>],
+    ) -> AssignedInteger<W, N> {
+        let a = self.int_from_bits_le(bits);
+        self.assert_int_less_than_modulus(&a);
+        a
+    }
+    // Little-endian bits of the canonical representative of `a`, checked
+    // against w - 1.
+    fn int_to_bits_le(&mut self, a: &AssignedInteger<W, N>) -> Vec<AssignedCondition<N>> {
+        let info = self.range_chip().info();
+        let v = field_to_bn(&self.get_w(a));
+
+        let bits = {
+            let mut base_chip = self.base_chip();
+            let bits = (0..info.w_ceil_bits)
+                .map(|i| base_chip.assign_bit(N::from(v.bit(i) as u64)))
+                .collect::<Vec<_>>();
+            base_chip.assert_bits_le_canonical(&bits, &(&info.w_modulus - 1u64));
+            bits
+        };
+
+        let c = self.int_from_bits_le(&bits);
+        self.assert_int_equal(&c, a);
+
+        bits
+    }
+    // Inverses of non-zero `xs` with Montgomery's trick: one inversion of the
+    // product of all of them and 3 * (len - 1) multiplications. Every product
+    // is constrained by int_mul, so a zero input fails the inversion.
+    fn int_batch_unsafe_invert(
+        &mut self,
+        xs: &[AssignedInteger<W, N>],
+    ) -> Vec<AssignedInteger<W, N>> {
+        if xs.is_empty() {
+            return vec![];
+        }
+
+        // prefix[i] = x_0 * ... * x_i
+        let mut prefix = vec![xs[0].clone()];
+        for x in xs[1..].iter() {
+            let p = self.int_mul(prefix.last().unwrap(), x);
+            prefix.push(p);
+        }
+
+        // acc = (x_0 * ... * x_i)^-1 when handling x_i
+        let mut acc = self.int_unsafe_invert(prefix.last().unwrap());
+        let mut res = vec![];
+        for i in (1..xs.len()).rev() {
+            res.push(self.int_mul(&acc, &prefix[i - 1]));
+            acc = self.int_mul(&acc, &xs[i]);
+        }
+        res.push(acc);
+        res.reverse();
+
+        res
+    }
 }
 
 impl<W: BaseExt, N: FieldExt> IntegerContext<W, N> {
-    fn add_constraints_for_mul_equation_on_limbs(
+    // l_pos = sum(a_i * b_j) - sum(d_i * w_j) over i + j = pos.
+    fn mul_equation_limbs_schoolbook(
         &mut self,
         a: &AssignedInteger<W, N>,
         b: &AssignedInteger<W, N>,
         d: &Vec<AssignedValue<N>>,
-        rem: &AssignedInteger<W, N>,
-    ) {
-        assert!(a.times < self.info().overflow_limit);
-        assert!(b.times < self.info().overflow_limit);
-        assert!(rem.times < self.info().overflow_limit);
-
+    ) -> Vec<AssignedValue<N>> {
         let info = self.info();
-        let one = N::one();
 
         let mut limbs = vec![];
         for pos in 0..info.mul_check_limbs as usize {
@@ -110,6 +225,94 @@ impl<W: BaseExt, N: FieldExt> IntegerContext<W, N> {
             limbs.push(l);
         }
 
+        limbs
+    }
+
+    // Same limbs as the schoolbook schedule, the cross products of every
+    // position come from one product per limb pair and the shared a_i * b_i.
+    fn mul_equation_limbs_karatsuba(
+        &mut self,
+        a: &AssignedInteger<W, N>,
+        b: &AssignedInteger<W, N>,
+        d: &Vec<AssignedValue<N>>,
+    ) -> Vec<AssignedValue<N>> {
+        let info = self.info();
+        let zero = N::zero();
+        let one = N::one();
+        let limbs = info.limbs as usize;
+
+        let mut ctx = self.ctx.borrow_mut();
+        let diagonal = (0..limbs)
+            .map(|i| ctx.mul(&a.limbs_le[i], &b.limbs_le[i]))
+            .collect::<Vec<_>>();
+
+        let mut res = vec![];
+        for pos in 0..info.mul_check_limbs as usize {
+            let r_bound = usize::min(pos + 1, limbs);
+            let l_bound = pos.checked_sub(limbs - 1).unwrap_or(0);
+
+            let mut cross = vec![];
+            for i in l_bound..r_bound {
+                let j = pos - i;
+                if i >= j {
+                    continue;
+                }
+
+                let a_sum = ctx.sum_with_constant_in_one_line(
+                    vec![(&a.limbs_le[i], one), (&a.limbs_le[j], one)],
+                    None,
+                );
+                let b_sum = ctx.sum_with_constant_in_one_line(
+                    vec![(&b.limbs_le[i], one), (&b.limbs_le[j], one)],
+                    None,
+                );
+                let v = a_sum.val * b_sum.val - diagonal[i].val - diagonal[j].val;
+                let (_, c) = ctx.one_line_with_last(
+                    vec![
+                        pair!(&a_sum, zero),
+                        pair!(&b_sum, zero),
+                        pair!(&diagonal[i], -one),
+                        pair!(&diagonal[j], -one),
+                    ],
+                    pair!(v, -one),
+                    None,
+                    (vec![one], None),
+                );
+                cross.push(c);
+            }
+
+            let mut schema = cross.iter().map(|c| (c, one)).collect::<Vec<_>>();
+            if pos % 2 == 0 && pos / 2 < limbs {
+                schema.push((&diagonal[pos / 2], one));
+            }
+            for i in l_bound..r_bound {
+                schema.push((&d[i], -info.w_modulus_limbs_le[pos - i]));
+            }
+            res.push(ctx.sum_with_constant(schema, None));
+        }
+
+        res
+    }
+
+    fn add_constraints_for_mul_equation_on_limbs(
+        &mut self,
+        a: &AssignedInteger<W, N>,
+        b: &AssignedInteger<W, N>,
+        d: &Vec<AssignedValue<N>>,
+        rem: &AssignedInteger<W, N>,
+    ) {
+        assert!(a.times < self.info().overflow_limit);
+        assert!(b.times < self.info().overflow_limit);
+        assert!(rem.times < self.info().overflow_limit);
+
+        let info = self.info();
+        let one = N::one();
+
+        let limbs = match info.mul_schedule {
+            MulSchedule::Schoolbook => self.mul_equation_limbs_schoolbook(a, b, d),
+            MulSchedule::Karatsuba => self.mul_equation_limbs_karatsuba(a, b, d),
+        };
+
         let borrow = N::from(info.limbs) * info.limb_modulus_n + N::from(2u64);
 
         // check sum limb[0]
@@ -185,6 +388,41 @@ impl<W: BaseExt, N: FieldExt> IntegerContext<W, N> {
         );
     }
 
+    // a < b by subtracting limb-wise from the lowest limb up, the borrow out
+    // of the top limb is set iff a < b. Each difference plus its borrow is
+    // range checked to a limb, which leaves a single valid borrow bit.
+    fn limbs_lt(&mut self, a: &[AssignedValue<N>], b: &[AssignedValue<N>]) -> AssignedCondition<N> {
+        assert_eq!(a.len(), b.len());
+
+        let info = self.info();
+        let one = N::one();
+
+        let mut borrow: Option<AssignedCondition<N>> = None;
+        for (a, b) in a.iter().zip(b.iter()) {
+            let a_bn = field_to_bn(&a.val);
+            let b_bn = field_to_bn(&b.val) + borrow.map_or(0u64, |c| (c.0.val == one) as u64);
+            let borrow_out = a_bn < b_bn;
+            let d = a_bn + &info.limb_modulus * (borrow_out as u64) - b_bn;
+
+            let d = self.assign_nonleading_limb(&d);
+            let borrow_out = self.ctx.borrow_mut().assign_bit(N::from(borrow_out as u64));
+            let mut schema = vec![
+                pair!(a, one),
+                pair!(b, -one),
+                pair!(&borrow_out.0, info.limb_modulus_n),
+                pair!(&d, -one),
+            ];
+            if let Some(borrow) = borrow.as_ref() {
+                schema.push(pair!(&borrow.0, -one));
+            }
+            self.ctx.borrow_mut().one_line_add(schema, None);
+
+            borrow = Some(borrow_out);
+        }
+
+        borrow.unwrap()
+    }
+
     fn get_w_bn(&self, a: &AssignedInteger<W, N>) -> BigUint {
         let mut res = BigUint::from(0u64);
         for i in (0..self.info().limbs as usize).rev() {
@@ -256,7 +494,6 @@ impl<W: BaseExt, N: FieldExt> IntegerChipOps<W, N> for IntegerContext<W, N> {
             return a.clone();
         }
 
-        let zero = N::zero();
         let one = N::one();
 
         let info = self.info();
@@ -319,16 +556,18 @@ impl<W: BaseExt, N: FieldExt> IntegerChipOps<W, N> for IntegerContext<W, N> {
             let v = self.assign_nonleading_limb(&v);
 
             // constrains on limb_modulus
+            let mut pairs = vec![
+                pair!(&assigned_d, info.w_modulus_limbs_le[i]),
+                pair!(&assigned_rem.limbs_le[i], one),
+                pair!(&a.limbs_le[i], -one),
+            ];
+            // no placeholder for the first limb, the audit would see an
+            // unconstrained zero
+            if let Some(last_v) = &last_v {
+                pairs.push(pair!(last_v, one));
+            }
             self.ctx.borrow_mut().one_line_with_last(
-                vec![
-                    pair!(&assigned_d, info.w_modulus_limbs_le[i]),
-                    pair!(&assigned_rem.limbs_le[i], one),
-                    pair!(&a.limbs_le[i], -one),
-                    match &last_v {
-                        Some(last_v) => pair!(last_v, one),
-                        None => pair!(zero, zero),
-                    },
-                ],
+                pairs,
                 pair!(&v, -bn_to_field::<N>(&info.limb_modulus)),
                 Some(bn_to_field(
                     &(&info.limb_modulus * overflow_limit
@@ -651,4 +890,228 @@ impl<W: BaseExt, N: FieldExt> IntegerChipOps<W, N> for IntegerContext<W, N> {
     fn get_w(&self, a: &AssignedInteger<W, N>) -> W {
         bn_to_field(&self.get_w_bn(a))
     }
+
+    fn int_lt(
+        &mut self,
+        a: &AssignedInteger<W, N>,
+        b: &AssignedInteger<W, N>,
+    ) -> AssignedCondition<N> {
+        assert_eq!(a.times, 1);
+        assert_eq!(b.times, 1);
+
+        self.limbs_lt(&a.limbs_le, &b.limbs_le)
+    }
+
+    fn int_le(
+        &mut self,
+        a: &AssignedInteger<W, N>,
+        b: &AssignedInteger<W, N>,
+    ) -> AssignedCondition<N> {
+        let gt = self.int_lt(b, a);
+        self.ctx.borrow_mut().not(&gt)
+    }
+
+    fn assert_int_less_than_modulus(&mut self, a: &AssignedInteger<W, N>) {
+        assert_eq!(a.times, 1);
+
+        let info = self.info();
+        let w_modulus_limbs = info
+            .w_modulus_limbs_le
+            .iter()
+            .map(|limb| self.ctx.borrow_mut().assign_constant(*limb))
+            .collect::<Vec<_>>();
+        let lt = self.limbs_lt(&a.limbs_le, &w_modulus_limbs);
+        self.ctx.borrow_mut().assert_true(&lt);
+    }
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_int_comparisons() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381::Fq;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::NegativeTest;
+
+    let w = field_to_bn(&-Fq::one()) + 1u64;
+    let values = [
+        BigUint::from(0u64),
+        BigUint::from(1u64) << 200,
+        (BigUint::from(1u64) << 200) + 1u64,
+        &w - 1u64,
+    ];
+
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("int comparisons", |ctx| {
+        let mut ctx = IntegerContext::<Fq, Fr>::new(ctx.clone());
+        let assigned = values.iter().map(|v| ctx.assign_w(v)).collect::<Vec<_>>();
+        for (a, a_bn) in assigned.iter().zip(values.iter()) {
+            ctx.assert_int_less_than_modulus(a);
+            for (b, b_bn) in assigned.iter().zip(values.iter()) {
+                let lt = ctx.int_lt(a, b);
+                let le = ctx.int_le(a, b);
+                assert_eq!(lt.0.val, Fr::from((a_bn < b_bn) as u64));
+                assert_eq!(le.0.val, Fr::from((a_bn <= b_bn) as u64));
+            }
+        }
+    });
+    assert_eq!(test.run(&[]), vec![]);
+
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("non-canonical", |ctx| {
+        let mut ctx = IntegerContext::<Fq, Fr>::new(ctx.clone());
+        let a = ctx.assign_w(&(&w + 1u64));
+        ctx.assert_int_less_than_modulus(&a);
+    });
+    assert!(!test.run(&[]).is_empty());
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_assert_int_equal_canonical() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381::Fq;
+    use halo2_proofs::pairing::bn256::Fr;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::negative_test::NegativeTest;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let x = Fq::random(&mut rng);
+    let y = Fq::random(&mut rng);
+    let w = field_to_bn(&-Fq::one()) + 1u64;
+
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("lazy sum", |ctx| {
+        let mut ctx = IntegerContext::<Fq, Fr>::new(ctx.clone());
+        let a = ctx.assign_w(&field_to_bn(&x));
+        let b = ctx.assign_w(&field_to_bn(&y));
+        let sum = ctx.int_add(&a, &b);
+        let expected = ctx.assign_w(&field_to_bn(&(x + y)));
+        ctx.assert_int_equal_canonical(&sum, &expected);
+    });
+    assert_eq!(test.run(&[]), vec![]);
+
+    // 5 + w has the residue of 5, assert_int_equal takes it.
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("non-canonical", |ctx| {
+        let mut ctx = IntegerContext::<Fq, Fr>::new(ctx.clone());
+        let a = ctx.assign_w(&BigUint::from(5u64));
+        let b = ctx.assign_w(&(&w + 5u64));
+        ctx.assert_int_equal(&a, &b);
+    });
+    assert_eq!(test.run(&[]), vec![]);
+
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("non-canonical", |ctx| {
+        let mut ctx = IntegerContext::<Fq, Fr>::new(ctx.clone());
+        let a = ctx.assign_w(&BigUint::from(5u64));
+        let b = ctx.assign_w(&(&w + 5u64));
+        ctx.assert_int_equal_canonical(&a, &b);
+    });
+    assert!(!test.run(&[]).is_empty());
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_int_bits_round_trip() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381::Fq;
+    use halo2_proofs::pairing::bn256::Fr;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::negative_test::NegativeTest;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("int bits", |ctx| {
+        let mut ctx = IntegerContext::<Fq, Fr>::new(ctx.clone());
+        for v in [Fq::zero(), -Fq::one(), Fq::random(&mut rng)] {
+            let a = ctx.assign_w(&field_to_bn(&v));
+            let a = ctx.int_add(&a, &a);
+            let bits = ctx.int_to_bits_le(&a);
+            assert_eq!(bits.len(), 381);
+
+            let expected = field_to_bn(&v.double());
+            for (i, b) in bits.iter().enumerate() {
+                assert_eq!(b.0.val == Fr::one(), expected.bit(i as u64));
+            }
+            let b = ctx.int_from_bits_le(&bits);
+            assert_eq!(ctx.get_w(&b), v.double());
+        }
+    });
+    assert_eq!(test.run(&[]), vec![]);
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_karatsuba_mul_schedule() {
+    use std::sync::Arc;
+
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381::Fq;
+    use halo2_proofs::pairing::bn256::Fr;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::circuit_utils::range_chip::{COMMON_RANGE_BITS, OVERFLOW_BITS};
+    use crate::negative_test::NegativeTest;
+    use crate::range_info::{MulSchedule, RangeInfo};
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let x = Fq::random(&mut rng);
+    let y = Fq::random(&mut rng);
+
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("karatsuba mul", |ctx| {
+        let info = RangeInfo::new(COMMON_RANGE_BITS, OVERFLOW_BITS)
+            .with_mul_schedule(MulSchedule::Karatsuba);
+        let mut ctx = IntegerContext::<Fq, Fr> {
+            ctx: ctx.clone(),
+            info: Arc::new(info),
+        };
+        let a = ctx.assign_w(&field_to_bn(&x));
+        let b = ctx.assign_w(&field_to_bn(&y));
+        let sum = ctx.int_add(&a, &b);
+        let c = ctx.int_mul(&a, &b);
+        let s = ctx.int_square(&sum);
+        assert_eq!(ctx.get_w(&c), x * y);
+        assert_eq!(ctx.get_w(&s), (x + y).square());
+    });
+    assert_eq!(test.run(&[]), vec![]);
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+#[test]
+fn test_int_batch_unsafe_invert() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381::Fq;
+    use halo2_proofs::pairing::bn256::Fr;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::negative_test::NegativeTest;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let xs = (0..5).map(|_| Fq::random(&mut rng)).collect::<Vec<_>>();
+
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("batch invert", |ctx| {
+        let mut ctx = IntegerContext::<Fq, Fr>::new(ctx.clone());
+        let assigned = xs
+            .iter()
+            .map(|x| ctx.assign_w(&field_to_bn(x)))
+            .collect::<Vec<_>>();
+
+        let inverses = ctx.int_batch_unsafe_invert(&assigned);
+        assert_eq!(inverses.len(), xs.len());
+        for (x, inv) in xs.iter().zip(inverses.iter()) {
+            assert_eq!(ctx.get_w(inv), x.invert().unwrap());
+        }
+        assert_eq!(ctx.int_batch_unsafe_invert(&assigned[..1]).len(), 1);
+    });
+    assert_eq!(test.run(&[]), vec![]);
 }
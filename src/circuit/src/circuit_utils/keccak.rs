@@ -0,0 +1,342 @@
+use halo2_proofs::arithmetic::FieldExt;
+
+use super::base_chip::BaseChipOps;
+use crate::assign::AssignedCondition;
+use crate::context::Context;
+use crate::pair;
+
+pub const KECCAK_RATE_BYTES: usize = 136;
+const KECCAK_STATE_BITS: usize = 1600;
+const KECCAK_LANE_BITS: usize = 64;
+
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000000000000001,
+    0x0000000000008082,
+    0x800000000000808A,
+    0x8000000080008000,
+    0x000000000000808B,
+    0x0000000080000001,
+    0x8000000080008081,
+    0x8000000000008009,
+    0x000000000000008A,
+    0x0000000000000088,
+    0x0000000080008009,
+    0x000000008000000A,
+    0x000000008000808B,
+    0x800000000000008B,
+    0x8000000000008089,
+    0x8000000000008003,
+    0x8000000000008002,
+    0x8000000000000080,
+    0x000000000000800A,
+    0x800000008000000A,
+    0x8000000080008081,
+    0x8000000000008080,
+    0x0000000080000001,
+    0x8000000080008008,
+];
+
+// ROTATIONS[x][y]
+const ROTATIONS: [[usize; 5]; 5] = [
+    [0, 36, 3, 41, 18],
+    [1, 44, 10, 45, 2],
+    [62, 6, 43, 15, 61],
+    [28, 55, 25, 21, 56],
+    [27, 20, 39, 8, 14],
+];
+
+fn bit_index(x: usize, y: usize, z: usize) -> usize {
+    (x % 5 + 5 * (y % 5)) * KECCAK_LANE_BITS + z % KECCAK_LANE_BITS
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeccakPadding {
+    // Original pad10*1, as used by Ethereum.
+    Keccak,
+    // FIPS 202 SHA-3, the domain bits 01 come before pad10*1.
+    Sha3,
+}
+
+// Absorbed bits are buffered until a whole block is available, so input can
+// be fed in pieces of any length.
+#[derive(Clone, Debug)]
+pub struct KeccakSponge<N: FieldExt> {
+    pub rate_bits: usize,
+    pub padding: KeccakPadding,
+    // None until the first block, the initial state is all zero.
+    state: Option<Vec<AssignedCondition<N>>>,
+    pending: Vec<AssignedCondition<N>>,
+    squeezing: bool,
+    // Output bits already taken from the current state.
+    squeezed: usize,
+}
+
+impl<N: FieldExt> KeccakSponge<N> {
+    pub fn new(rate_bytes: usize, padding: KeccakPadding) -> Self {
+        assert!(rate_bytes > 0 && rate_bytes * 8 < KECCAK_STATE_BITS);
+
+        Self {
+            rate_bits: rate_bytes * 8,
+            padding,
+            state: None,
+            pending: vec![],
+            squeezing: false,
+            squeezed: 0,
+        }
+    }
+}
+
+// Bits are little-endian inside each byte and bytes keep their message order,
+// which makes the message bit index equal to the keccak state bit index.
+pub trait KeccakChipOps<N: FieldExt>: BaseChipOps<N> {
+    // (1 - a) * b
+    fn keccak_and_not(
+        &mut self,
+        a: &AssignedCondition<N>,
+        b: &AssignedCondition<N>,
+    ) -> AssignedCondition<N> {
+        let zero = N::zero();
+        let one = N::one();
+        let c = (one - a.0.val) * b.0.val;
+
+        let cells = self.one_line_with_last(
+            vec![pair!(&a.0, zero), pair!(&b.0, one)],
+            pair!(c, -one),
+            None,
+            (vec![-one], None),
+        );
+
+        AssignedCondition(cells.1)
+    }
+
+    fn keccak_f(&mut self, state: Vec<AssignedCondition<N>>) -> Vec<AssignedCondition<N>> {
+        assert_eq!(state.len(), KECCAK_STATE_BITS);
+        let mut a = state;
+
+        for rc in ROUND_CONSTANTS {
+            // theta
+            let mut c = vec![];
+            for x in 0..5 {
+                for z in 0..KECCAK_LANE_BITS {
+                    let mut acc = a[bit_index(x, 0, z)];
+                    for y in 1..5 {
+                        acc = self.xor(&acc, &a[bit_index(x, y, z)]);
+                    }
+                    c.push(acc);
+                }
+            }
+            let mut d = vec![];
+            for x in 0..5 {
+                for z in 0..KECCAK_LANE_BITS {
+                    let l = &c[bit_index(x + 4, 0, z)];
+                    let r = &c[bit_index(x + 1, 0, z + KECCAK_LANE_BITS - 1)];
+                    d.push(self.xor(l, r));
+                }
+            }
+            for y in 0..5 {
+                for x in 0..5 {
+                    for z in 0..KECCAK_LANE_BITS {
+                        let i = bit_index(x, y, z);
+                        a[i] = self.xor(&a[i], &d[bit_index(x, 0, z)]);
+                    }
+                }
+            }
+
+            // rho and pi
+            let mut b = a.clone();
+            for x in 0..5 {
+                for y in 0..5 {
+                    for z in 0..KECCAK_LANE_BITS {
+                        b[bit_index(y, 2 * x + 3 * y, z)] =
+                            a[bit_index(x, y, z + KECCAK_LANE_BITS - ROTATIONS[x][y])];
+                    }
+                }
+            }
+
+            // chi
+            for y in 0..5 {
+                for x in 0..5 {
+                    for z in 0..KECCAK_LANE_BITS {
+                        let t = self
+                            .keccak_and_not(&b[bit_index(x + 1, y, z)], &b[bit_index(x + 2, y, z)]);
+                        a[bit_index(x, y, z)] = self.xor(&b[bit_index(x, y, z)], &t);
+                    }
+                }
+            }
+
+            // iota
+            for z in 0..KECCAK_LANE_BITS {
+                if (rc >> z) & 1 == 1 {
+                    a[z] = self.not(&a[z]);
+                }
+            }
+        }
+
+        a
+    }
+
+    fn keccak_sponge_absorb(
+        &mut self,
+        sponge: &mut KeccakSponge<N>,
+        bits: &[AssignedCondition<N>],
+    ) {
+        assert!(!sponge.squeezing);
+
+        sponge.pending.extend_from_slice(bits);
+        while sponge.pending.len() >= sponge.rate_bits {
+            let block = sponge.pending.drain(..sponge.rate_bits).collect::<Vec<_>>();
+            let absorbed = match sponge.state.take() {
+                None => {
+                    let zero = AssignedCondition(self.assign_constant(N::zero()));
+                    let mut s = block;
+                    s.resize(KECCAK_STATE_BITS, zero);
+                    s
+                }
+                Some(mut s) => {
+                    for (i, bit) in block.iter().enumerate() {
+                        s[i] = self.xor(&s[i], bit);
+                    }
+                    s
+                }
+            };
+            sponge.state = Some(self.keccak_f(absorbed));
+        }
+    }
+
+    // Pads the absorbed input on the first call, then every call continues
+    // the output stream where the previous one stopped.
+    fn keccak_sponge_squeeze(
+        &mut self,
+        sponge: &mut KeccakSponge<N>,
+        n_bits: usize,
+    ) -> Vec<AssignedCondition<N>> {
+        if !sponge.squeezing {
+            let zero = AssignedCondition(self.assign_constant(N::zero()));
+            let one = AssignedCondition(self.assign_constant(N::one()));
+
+            let mut padding = match sponge.padding {
+                KeccakPadding::Keccak => vec![],
+                KeccakPadding::Sha3 => vec![zero, one],
+            };
+            padding.push(one);
+            while (sponge.pending.len() + padding.len()) % sponge.rate_bits != sponge.rate_bits - 1
+            {
+                padding.push(zero);
+            }
+            padding.push(one);
+
+            self.keccak_sponge_absorb(sponge, &padding);
+            sponge.squeezing = true;
+        }
+
+        let mut out = vec![];
+        while out.len() < n_bits {
+            if sponge.squeezed == sponge.rate_bits {
+                let state = sponge.state.take().unwrap();
+                sponge.state = Some(self.keccak_f(state));
+                sponge.squeezed = 0;
+            }
+
+            let n = usize::min(n_bits - out.len(), sponge.rate_bits - sponge.squeezed);
+            let state = sponge.state.as_ref().unwrap();
+            out.extend_from_slice(&state[sponge.squeezed..sponge.squeezed + n]);
+            sponge.squeezed += n;
+        }
+
+        out
+    }
+
+    // Keccak-256 (pre-NIST padding) of a byte aligned bit string,
+    // returns the 256 digest bits in the same layout.
+    fn keccak256(&mut self, message: &[AssignedCondition<N>]) -> Vec<AssignedCondition<N>> {
+        assert_eq!(message.len() % 8, 0);
+
+        let mut sponge = KeccakSponge::new(KECCAK_RATE_BYTES, KeccakPadding::Keccak);
+        self.keccak_sponge_absorb(&mut sponge, message);
+        self.keccak_sponge_squeeze(&mut sponge, 256)
+    }
+
+    // FIPS 202 SHA3-256, same layout as `keccak256`.
+    fn sha3_256(&mut self, message: &[AssignedCondition<N>]) -> Vec<AssignedCondition<N>> {
+        assert_eq!(message.len() % 8, 0);
+
+        let mut sponge = KeccakSponge::new(KECCAK_RATE_BYTES, KeccakPadding::Sha3);
+        self.keccak_sponge_absorb(&mut sponge, message);
+        self.keccak_sponge_squeeze(&mut sponge, 256)
+    }
+}
+
+impl<N: FieldExt> KeccakChipOps<N> for Context<N> {}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_keccak_chip_matches_native() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+    use tiny_keccak::{Hasher, Keccak};
+
+    use crate::negative_test::{assign_bytes, to_bytes, NegativeTest};
+
+    let message = (0..150u64)
+        .map(|i| (i * 37 % 256) as u8)
+        .collect::<Vec<_>>();
+
+    let mut expected = [0u8; 32];
+    let mut hasher = Keccak::v256();
+    hasher.update(&message);
+    hasher.finalize(&mut expected);
+
+    let mut test = NegativeTest::<Fr>::new();
+    let digest = test.gadget("keccak256", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let bits = assign_bytes(&mut ctx, &message);
+        ctx.keccak256(&bits)
+    });
+    assert_eq!(to_bytes(&digest), expected);
+
+    assert_eq!(test.run(&[]), vec![]);
+    let flipped = Fr::one() - digest[0].0.val;
+    assert!(!test.run(&[(digest[0].0.cell, flipped)]).is_empty());
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_keccak_sponge_streams_input_and_output() {
+    use halo2_proofs::pairing::bn256::Fr;
+    use tiny_keccak::{Hasher, Keccak};
+
+    use crate::negative_test::{assign_bytes, to_bytes};
+
+    let message = (0..200u64)
+        .map(|i| (i * 53 % 256) as u8)
+        .collect::<Vec<_>>();
+
+    // Longer than one rate, so squeezing runs the permutation again.
+    let mut expected = [0u8; 150];
+    let mut hasher = Keccak::v256();
+    hasher.update(&message);
+    hasher.finalize(&mut expected);
+
+    let mut ctx = Context::<Fr>::new();
+    let bits = assign_bytes(&mut ctx, &message);
+
+    let mut sponge = KeccakSponge::new(KECCAK_RATE_BYTES, KeccakPadding::Keccak);
+    for piece in bits.chunks(8 * 70) {
+        ctx.keccak_sponge_absorb(&mut sponge, piece);
+    }
+    let mut output = ctx.keccak_sponge_squeeze(&mut sponge, 8 * 100);
+    output.extend(ctx.keccak_sponge_squeeze(&mut sponge, 8 * 50));
+
+    assert_eq!(to_bytes(&output), expected);
+
+    // SHA3-256("abc") from FIPS 202
+    let bits = assign_bytes(&mut ctx, b"abc");
+    let digest = to_bytes(&ctx.sha3_256(&bits))
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    assert_eq!(
+        digest,
+        "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"
+    );
+}
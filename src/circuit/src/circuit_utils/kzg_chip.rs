@@ -0,0 +1,176 @@
+use halo2_proofs::arithmetic::{CurveAffine, Field, FieldExt};
+
+use super::base_chip::BaseChipOps;
+use super::ecc_chip::{EccChipBaseOps, EccChipScalarOps};
+use super::integer_chip::IntegerChipOps;
+use super::poseidon::{PoseidonChipOps, PoseidonSpec};
+use crate::assign::{AssignedInteger, AssignedPoint};
+use crate::context::GeneralScalarEccContext;
+use crate::utils::field_to_bn;
+
+// (lhs, rhs) with the deferred check e(lhs, [s]_2) == e(rhs, [1]_2)
+pub type AssignedKzgAccumulator<C, N> = (AssignedPoint<C, N>, AssignedPoint<C, N>);
+
+// Verifier side of KZG openings for proofs committed over `C`,
+// see `crate::aggregation` for the native counterpart.
+pub trait KzgChipOps<C: CurveAffine, N: FieldExt> {
+    fn assign_kzg_accumulator(&mut self, lhs: &C, rhs: &C) -> AssignedKzgAccumulator<C, N>;
+
+    // Reduce the opening `p(z) = eval` of `commitment` with quotient
+    // commitment `proof` to an accumulator.
+    fn kzg_opening_to_accumulator(
+        &mut self,
+        commitment: &AssignedPoint<C, N>,
+        proof: &AssignedPoint<C, N>,
+        z: &AssignedInteger<C::Scalar, N>,
+        eval: &AssignedInteger<C::Scalar, N>,
+    ) -> AssignedKzgAccumulator<C, N>;
+
+    // In-circuit equivalent of `KzgAccumulator::fold`.
+    fn kzg_fold_accumulators(
+        &mut self,
+        accumulators: &[AssignedKzgAccumulator<C, N>],
+    ) -> AssignedKzgAccumulator<C, N>;
+}
+
+impl<C: CurveAffine, N: FieldExt, B: IntegerChipOps<C::Base, N>> KzgChipOps<C, N>
+    for GeneralScalarEccContext<C, N, B>
+{
+    fn assign_kzg_accumulator(&mut self, lhs: &C, rhs: &C) -> AssignedKzgAccumulator<C, N> {
+        (
+            self.assign_non_zero_point(lhs),
+            self.assign_non_zero_point(rhs),
+        )
+    }
+
+    fn kzg_opening_to_accumulator(
+        &mut self,
+        commitment: &AssignedPoint<C, N>,
+        proof: &AssignedPoint<C, N>,
+        z: &AssignedInteger<C::Scalar, N>,
+        eval: &AssignedInteger<C::Scalar, N>,
+    ) -> AssignedKzgAccumulator<C, N> {
+        // e(W, [s]_2) == e(C - eval * G + z * W, [1]_2)
+        let g = self.assign_constant_point(&C::generator());
+        let one = self
+            .scalar_integer_ctx
+            .assign_int_constant(C::Scalar::one());
+        let neg_eval = self.scalar_integer_ctx.int_neg(eval);
+
+        let rhs = self.msm(
+            &vec![commitment.clone(), proof.clone(), g],
+            &vec![one, z.clone(), neg_eval],
+        );
+
+        (proof.clone(), rhs)
+    }
+
+    fn kzg_fold_accumulators(
+        &mut self,
+        accumulators: &[AssignedKzgAccumulator<C, N>],
+    ) -> AssignedKzgAccumulator<C, N> {
+        // The challenge is squeezed on the native field and reused as a scalar.
+        assert_eq!(
+            field_to_bn(&-C::Scalar::one()),
+            field_to_bn(&-N::one()),
+            "accumulator folding needs a native scalar field"
+        );
+        assert!(!accumulators.is_empty());
+
+        // The native transcript hashes the canonical limbs, msm outputs are
+        // only reduced below w_ceil.
+        let mut transcript = vec![];
+        for (lhs, rhs) in accumulators {
+            for coordinate in [&lhs.x, &lhs.y, &rhs.x, &rhs.y] {
+                let canonical = self.base_integer_ctx.int_canonical(coordinate);
+                transcript.extend(canonical.limbs_le);
+            }
+        }
+
+        let spec = PoseidonSpec::<N>::new();
+        let challenge = self
+            .native_ctx
+            .borrow_mut()
+            .poseidon_hash(&spec, &transcript);
+        let challenge_scalar = self
+            .scalar_integer_ctx
+            .assign_w(&field_to_bn(&challenge.val));
+        self.native_ctx
+            .borrow_mut()
+            .assert_equal(&challenge_scalar.native, &challenge);
+
+        let mut powers = vec![self
+            .scalar_integer_ctx
+            .assign_int_constant(C::Scalar::one())];
+        for i in 1..accumulators.len() {
+            let power = self
+                .scalar_integer_ctx
+                .int_mul(&powers[i - 1], &challenge_scalar);
+            powers.push(power);
+        }
+
+        let lhs = accumulators.iter().map(|acc| acc.0.clone()).collect();
+        let rhs = accumulators.iter().map(|acc| acc.1.clone()).collect();
+
+        (self.msm(&lhs, &powers), self.msm(&rhs, &powers))
+    }
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_kzg_fold_matches_native() {
+    use halo2_proofs::pairing::bn256::{Fr, G1Affine, G2Affine};
+    use halo2_proofs::pairing::group::Curve;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::aggregation::KzgAccumulator;
+    use crate::negative_test::NegativeTest;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let s = Fr::random(&mut rng);
+    let (z, eval) = (Fr::random(&mut rng), Fr::random(&mut rng));
+    // an opening valid for s: C = eval * G + (s - z) * W
+    let proof = (G1Affine::generator() * Fr::random(&mut rng)).to_affine();
+    let commitment = (G1Affine::generator() * eval + proof * (s - z)).to_affine();
+    let opened = KzgAccumulator {
+        lhs: proof,
+        rhs: (proof * s).to_affine(),
+    };
+    let other = KzgAccumulator {
+        lhs: (G1Affine::generator() * Fr::random(&mut rng)).to_affine(),
+        rhs: (G1Affine::generator() * Fr::random(&mut rng)).to_affine(),
+    };
+    let folded = KzgAccumulator::fold(&[opened, other]).unwrap();
+
+    let test = NegativeTest::<Fr>::new();
+    let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(test.ctx.clone());
+    let assert_point = |ctx: &mut GeneralScalarEccContext<G1Affine, Fr>,
+                        p: &AssignedPoint<G1Affine, Fr>,
+                        expected: &G1Affine| {
+        let expected = expected.coordinates().unwrap();
+        assert_eq!(p.z.0.val, Fr::zero());
+        assert_eq!(ctx.base_integer_ctx.get_w(&p.x), *expected.x());
+        assert_eq!(ctx.base_integer_ctx.get_w(&p.y), *expected.y());
+    };
+
+    let commitment = ctx.assign_non_zero_point(&commitment);
+    let proof = ctx.assign_non_zero_point(&proof);
+    let z = ctx.scalar_integer_ctx.assign_w(&field_to_bn(&z));
+    let eval = ctx.scalar_integer_ctx.assign_w(&field_to_bn(&eval));
+    let opening = ctx.kzg_opening_to_accumulator(&commitment, &proof, &z, &eval);
+    assert_point(&mut ctx, &opening.0, &opened.lhs);
+    assert_point(&mut ctx, &opening.1, &opened.rhs);
+
+    let other = ctx.assign_kzg_accumulator(&other.lhs, &other.rhs);
+    let (lhs, rhs) = ctx.kzg_fold_accumulators(&[opening, other]);
+    assert_point(&mut ctx, &lhs, &folded.lhs);
+    assert_point(&mut ctx, &rhs, &folded.rhs);
+
+    let g2 = G2Affine::generator();
+    assert!(!folded.decide(&g2, &(g2 * s).to_affine()));
+    assert!(KzgAccumulator::fold(&[opened])
+        .unwrap()
+        .decide(&g2, &(g2 * s).to_affine()));
+    assert_eq!(test.run(&[]), vec![]);
+}
@@ -0,0 +1,251 @@
+use halo2_proofs::arithmetic::FieldExt;
+
+use super::base_chip::BaseChipOps;
+use super::poseidon::{PoseidonChipOps, PoseidonSpec};
+use super::sha256::Sha256ChipOps;
+use crate::assign::{AssignedCondition, AssignedValue};
+use crate::context::Context;
+
+// Node hash of a Merkle tree, with the node type it works on.
+pub trait MerkleHasher<N: FieldExt> {
+    type Node: Clone;
+
+    fn hash_pair(&self, ctx: &mut Context<N>, left: &Self::Node, right: &Self::Node) -> Self::Node;
+    // if cond then a else b
+    fn select(
+        &self,
+        ctx: &mut Context<N>,
+        cond: &AssignedCondition<N>,
+        a: &Self::Node,
+        b: &Self::Node,
+    ) -> Self::Node;
+    fn assert_equal(&self, ctx: &mut Context<N>, a: &Self::Node, b: &Self::Node);
+}
+
+// sha256(left || right) over 256-bit nodes in the hash chip bit layout, as in
+// SSZ and the beacon state.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sha256Merkle;
+
+impl<N: FieldExt> MerkleHasher<N> for Sha256Merkle {
+    type Node = Vec<AssignedCondition<N>>;
+
+    fn hash_pair(&self, ctx: &mut Context<N>, left: &Self::Node, right: &Self::Node) -> Self::Node {
+        assert_eq!(left.len(), 256);
+        assert_eq!(right.len(), 256);
+        ctx.sha256(&[&left[..], &right[..]].concat())
+    }
+
+    fn select(
+        &self,
+        ctx: &mut Context<N>,
+        cond: &AssignedCondition<N>,
+        a: &Self::Node,
+        b: &Self::Node,
+    ) -> Self::Node {
+        a.iter()
+            .zip(b.iter())
+            .map(|(a, b)| ctx.bisec_cond(cond, a, b))
+            .collect()
+    }
+
+    fn assert_equal(&self, ctx: &mut Context<N>, a: &Self::Node, b: &Self::Node) {
+        for (a, b) in a.iter().zip(b.iter()) {
+            ctx.assert_equal(&a.0, &b.0);
+        }
+    }
+}
+
+// Two-input Poseidon over native field nodes.
+#[derive(Clone, Debug)]
+pub struct PoseidonMerkle<N: FieldExt> {
+    pub spec: PoseidonSpec<N>,
+}
+
+impl<N: FieldExt> PoseidonMerkle<N> {
+    pub fn new() -> Self {
+        Self {
+            spec: PoseidonSpec::new(),
+        }
+    }
+
+    // Off-circuit node hash, to build the trees being proven against.
+    pub fn hash_pair_native(&self, left: N, right: N) -> N {
+        self.spec.hash(&[left, right])
+    }
+}
+
+impl<N: FieldExt> Default for PoseidonMerkle<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: FieldExt> MerkleHasher<N> for PoseidonMerkle<N> {
+    type Node = AssignedValue<N>;
+
+    fn hash_pair(&self, ctx: &mut Context<N>, left: &Self::Node, right: &Self::Node) -> Self::Node {
+        ctx.poseidon_hash(&self.spec, &[*left, *right])
+    }
+
+    fn select(
+        &self,
+        ctx: &mut Context<N>,
+        cond: &AssignedCondition<N>,
+        a: &Self::Node,
+        b: &Self::Node,
+    ) -> Self::Node {
+        ctx.bisec(cond, a, b)
+    }
+
+    fn assert_equal(&self, ctx: &mut Context<N>, a: &Self::Node, b: &Self::Node) {
+        ctx.assert_equal(a, b)
+    }
+}
+
+pub trait MerkleChipOps<N: FieldExt> {
+    // `index_bits` are little-endian, bit i is set when the node at depth i
+    // above the leaf is a right child. `branch[i]` is its sibling.
+    fn merkle_root<H: MerkleHasher<N>>(
+        &mut self,
+        hasher: &H,
+        leaf: &H::Node,
+        branch: &[H::Node],
+        index_bits: &[AssignedCondition<N>],
+    ) -> H::Node;
+
+    fn merkle_verify<H: MerkleHasher<N>>(
+        &mut self,
+        hasher: &H,
+        leaf: &H::Node,
+        branch: &[H::Node],
+        index_bits: &[AssignedCondition<N>],
+        root: &H::Node,
+    );
+}
+
+impl<N: FieldExt> MerkleChipOps<N> for Context<N> {
+    fn merkle_root<H: MerkleHasher<N>>(
+        &mut self,
+        hasher: &H,
+        leaf: &H::Node,
+        branch: &[H::Node],
+        index_bits: &[AssignedCondition<N>],
+    ) -> H::Node {
+        assert_eq!(branch.len(), index_bits.len());
+
+        let mut node = leaf.clone();
+        for (sibling, bit) in branch.iter().zip(index_bits.iter()) {
+            let left = hasher.select(self, bit, sibling, &node);
+            let right = hasher.select(self, bit, &node, sibling);
+            node = hasher.hash_pair(self, &left, &right);
+        }
+
+        node
+    }
+
+    fn merkle_verify<H: MerkleHasher<N>>(
+        &mut self,
+        hasher: &H,
+        leaf: &H::Node,
+        branch: &[H::Node],
+        index_bits: &[AssignedCondition<N>],
+        root: &H::Node,
+    ) {
+        let computed = self.merkle_root(hasher, leaf, branch, index_bits);
+        hasher.assert_equal(self, &computed, root);
+    }
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_merkle_verify_with_poseidon() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::NegativeTest;
+
+    let hasher = PoseidonMerkle::<Fr>::new();
+    let leaves = (0..4u64).map(|i| Fr::from(i + 100)).collect::<Vec<_>>();
+    let n01 = hasher.hash_pair_native(leaves[0], leaves[1]);
+    let n23 = hasher.hash_pair_native(leaves[2], leaves[3]);
+    let root = hasher.hash_pair_native(n01, n23);
+
+    // leaf 1 is a left child's right child
+    let verify = |sibling: Fr| {
+        let mut test = NegativeTest::<Fr>::new();
+        let sibling = test.gadget("merkle", |ctx| {
+            let mut ctx = ctx.borrow_mut();
+            let leaf = ctx.assign(leaves[1]);
+            let branch = [ctx.assign(sibling), ctx.assign(n23)];
+            let index_bits = [ctx.assign_bit(Fr::one()), ctx.assign_bit(Fr::zero())];
+            let root = ctx.assign(root);
+            let computed = ctx.merkle_root(&hasher, &leaf, &branch, &index_bits);
+            assert_eq!(computed.val == root.val, sibling == leaves[0]);
+            hasher.assert_equal(&mut ctx, &computed, &root);
+            branch[0]
+        });
+        (test, sibling)
+    };
+
+    let (test, sibling) = verify(leaves[0]);
+    assert_eq!(test.run(&[]), vec![]);
+    // a sibling tampered with after hashing no longer matches its copies
+    let failures = test.run(&[(sibling.cell, leaves[2])]);
+    assert!(!failures.is_empty());
+    assert!(failures.iter().all(|(gadget, _)| *gadget == "merkle"));
+
+    // another sibling leads to another root
+    let (test, _) = verify(leaves[2]);
+    assert!(!test.run(&[]).is_empty());
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_merkle_verify_with_sha256() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::{assign_bytes, to_bytes, NegativeTest};
+
+    let hex = |s: &str| {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let root = hex("d35f51699389da7eec7ce5eb02640c6d318cf51ae39eca890bbc7b84ecb5da68");
+
+    // leaves are [i; 32], proving leaf 2
+    let verify = |sibling: &[u8]| {
+        let mut test = NegativeTest::<Fr>::new();
+        let sibling = test.gadget("merkle", |ctx| {
+            let mut ctx = ctx.borrow_mut();
+            let leaf = assign_bytes(&mut ctx, &[2u8; 32]);
+            let branch = [
+                assign_bytes(&mut ctx, sibling),
+                assign_bytes(
+                    &mut ctx,
+                    &hex("5c85955f709283ecce2b74f1b1552918819f390911816e7bb466805a38ab87f3"),
+                ),
+            ];
+            let index_bits = [ctx.assign_bit(Fr::zero()), ctx.assign_bit(Fr::one())];
+            let expected = assign_bytes(&mut ctx, &root);
+            let computed = ctx.merkle_root(&Sha256Merkle, &leaf, &branch, &index_bits);
+            assert_eq!(to_bytes(&computed) == root, sibling == [3u8; 32]);
+            Sha256Merkle.assert_equal(&mut ctx, &computed, &expected);
+            branch[0].clone()
+        });
+        (test, sibling)
+    };
+
+    let (test, sibling) = verify(&[3u8; 32]);
+    assert_eq!(test.run(&[]), vec![]);
+    // flipping a bit of the sibling after hashing breaks its copies
+    let failures = test.run(&[(sibling[0].0.cell, Fr::one() - sibling[0].0.val)]);
+    assert!(!failures.is_empty());
+    assert!(failures.iter().all(|(gadget, _)| *gadget == "merkle"));
+
+    let (test, _) = verify(&[4u8; 32]);
+    assert!(!test.run(&[]).is_empty());
+}
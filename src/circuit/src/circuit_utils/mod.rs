@@ -1,6 +1,29 @@
+pub mod aggregate;
 pub mod base_chip;
+#[cfg(feature = "bls12381-target")]
+pub mod bdn;
+pub mod blake2;
+#[cfg(feature = "bls12381-target")]
+pub mod compress;
 pub mod ecc_chip;
 pub mod fq2;
+#[cfg(feature = "bls12381-target")]
+pub mod frobenius;
 mod general_scalar_ecc_chip;
+#[cfg(feature = "bls12381-target")]
+pub mod gls;
+#[cfg(feature = "bls12381-target")]
+pub mod hash_to_curve;
 pub mod integer_chip;
+#[cfg(feature = "bls12381-target")]
+pub mod isogeny;
+pub mod keccak;
+pub mod kzg_chip;
+pub mod merkle;
+pub mod poseidon;
 pub mod range_chip;
+pub mod sha256;
+pub mod sha512;
+pub mod ssz;
+#[cfg(feature = "bls12381-target")]
+pub mod subgroup;
@@ -0,0 +1,432 @@
+use halo2_proofs::arithmetic::FieldExt;
+use num_bigint::BigUint;
+
+use super::base_chip::BaseChipOps;
+use crate::assign::AssignedValue;
+use crate::context::Context;
+use crate::utils::{bn_to_field, field_to_bn};
+
+pub const POSEIDON_T: usize = 5;
+pub const POSEIDON_RATE: usize = POSEIDON_T - 1;
+pub const POSEIDON_R_F: usize = 8;
+pub const POSEIDON_R_P: usize = 60;
+
+// Grain LFSR used by the reference implementation to derive round constants
+// and the MDS matrix.
+struct Grain {
+    state: Vec<bool>,
+}
+
+impl Grain {
+    fn new(field_bits: u64, t: usize, r_f: usize, r_p: usize) -> Self {
+        let mut state = vec![];
+        let mut push = |v: u64, n: usize| {
+            for i in (0..n).rev() {
+                state.push((v >> i) & 1 == 1);
+            }
+        };
+        // prime field, x^alpha sbox
+        push(1, 2);
+        push(0, 4);
+        push(field_bits, 12);
+        push(t as u64, 12);
+        push(r_f as u64, 10);
+        push(r_p as u64, 10);
+        push((1 << 30) - 1, 30);
+
+        let mut grain = Self { state };
+        for _ in 0..160 {
+            grain.next_bit();
+        }
+        grain
+    }
+
+    fn next_bit(&mut self) -> bool {
+        let s = &self.state;
+        let b = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.state.remove(0);
+        self.state.push(b);
+        b
+    }
+
+    fn next_filtered_bit(&mut self) -> bool {
+        loop {
+            let b1 = self.next_bit();
+            let b2 = self.next_bit();
+            if b1 {
+                return b2;
+            }
+        }
+    }
+
+    fn next_bits(&mut self, n: u64) -> BigUint {
+        let mut v = BigUint::from(0u64);
+        for _ in 0..n {
+            v = (v << 1) + if self.next_filtered_bit() { 1u64 } else { 0u64 };
+        }
+        v
+    }
+
+    fn next_field<N: FieldExt>(&mut self, modulus: &BigUint) -> N {
+        loop {
+            let v = self.next_bits(modulus.bits());
+            if &v < modulus {
+                return bn_to_field(&v);
+            }
+        }
+    }
+
+    // The MDS entries are drawn reduced instead of rejected.
+    fn next_field_reduced<N: FieldExt>(&mut self, modulus: &BigUint) -> N {
+        bn_to_field(&(self.next_bits(modulus.bits()) % modulus))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PoseidonSpec<N: FieldExt> {
+    pub round_constants: Vec<[N; POSEIDON_T]>,
+    pub mds: [[N; POSEIDON_T]; POSEIDON_T],
+}
+
+impl<N: FieldExt> PoseidonSpec<N> {
+    pub fn new() -> Self {
+        let modulus = field_to_bn(&-N::one()) + 1u64;
+        let mut grain = Grain::new(modulus.bits(), POSEIDON_T, POSEIDON_R_F, POSEIDON_R_P);
+
+        let round_constants = (0..POSEIDON_R_F + POSEIDON_R_P)
+            .map(|_| [(); POSEIDON_T].map(|_| grain.next_field::<N>(&modulus)))
+            .collect();
+
+        // Cauchy matrix 1 / (x_i + y_j) over the next 2T distinct draws, as
+        // create_mds_p of the reference. Its security checks pass on the
+        // first draw for the BN254 parameters.
+        let (xs, ys) = loop {
+            let draws = (0..2 * POSEIDON_T)
+                .map(|_| grain.next_field_reduced::<N>(&modulus))
+                .collect::<Vec<_>>();
+            let distinct = (0..draws.len()).all(|i| !draws[..i].contains(&draws[i]));
+            let (xs, ys) = draws.split_at(POSEIDON_T);
+            if distinct && xs.iter().all(|x| ys.iter().all(|y| *x + y != N::zero())) {
+                break (xs.to_vec(), ys.to_vec());
+            }
+        };
+        let mut mds = [[N::zero(); POSEIDON_T]; POSEIDON_T];
+        for i in 0..POSEIDON_T {
+            for j in 0..POSEIDON_T {
+                mds[i][j] = (xs[i] + ys[j]).invert().unwrap();
+            }
+        }
+
+        Self {
+            round_constants,
+            mds,
+        }
+    }
+
+    fn is_full_round(r: usize) -> bool {
+        r < POSEIDON_R_F / 2 || r >= POSEIDON_R_F / 2 + POSEIDON_R_P
+    }
+
+    pub fn permute(&self, state: &mut [N; POSEIDON_T]) {
+        for i in 0..POSEIDON_T {
+            state[i] += self.round_constants[0][i];
+        }
+
+        for r in 0..POSEIDON_R_F + POSEIDON_R_P {
+            for i in 0..POSEIDON_T {
+                if Self::is_full_round(r) || i == 0 {
+                    state[i] = state[i].square().square() * state[i];
+                }
+            }
+
+            let mut next = [N::zero(); POSEIDON_T];
+            for i in 0..POSEIDON_T {
+                for j in 0..POSEIDON_T {
+                    next[i] += self.mds[i][j] * state[j];
+                }
+                if let Some(c) = self.round_constants.get(r + 1) {
+                    next[i] += c[i];
+                }
+            }
+            *state = next;
+        }
+    }
+
+    // The capacity element is initialized with the input length so that
+    // inputs of different lengths never share a padded absorption.
+    pub fn hash(&self, inputs: &[N]) -> N {
+        let mut state = [N::zero(); POSEIDON_T];
+        state[0] = N::from(inputs.len() as u64);
+
+        if inputs.is_empty() {
+            self.permute(&mut state);
+        }
+
+        for chunk in inputs.chunks(POSEIDON_RATE) {
+            for (i, v) in chunk.iter().enumerate() {
+                state[i + 1] += v;
+            }
+            self.permute(&mut state);
+        }
+
+        state[1]
+    }
+}
+
+// Duplex sponge for transcripts: absorbed values are buffered up to the rate
+// and every squeeze runs the permutation over what is buffered, so absorbs
+// and squeezes can interleave freely. The capacity element holds a domain
+// tag chosen by the caller.
+#[derive(Debug, Clone)]
+pub struct PoseidonSponge<N: FieldExt> {
+    state: [N; POSEIDON_T],
+    pending: Vec<N>,
+}
+
+impl<N: FieldExt> PoseidonSponge<N> {
+    pub fn new(domain: u64) -> Self {
+        let mut state = [N::zero(); POSEIDON_T];
+        state[0] = N::from(domain);
+
+        Self {
+            state,
+            pending: vec![],
+        }
+    }
+
+    fn flush(&mut self, spec: &PoseidonSpec<N>) {
+        for (i, v) in self.pending.drain(..).enumerate() {
+            self.state[i + 1] += v;
+        }
+        spec.permute(&mut self.state);
+    }
+
+    pub fn absorb(&mut self, spec: &PoseidonSpec<N>, values: &[N]) {
+        for v in values {
+            if self.pending.len() == POSEIDON_RATE {
+                self.flush(spec);
+            }
+            self.pending.push(*v);
+        }
+    }
+
+    pub fn squeeze(&mut self, spec: &PoseidonSpec<N>) -> N {
+        self.flush(spec);
+        self.state[1]
+    }
+}
+
+// In-circuit counterpart of `PoseidonSponge`.
+#[derive(Debug, Clone)]
+pub struct AssignedPoseidonSponge<N: FieldExt> {
+    state: Vec<AssignedValue<N>>,
+    pending: Vec<AssignedValue<N>>,
+}
+
+pub trait PoseidonChipOps<N: FieldExt>: BaseChipOps<N> {
+    fn poseidon_sbox(&mut self, x: &AssignedValue<N>) -> AssignedValue<N> {
+        let x2 = self.mul(x, x);
+        let x4 = self.mul(&x2, &x2);
+        self.mul(&x4, x)
+    }
+
+    fn poseidon_permute(
+        &mut self,
+        spec: &PoseidonSpec<N>,
+        state: Vec<AssignedValue<N>>,
+    ) -> Vec<AssignedValue<N>> {
+        assert_eq!(state.len(), POSEIDON_T);
+
+        let mut state = state
+            .iter()
+            .zip(spec.round_constants[0].iter())
+            .map(|(s, c)| self.add_constant(s, *c))
+            .collect::<Vec<_>>();
+
+        for r in 0..POSEIDON_R_F + POSEIDON_R_P {
+            for i in 0..POSEIDON_T {
+                if PoseidonSpec::<N>::is_full_round(r) || i == 0 {
+                    state[i] = self.poseidon_sbox(&state[i]);
+                }
+            }
+
+            // MDS mixing with the next round constants folded in
+            let next = (0..POSEIDON_T)
+                .map(|i| {
+                    let elems = state
+                        .iter()
+                        .zip(spec.mds[i].iter())
+                        .map(|(s, m)| (s, *m))
+                        .collect();
+                    let constant = spec.round_constants.get(r + 1).map(|c| c[i]);
+                    self.sum_with_constant(elems, constant)
+                })
+                .collect::<Vec<_>>();
+            state = next;
+        }
+
+        state
+    }
+
+    fn poseidon_hash(
+        &mut self,
+        spec: &PoseidonSpec<N>,
+        inputs: &[AssignedValue<N>],
+    ) -> AssignedValue<N> {
+        let capacity = self.assign_constant(N::from(inputs.len() as u64));
+        let zero = self.assign_constant(N::zero());
+        let mut state = vec![capacity, zero, zero, zero, zero];
+
+        if inputs.is_empty() {
+            state = self.poseidon_permute(spec, state);
+        }
+
+        for chunk in inputs.chunks(POSEIDON_RATE) {
+            for (i, v) in chunk.iter().enumerate() {
+                state[i + 1] = self.add(&state[i + 1], v);
+            }
+            state = self.poseidon_permute(spec, state);
+        }
+
+        state[1]
+    }
+
+    fn poseidon_sponge_new(&mut self, domain: u64) -> AssignedPoseidonSponge<N> {
+        let capacity = self.assign_constant(N::from(domain));
+        let zero = self.assign_constant(N::zero());
+
+        AssignedPoseidonSponge {
+            state: vec![capacity, zero, zero, zero, zero],
+            pending: vec![],
+        }
+    }
+
+    fn poseidon_sponge_flush(
+        &mut self,
+        spec: &PoseidonSpec<N>,
+        sponge: &mut AssignedPoseidonSponge<N>,
+    ) {
+        let mut state = sponge.state.clone();
+        for (i, v) in sponge.pending.drain(..).enumerate() {
+            state[i + 1] = self.add(&state[i + 1], &v);
+        }
+        sponge.state = self.poseidon_permute(spec, state);
+    }
+
+    fn poseidon_sponge_absorb(
+        &mut self,
+        spec: &PoseidonSpec<N>,
+        sponge: &mut AssignedPoseidonSponge<N>,
+        values: &[AssignedValue<N>],
+    ) {
+        for v in values {
+            if sponge.pending.len() == POSEIDON_RATE {
+                self.poseidon_sponge_flush(spec, sponge);
+            }
+            sponge.pending.push(*v);
+        }
+    }
+
+    fn poseidon_sponge_squeeze(
+        &mut self,
+        spec: &PoseidonSpec<N>,
+        sponge: &mut AssignedPoseidonSponge<N>,
+    ) -> AssignedValue<N> {
+        self.poseidon_sponge_flush(spec, sponge);
+        sponge.state[1]
+    }
+}
+
+impl<N: FieldExt> PoseidonChipOps<N> for Context<N> {}
+
+// Known answers of the reference implementation, poseidonperm_x5_254_5,
+// and of the hash and sponge on top of it.
+#[cfg(all(test, feature = "bn254-native"))]
+fn fr(hex: &str) -> halo2_proofs::pairing::bn256::Fr {
+    bn_to_field(&BigUint::parse_bytes(hex.as_bytes(), 16).unwrap())
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_poseidon_permutation_matches_reference() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    let expected = [
+        "299c867db6c1fdd79dcefa40e4510b9837e60ebb1ce0663dbaa525df65250465",
+        "1148aaef609aa338b27dafd89bb98862d8bb2b429aceac47d86206154ffe053d",
+        "24febb87fed7462e23f6665ff9a0111f4044c38ee1672c1ac6b0637d34f24907",
+        "0eb08f6d809668a981c186beaf6110060707059576406b248e5d9cf6e78b3d3e",
+        "07748bc6877c9b82c8b98666ee9d0626ec7f5be4205f79ee8528ef1c4a376fc7",
+    ]
+    .map(fr);
+
+    let spec = PoseidonSpec::<Fr>::new();
+    let mut state = [0u64, 1, 2, 3, 4].map(Fr::from);
+    spec.permute(&mut state);
+    assert_eq!(state, expected);
+
+    let mut ctx = Context::<Fr>::new();
+    let state = (0..POSEIDON_T as u64)
+        .map(|i| ctx.assign(Fr::from(i)))
+        .collect();
+    let state = ctx.poseidon_permute(&spec, state);
+    assert_eq!(state.iter().map(|s| s.val).collect::<Vec<_>>(), expected);
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_poseidon_hash_known_answer() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::NegativeTest;
+
+    let spec = PoseidonSpec::<Fr>::new();
+    let values = (0..11u64).map(|i| Fr::from(i * 7 + 3)).collect::<Vec<_>>();
+    let expected = fr("19a4ef40c3afceb261cef1741781709318d98c1d8474bf73eca7203f39ed1625");
+    assert_eq!(spec.hash(&values), expected);
+
+    let mut test = NegativeTest::<Fr>::new();
+    let hash = test.gadget("poseidon_hash", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let assigned = values.iter().map(|v| ctx.assign(*v)).collect::<Vec<_>>();
+        ctx.poseidon_hash(&spec, &assigned)
+    });
+    assert_eq!(hash.val, expected);
+
+    assert_eq!(test.run(&[]), vec![]);
+    assert!(!test.run(&[(hash.cell, hash.val + Fr::one())]).is_empty());
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_poseidon_sponge_known_answer() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    let spec = PoseidonSpec::<Fr>::new();
+    let values = (0..9u64).map(|i| Fr::from(i * 11 + 5)).collect::<Vec<_>>();
+    let expected = [
+        "0ab5a130c7984a4defc2d739bbbd2d4008e61f2d7733f11489aa70056c7042a8",
+        "2d5431933a879f63564ef7f51e8da2404864e00c4f33376c7fb2df6f46d607ec",
+        "0b388af0cb551625826d8e8ca4d884fb56f4d8b64e45baddc8e2d6a5b3616e08",
+    ]
+    .map(fr);
+
+    let mut sponge = PoseidonSponge::new(7);
+    sponge.absorb(&spec, &values[..6]);
+    let c0 = sponge.squeeze(&spec);
+    let c1 = sponge.squeeze(&spec);
+    sponge.absorb(&spec, &values[6..]);
+    let c2 = sponge.squeeze(&spec);
+    assert_eq!([c0, c1, c2], expected);
+
+    let mut ctx = Context::<Fr>::new();
+    let assigned = values.iter().map(|v| ctx.assign(*v)).collect::<Vec<_>>();
+    let mut assigned_sponge = ctx.poseidon_sponge_new(7);
+    ctx.poseidon_sponge_absorb(&spec, &mut assigned_sponge, &assigned[..6]);
+    let a0 = ctx.poseidon_sponge_squeeze(&spec, &mut assigned_sponge);
+    let a1 = ctx.poseidon_sponge_squeeze(&spec, &mut assigned_sponge);
+    ctx.poseidon_sponge_absorb(&spec, &mut assigned_sponge, &assigned[6..]);
+    let a2 = ctx.poseidon_sponge_squeeze(&spec, &mut assigned_sponge);
+    assert_eq!([a0.val, a1.val, a2.val], expected);
+}
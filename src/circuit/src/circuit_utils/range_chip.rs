@@ -3,7 +3,9 @@
 */
 
 use crate::assign::AssignedValue;
-use crate::context::IntegerContext;
+use crate::circuit_utils::base_chip::BaseChipOps;
+use crate::compat::assign_table_cell;
+use crate::context::{Context, IntegerContext};
 use crate::range_info::*;
 use crate::utils::bn_to_field;
 use halo2_proofs::{
@@ -13,12 +15,25 @@ use halo2_proofs::{
     poly::Rotation,
 };
 use num_bigint::BigUint;
+use std::io::{self, Read, Write};
 use std::{marker::PhantomData, sync::Arc, vec};
 
 pub const MAX_CHUNKS: u64 = 6;
+#[cfg(not(feature = "small-range-table"))]
 pub const MAX_BITS: u64 = 18;
+// A 2^11 row table, small enough to run gadgets under MockProver at k = 12.
+// 8-bit chunks would leave too little headroom for the mul carries.
+#[cfg(feature = "small-range-table")]
+pub const MAX_BITS: u64 = 10;
 pub const COMMON_RANGE_BITS: u64 = MAX_BITS as u64;
 
+// Default headroom of integers over the field width before a reduce, the
+// carry checks of the small table only leave room for 3 bits.
+#[cfg(not(feature = "small-range-table"))]
+pub const OVERFLOW_BITS: u64 = 6;
+#[cfg(feature = "small-range-table")]
+pub const OVERFLOW_BITS: u64 = 3;
+
 const CLASS_SHIFT_BITS: usize = 128;
 
 #[derive(Clone, Debug)]
@@ -100,17 +115,25 @@ impl<N: FieldExt> RangeChip<N> {
     }
 
     pub fn init_table(&self, layouter: &mut impl Layouter<N>) -> Result<(), Error> {
-        let class_shift = bn_to_field::<N>(&(BigUint::from(1u64) << CLASS_SHIFT_BITS));
+        self.load_table(layouter, &RangeTable::generate())
+    }
 
+    // Assigns a table generated earlier, e.g. read back from disk.
+    pub fn load_table(
+        &self,
+        layouter: &mut impl Layouter<N>,
+        table: &RangeTable<N>,
+    ) -> Result<(), Error> {
         layouter.assign_table(
             || "common range table",
-            |mut table| {
-                for i in 0..1 << COMMON_RANGE_BITS {
-                    table.assign_cell(
-                        || "range table",
+            |mut table_region| {
+                for (i, v) in table.max_range.iter().enumerate() {
+                    assign_table_cell(
+                        &mut table_region,
+                        "range table",
                         self.config.max_range_table_column,
                         i,
-                        || Ok(N::from(i as u64)),
+                        *v,
                     )?;
                 }
 
@@ -120,20 +143,15 @@ impl<N: FieldExt> RangeChip<N> {
 
         layouter.assign_table(
             || "common range table",
-            |mut table| {
-                let mut offset = 0;
-
-                for i in 0..COMMON_RANGE_BITS + 1 {
-                    let prefix = N::from(i) * &class_shift;
-                    for j in 0..1 << i {
-                        table.assign_cell(
-                            || "range table",
-                            self.config.tag_range_table_column,
-                            offset,
-                            || Ok(prefix + N::from(j)),
-                        )?;
-                        offset += 1;
-                    }
+            |mut table_region| {
+                for (i, v) in table.tag_range.iter().enumerate() {
+                    assign_table_cell(
+                        &mut table_region,
+                        "range table",
+                        self.config.tag_range_table_column,
+                        i,
+                        *v,
+                    )?;
                 }
 
                 Ok(())
@@ -144,6 +162,95 @@ impl<N: FieldExt> RangeChip<N> {
     }
 }
 
+// Values of the two range lookup tables. They only depend on
+// COMMON_RANGE_BITS, so large-k provers can generate them once and read
+// them back instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeTable<N: FieldExt> {
+    pub max_range: Vec<N>,
+    pub tag_range: Vec<N>,
+}
+
+impl<N: FieldExt> RangeTable<N> {
+    pub fn generate() -> Self {
+        let class_shift = bn_to_field::<N>(&(BigUint::from(1u64) << CLASS_SHIFT_BITS));
+
+        let max_range = (0..1u64 << COMMON_RANGE_BITS).map(|i| N::from(i)).collect();
+
+        let mut tag_range = vec![];
+        for i in 0..COMMON_RANGE_BITS + 1 {
+            let prefix = N::from(i) * &class_shift;
+            for j in 0..1u64 << i {
+                tag_range.push(prefix + N::from(j));
+            }
+        }
+
+        Self {
+            max_range,
+            tag_range,
+        }
+    }
+
+    // COMMON_RANGE_BITS, then both tables prefixed with their length.
+    pub fn write<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+        writer.write_all(&COMMON_RANGE_BITS.to_le_bytes())?;
+        for column in [&self.max_range, &self.tag_range] {
+            writer.write_all(&(column.len() as u64).to_le_bytes())?;
+            for v in column.iter() {
+                v.write(writer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Fails on tables written for another COMMON_RANGE_BITS.
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let read_u64 = |reader: &mut R| -> io::Result<u64> {
+            let mut bytes = [0u8; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+
+        let range_bits = read_u64(reader)?;
+        if range_bits != COMMON_RANGE_BITS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "range table of {} bits, expected {}",
+                    range_bits, COMMON_RANGE_BITS
+                ),
+            ));
+        }
+
+        let mut columns = vec![];
+        for expected in [
+            1usize << COMMON_RANGE_BITS,
+            (1 << (COMMON_RANGE_BITS + 1)) - 1,
+        ] {
+            let len = read_u64(reader)? as usize;
+            if len != expected {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("range table column of {} rows, expected {}", len, expected),
+                ));
+            }
+            columns.push(
+                (0..len)
+                    .map(|_| N::read(reader))
+                    .collect::<io::Result<Vec<_>>>()?,
+            );
+        }
+
+        let tag_range = columns.pop().unwrap();
+        let max_range = columns.pop().unwrap();
+        Ok(Self {
+            max_range,
+            tag_range,
+        })
+    }
+}
+
 // A range info that implements limb assignment for W on N
 pub trait RangeChipOps<W: BaseExt, N: FieldExt> {
     fn info(&self) -> Arc<RangeInfo<W, N>>;
@@ -185,11 +292,15 @@ impl<W: BaseExt, N: FieldExt> RangeChipOps<W, N> for IntegerContext<W, N> {
     }
 
     fn assign_nonleading_limb(&mut self, bn: &BigUint) -> AssignedValue<N> {
-        let v = decompose_bn(bn, MAX_CHUNKS, &self.info().common_range_mask);
+        let info = self.info();
+        let v = decompose_bn(bn, info.limb_chunks, &info.common_range_mask);
         let records_mtx = self.ctx.borrow().records.clone();
         let mut records = records_mtx.lock().unwrap();
-        let res =
-            records.assign_range_value(self.ctx.borrow_mut().range_offset, v, COMMON_RANGE_BITS);
+        let res = records.assign_range_value(
+            self.ctx.borrow_mut().range_offset,
+            v,
+            info.limb_leading_bits,
+        );
         self.ctx.borrow_mut().range_offset += MAX_CHUNKS as usize + 1;
         res
     }
@@ -242,17 +353,111 @@ impl<W: BaseExt, N: FieldExt> RangeChipOps<W, N> for IntegerContext<W, N> {
     }
 
     fn assign_small_number(&mut self, n: usize, bits: usize) -> AssignedValue<N> {
-        assert!(bits <= 16);
         assert!(n < (1 << bits));
+        self.ctx
+            .borrow_mut()
+            .assign_range_bits(bn_to_field(&BigUint::from(n)), bits as u64)
+    }
+}
 
-        let records_mtx = self.ctx.borrow().records.clone();
+// Values of any width up to COMMON_RANGE_BITS, e.g. 16-bit hash words or
+// 17-bit length fields, are checked in a single row: the tagged table holds
+// a class for every width, not only for the common and the leading chunks.
+pub trait RangeCheckOps<N: FieldExt>: BaseChipOps<N> {
+    // Assigns `v` on the range chip, checked below 2^bits.
+    fn assign_range_bits(&mut self, v: N, bits: u64) -> AssignedValue<N>;
+
+    fn range_check_bits(&mut self, v: &AssignedValue<N>, bits: u64) {
+        let checked = self.assign_range_bits(v.val, bits);
+        self.assert_equal(v, &checked);
+    }
+}
+
+impl<N: FieldExt> RangeCheckOps<N> for Context<N> {
+    fn assign_range_bits(&mut self, v: N, bits: u64) -> AssignedValue<N> {
+        assert!(bits <= COMMON_RANGE_BITS);
+
+        let records_mtx = self.records.clone();
         let mut records = records_mtx.lock().unwrap();
-        let res = records.assign_single_range_value(
-            self.ctx.borrow_mut().range_offset,
-            bn_to_field(&BigUint::from(n)),
-            bits as u64,
-        );
-        self.ctx.borrow_mut().range_offset += 1;
+        let res = records.assign_single_range_value(self.range_offset, v, bits);
+        self.range_offset += 1;
         res
     }
 }
+
+#[cfg(all(
+    feature = "small-range-table",
+    feature = "bn254-native",
+    feature = "bls12381-target"
+))]
+#[test]
+fn test_small_range_table_runs_at_k12() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381::Fq;
+    use halo2_proofs::pairing::bn256::Fr;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::circuit_utils::integer_chip::IntegerChipOps;
+    use crate::cost::CircuitCost;
+    use crate::negative_test::NegativeTest;
+    use crate::utils::field_to_bn;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let a = Fq::random(&mut rng);
+    let b = Fq::random(&mut rng);
+
+    let mut test = NegativeTest::<Fr>::new();
+    let c = test.gadget("int_mul", |ctx| {
+        let mut ctx = IntegerContext::<Fq, Fr>::new(ctx.clone());
+        let a = ctx.assign_w(&field_to_bn(&a));
+        let b = ctx.assign_w(&field_to_bn(&b));
+        let c = ctx.int_mul(&a, &b);
+        ctx.get_w(&c)
+    });
+    assert_eq!(c, a * b);
+
+    let records = test.ctx.borrow().records.lock().unwrap().clone();
+    assert!(CircuitCost::from_records(&records).min_k <= 12);
+    assert_eq!(test.run(&[]), vec![]);
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_range_table_round_trip() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    let table = RangeTable::<Fr>::generate();
+    assert_eq!(table.max_range.len(), 1 << COMMON_RANGE_BITS);
+    assert_eq!(table.tag_range.len(), (1 << (COMMON_RANGE_BITS + 1)) - 1);
+
+    let mut bytes = vec![];
+    table.write(&mut bytes).unwrap();
+    assert_eq!(RangeTable::<Fr>::read(&mut &bytes[..]).unwrap(), table);
+
+    assert!(RangeTable::<Fr>::read(&mut &bytes[..bytes.len() - 1]).is_err());
+    bytes[0] ^= 1;
+    assert!(RangeTable::<Fr>::read(&mut &bytes[..]).is_err());
+}
+
+#[cfg(all(feature = "bn254-native", not(feature = "small-range-table")))]
+#[test]
+fn test_range_check_bits() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::{FailureKind, NegativeTest};
+
+    let mut test = NegativeTest::<Fr>::new();
+    let (word, length) = test.gadget("range_check_bits", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let word = ctx.assign_range_bits(Fr::from(0xffff), 16);
+        let length = ctx.assign(Fr::from((1 << 17) - 1));
+        ctx.range_check_bits(&length, 17);
+        (word, length)
+    });
+    assert_eq!(test.run(&[]), vec![]);
+
+    let failures = test.run(&[(word.cell, Fr::from(1 << 16))]);
+    assert!(failures.contains(&("range_check_bits", FailureKind::Lookup(0))));
+    assert!(!test.run(&[(length.cell, Fr::from(1 << 17))]).is_empty());
+}
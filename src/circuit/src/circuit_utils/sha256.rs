@@ -0,0 +1,216 @@
+use halo2_proofs::arithmetic::FieldExt;
+
+use super::base_chip::{rotate_right, BaseChipOps};
+use crate::assign::AssignedCondition;
+use crate::context::Context;
+use crate::pair;
+
+pub const SHA256_BLOCK_BYTES: usize = 64;
+const SHA256_WORD_BITS: usize = 32;
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+// Words are little-endian bit vectors, bit i has weight 2^i. Messages and
+// digests use the keccak chip layout: bits are little-endian inside each
+// byte and bytes keep their message order, so words are read big-endian
+// from it as the standard requires.
+pub trait Sha256ChipOps<N: FieldExt>: BaseChipOps<N> {
+    // Xor of the word rotated right by each of `rotations`, and shifted
+    // right by `shift` if any. Words of SHA-512 go through it too.
+    fn sha2_sigma(
+        &mut self,
+        w: &[AssignedCondition<N>],
+        rotations: &[usize],
+        shift: Option<usize>,
+    ) -> Vec<AssignedCondition<N>> {
+        let rotated = rotations
+            .iter()
+            .map(|r| rotate_right(w, *r))
+            .collect::<Vec<_>>();
+        (0..w.len())
+            .map(|i| {
+                let mut terms = rotated.iter().map(|r| r[i]).collect::<Vec<_>>();
+                if let Some(n) = shift.filter(|n| i + n < w.len()) {
+                    terms.push(w[i + n]);
+                }
+
+                let mut acc = terms[0];
+                for t in &terms[1..] {
+                    acc = self.xor(&acc, t);
+                }
+                acc
+            })
+            .collect()
+    }
+
+    // (a & b) ^ (a & c) ^ (b & c) = a * b + c * (a ^ b), the two terms are
+    // never both set.
+    fn sha256_maj(
+        &mut self,
+        a: &AssignedCondition<N>,
+        b: &AssignedCondition<N>,
+        c: &AssignedCondition<N>,
+    ) -> AssignedCondition<N> {
+        let zero = N::zero();
+        let one = N::one();
+        let t = self.xor(a, b);
+        let v = a.0.val * b.0.val + c.0.val * t.0.val;
+
+        let cells = self.one_line_with_last(
+            vec![
+                pair!(&a.0, zero),
+                pair!(&b.0, zero),
+                pair!(&c.0, zero),
+                pair!(&t.0, zero),
+            ],
+            pair!(v, -one),
+            None,
+            (vec![one, one], None),
+        );
+
+        AssignedCondition(cells.1)
+    }
+
+    fn sha256_compress(
+        &mut self,
+        state: &[Vec<AssignedCondition<N>>],
+        block: &[AssignedCondition<N>],
+    ) -> Vec<Vec<AssignedCondition<N>>> {
+        assert_eq!(state.len(), 8);
+        assert_eq!(block.len(), SHA256_BLOCK_BYTES * 8);
+
+        let mut w = block
+            .chunks(SHA256_WORD_BITS)
+            .map(|bytes| {
+                (0..SHA256_WORD_BITS)
+                    .map(|i| bytes[(3 - i / 8) * 8 + i % 8])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for t in 16..64 {
+            let s0 = self.sha2_sigma(&w[t - 15], &[7, 18], Some(3));
+            let s1 = self.sha2_sigma(&w[t - 2], &[17, 19], Some(10));
+            let wt = self.word_add(&[&w[t - 16], &s0, &w[t - 7], &s1], 0);
+            w.push(wt);
+        }
+
+        // [a, b, c, d, e, f, g, h]
+        let mut v = state.to_vec();
+        for t in 0..64 {
+            let s1 = self.sha2_sigma(&v[4], &[6, 11, 25], None);
+            let ch = (0..SHA256_WORD_BITS)
+                .map(|i| self.bisec_cond(&v[4][i], &v[5][i], &v[6][i]))
+                .collect::<Vec<_>>();
+            let t1 = self.word_add(&[&v[7], &s1, &ch, &w[t]], ROUND_CONSTANTS[t] as u64);
+
+            let s0 = self.sha2_sigma(&v[0], &[2, 13, 22], None);
+            let maj = (0..SHA256_WORD_BITS)
+                .map(|i| self.sha256_maj(&v[0][i], &v[1][i], &v[2][i]))
+                .collect::<Vec<_>>();
+
+            let e = self.word_add(&[&v[3], &t1], 0);
+            let a = self.word_add(&[&t1, &s0, &maj], 0);
+
+            v.pop();
+            v.insert(0, a);
+            v[4] = e;
+        }
+
+        state
+            .iter()
+            .zip(v.iter())
+            .map(|(s, v)| self.word_add(&[s, v], 0))
+            .collect()
+    }
+
+    // SHA-256 of a byte aligned bit string, returns the 256 digest bits in
+    // the same layout.
+    fn sha256(&mut self, message: &[AssignedCondition<N>]) -> Vec<AssignedCondition<N>> {
+        assert_eq!(message.len() % 8, 0);
+
+        let zero = AssignedCondition(self.assign_constant(N::zero()));
+        let one = AssignedCondition(self.assign_constant(N::one()));
+        let constant_bits = |v: u64, bits: usize| {
+            (0..bits)
+                .map(|i| if (v >> i) & 1 == 1 { one } else { zero })
+                .collect::<Vec<_>>()
+        };
+
+        let block_bits = SHA256_BLOCK_BYTES * 8;
+        let mut padded = message.to_vec();
+        padded.extend(constant_bits(0x80, 8));
+        while padded.len() % block_bits != block_bits - 64 {
+            padded.extend(constant_bits(0, 8));
+        }
+        for byte in (message.len() as u64).to_be_bytes() {
+            padded.extend(constant_bits(byte as u64, 8));
+        }
+
+        let mut state = INITIAL_STATE
+            .iter()
+            .map(|w| constant_bits(*w as u64, SHA256_WORD_BITS))
+            .collect::<Vec<_>>();
+        for block in padded.chunks(block_bits) {
+            state = self.sha256_compress(&state, block);
+        }
+
+        state
+            .iter()
+            .flat_map(|w| (0..4).flat_map(move |k| (0..8).map(move |i| w[(3 - k) * 8 + i])))
+            .collect()
+    }
+}
+
+impl<N: FieldExt> Sha256ChipOps<N> for Context<N> {}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_sha256_chip_matches_test_vectors() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::{assign_bytes, to_bytes, NegativeTest};
+
+    let vectors = [
+        (
+            "abc",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        (
+            "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+        ),
+    ];
+
+    for (message, expected) in vectors {
+        let expected = (0..expected.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&expected[i..i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut test = NegativeTest::<Fr>::new();
+        let digest = test.gadget("sha256", |ctx| {
+            let mut ctx = ctx.borrow_mut();
+            let bits = assign_bytes(&mut ctx, message.as_bytes());
+            ctx.sha256(&bits)
+        });
+        assert_eq!(to_bytes(&digest), expected);
+
+        assert_eq!(test.run(&[]), vec![]);
+        let flipped = Fr::one() - digest[0].0.val;
+        assert!(!test.run(&[(digest[0].0.cell, flipped)]).is_empty());
+    }
+}
@@ -0,0 +1,268 @@
+use halo2_proofs::arithmetic::FieldExt;
+
+use super::sha256::Sha256ChipOps;
+use crate::assign::AssignedCondition;
+use crate::context::Context;
+
+pub const SHA512_BLOCK_BYTES: usize = 128;
+const SHA512_WORD_BITS: usize = 64;
+
+const ROUND_CONSTANTS: [u64; 80] = [
+    0x428a2f98d728ae22,
+    0x7137449123ef65cd,
+    0xb5c0fbcfec4d3b2f,
+    0xe9b5dba58189dbbc,
+    0x3956c25bf348b538,
+    0x59f111f1b605d019,
+    0x923f82a4af194f9b,
+    0xab1c5ed5da6d8118,
+    0xd807aa98a3030242,
+    0x12835b0145706fbe,
+    0x243185be4ee4b28c,
+    0x550c7dc3d5ffb4e2,
+    0x72be5d74f27b896f,
+    0x80deb1fe3b1696b1,
+    0x9bdc06a725c71235,
+    0xc19bf174cf692694,
+    0xe49b69c19ef14ad2,
+    0xefbe4786384f25e3,
+    0x0fc19dc68b8cd5b5,
+    0x240ca1cc77ac9c65,
+    0x2de92c6f592b0275,
+    0x4a7484aa6ea6e483,
+    0x5cb0a9dcbd41fbd4,
+    0x76f988da831153b5,
+    0x983e5152ee66dfab,
+    0xa831c66d2db43210,
+    0xb00327c898fb213f,
+    0xbf597fc7beef0ee4,
+    0xc6e00bf33da88fc2,
+    0xd5a79147930aa725,
+    0x06ca6351e003826f,
+    0x142929670a0e6e70,
+    0x27b70a8546d22ffc,
+    0x2e1b21385c26c926,
+    0x4d2c6dfc5ac42aed,
+    0x53380d139d95b3df,
+    0x650a73548baf63de,
+    0x766a0abb3c77b2a8,
+    0x81c2c92e47edaee6,
+    0x92722c851482353b,
+    0xa2bfe8a14cf10364,
+    0xa81a664bbc423001,
+    0xc24b8b70d0f89791,
+    0xc76c51a30654be30,
+    0xd192e819d6ef5218,
+    0xd69906245565a910,
+    0xf40e35855771202a,
+    0x106aa07032bbd1b8,
+    0x19a4c116b8d2d0c8,
+    0x1e376c085141ab53,
+    0x2748774cdf8eeb99,
+    0x34b0bcb5e19b48a8,
+    0x391c0cb3c5c95a63,
+    0x4ed8aa4ae3418acb,
+    0x5b9cca4f7763e373,
+    0x682e6ff3d6b2b8a3,
+    0x748f82ee5defb2fc,
+    0x78a5636f43172f60,
+    0x84c87814a1f0ab72,
+    0x8cc702081a6439ec,
+    0x90befffa23631e28,
+    0xa4506cebde82bde9,
+    0xbef9a3f7b2c67915,
+    0xc67178f2e372532b,
+    0xca273eceea26619c,
+    0xd186b8c721c0c207,
+    0xeada7dd6cde0eb1e,
+    0xf57d4f7fee6ed178,
+    0x06f067aa72176fba,
+    0x0a637dc5a2c898a6,
+    0x113f9804bef90dae,
+    0x1b710b35131c471b,
+    0x28db77f523047d84,
+    0x32caab7b40c72493,
+    0x3c9ebe0a15c9bebc,
+    0x431d67c49c100d4c,
+    0x4cc5d4becb3e42b6,
+    0x597f299cfc657e2a,
+    0x5fcb6fab3ad6faec,
+    0x6c44198c4a475817,
+];
+
+const SHA512_INITIAL_STATE: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const SHA384_INITIAL_STATE: [u64; 8] = [
+    0xcbbb9d5dc1059ed8,
+    0x629a292a367cd507,
+    0x9159015a3070dd17,
+    0x152fecd8f70e5939,
+    0x67332667ffc00b31,
+    0x8eb44a8768581511,
+    0xdb0c2e0d64f98fa7,
+    0x47b5481dbefa4fa4,
+];
+
+// Same layout as the SHA-256 chip, with 64-bit words. The additions, sigmas
+// and Maj take words of either width and are shared with it, only the
+// constants, rotations and padding differ.
+pub trait Sha512ChipOps<N: FieldExt>: Sha256ChipOps<N> {
+    fn sha512_compress(
+        &mut self,
+        state: &[Vec<AssignedCondition<N>>],
+        block: &[AssignedCondition<N>],
+    ) -> Vec<Vec<AssignedCondition<N>>> {
+        assert_eq!(state.len(), 8);
+        assert_eq!(block.len(), SHA512_BLOCK_BYTES * 8);
+
+        let mut w = block
+            .chunks(SHA512_WORD_BITS)
+            .map(|bytes| {
+                (0..SHA512_WORD_BITS)
+                    .map(|i| bytes[(7 - i / 8) * 8 + i % 8])
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for t in 16..80 {
+            let s0 = self.sha2_sigma(&w[t - 15], &[1, 8], Some(7));
+            let s1 = self.sha2_sigma(&w[t - 2], &[19, 61], Some(6));
+            let wt = self.word_add(&[&w[t - 16], &s0, &w[t - 7], &s1], 0);
+            w.push(wt);
+        }
+
+        // [a, b, c, d, e, f, g, h]
+        let mut v = state.to_vec();
+        for t in 0..80 {
+            let s1 = self.sha2_sigma(&v[4], &[14, 18, 41], None);
+            let ch = (0..SHA512_WORD_BITS)
+                .map(|i| self.bisec_cond(&v[4][i], &v[5][i], &v[6][i]))
+                .collect::<Vec<_>>();
+            let t1 = self.word_add(&[&v[7], &s1, &ch, &w[t]], ROUND_CONSTANTS[t]);
+
+            let s0 = self.sha2_sigma(&v[0], &[28, 34, 39], None);
+            let maj = (0..SHA512_WORD_BITS)
+                .map(|i| self.sha256_maj(&v[0][i], &v[1][i], &v[2][i]))
+                .collect::<Vec<_>>();
+
+            let e = self.word_add(&[&v[3], &t1], 0);
+            let a = self.word_add(&[&t1, &s0, &maj], 0);
+
+            v.pop();
+            v.insert(0, a);
+            v[4] = e;
+        }
+
+        state
+            .iter()
+            .zip(v.iter())
+            .map(|(s, v)| self.word_add(&[s, v], 0))
+            .collect()
+    }
+
+    // The digest is the first `digest_bytes` bytes of the final state.
+    fn sha512_with_initial_state(
+        &mut self,
+        message: &[AssignedCondition<N>],
+        initial_state: &[u64; 8],
+        digest_bytes: usize,
+    ) -> Vec<AssignedCondition<N>> {
+        assert_eq!(message.len() % 8, 0);
+
+        let zero = AssignedCondition(self.assign_constant(N::zero()));
+        let one = AssignedCondition(self.assign_constant(N::one()));
+        let constant_bits = |v: u64, bits: usize| {
+            (0..bits)
+                .map(|i| if (v >> i) & 1 == 1 { one } else { zero })
+                .collect::<Vec<_>>()
+        };
+
+        let block_bits = SHA512_BLOCK_BYTES * 8;
+        let mut padded = message.to_vec();
+        padded.extend(constant_bits(0x80, 8));
+        while padded.len() % block_bits != block_bits - 128 {
+            padded.extend(constant_bits(0, 8));
+        }
+        for byte in (message.len() as u128).to_be_bytes() {
+            padded.extend(constant_bits(byte as u64, 8));
+        }
+
+        let mut state = initial_state
+            .iter()
+            .map(|w| constant_bits(*w, SHA512_WORD_BITS))
+            .collect::<Vec<_>>();
+        for block in padded.chunks(block_bits) {
+            state = self.sha512_compress(&state, block);
+        }
+
+        state
+            .iter()
+            .flat_map(|w| (0..8).flat_map(move |k| (0..8).map(move |i| w[(7 - k) * 8 + i])))
+            .take(digest_bytes * 8)
+            .collect()
+    }
+
+    fn sha512(&mut self, message: &[AssignedCondition<N>]) -> Vec<AssignedCondition<N>> {
+        self.sha512_with_initial_state(message, &SHA512_INITIAL_STATE, 64)
+    }
+
+    fn sha384(&mut self, message: &[AssignedCondition<N>]) -> Vec<AssignedCondition<N>> {
+        self.sha512_with_initial_state(message, &SHA384_INITIAL_STATE, 48)
+    }
+}
+
+impl<N: FieldExt> Sha512ChipOps<N> for Context<N> {}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_sha512_chip_matches_test_vectors() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::{assign_bytes, to_bytes, NegativeTest};
+
+    let message = "abc";
+    let vectors = [
+        (
+            512,
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+        ),
+        (
+            384,
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded163\
+             1a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+        ),
+    ];
+
+    for (variant, expected) in vectors {
+        let expected = (0..expected.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&expected[i..i + 2], 16).unwrap())
+            .collect::<Vec<_>>();
+
+        let mut test = NegativeTest::<Fr>::new();
+        let digest = test.gadget("sha512", |ctx| {
+            let mut ctx = ctx.borrow_mut();
+            let bits = assign_bytes(&mut ctx, message.as_bytes());
+            if variant == 512 {
+                ctx.sha512(&bits)
+            } else {
+                ctx.sha384(&bits)
+            }
+        });
+        assert_eq!(to_bytes(&digest), expected);
+
+        assert_eq!(test.run(&[]), vec![]);
+        let flipped = Fr::one() - digest[0].0.val;
+        assert!(!test.run(&[(digest[0].0.cell, flipped)]).is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+use halo2_proofs::arithmetic::FieldExt;
+
+use super::base_chip::BaseChipOps;
+use super::sha256::Sha256ChipOps;
+use crate::assign::{AssignedCondition, AssignedValue};
+use crate::context::Context;
+
+pub const SSZ_CHUNK_BITS: usize = 256;
+pub const BLS_PUBKEY_BYTES: usize = 48;
+pub const SYNC_COMMITTEE_SIZE: usize = 512;
+
+// Roots, byte vectors and chunks use the hash chip bit layout.
+#[derive(Clone, Debug)]
+pub struct AssignedBeaconBlockHeader<N: FieldExt> {
+    pub slot: AssignedValue<N>,
+    pub proposer_index: AssignedValue<N>,
+    pub parent_root: Vec<AssignedCondition<N>>,
+    pub state_root: Vec<AssignedCondition<N>>,
+    pub body_root: Vec<AssignedCondition<N>>,
+}
+
+// Pubkeys are the 48-byte compressed encodings.
+#[derive(Clone, Debug)]
+pub struct AssignedSyncCommittee<N: FieldExt> {
+    pub pubkeys: Vec<Vec<AssignedCondition<N>>>,
+    pub aggregate_pubkey: Vec<AssignedCondition<N>>,
+}
+
+pub trait SszChipOps<N: FieldExt>: Sha256ChipOps<N> {
+    // Little-endian uint64 padded to a chunk, the value is range checked by
+    // the decomposition.
+    fn ssz_uint64_chunk(&mut self, v: &AssignedValue<N>) -> Vec<AssignedCondition<N>> {
+        let zero = AssignedCondition(self.assign_constant(N::zero()));
+        let mut chunk = self.decompose_bits_le(v, 64);
+        chunk.resize(SSZ_CHUNK_BITS, zero);
+        chunk
+    }
+
+    // Byte string packed into chunks, the last one zero padded.
+    fn ssz_pack_bytes(&mut self, bytes: &[AssignedCondition<N>]) -> Vec<Vec<AssignedCondition<N>>> {
+        assert_eq!(bytes.len() % 8, 0);
+
+        let zero = AssignedCondition(self.assign_constant(N::zero()));
+        bytes
+            .chunks(SSZ_CHUNK_BITS)
+            .map(|c| {
+                let mut chunk = c.to_vec();
+                chunk.resize(SSZ_CHUNK_BITS, zero);
+                chunk
+            })
+            .collect()
+    }
+
+    // Merkle root of `chunks` padded with zero chunks up to the next power of
+    // two of `limit`. Zero subtrees are hashed once per level.
+    fn ssz_merkleize(
+        &mut self,
+        chunks: &[Vec<AssignedCondition<N>>],
+        limit: usize,
+    ) -> Vec<AssignedCondition<N>> {
+        assert!(chunks.len() <= limit);
+
+        let zero = AssignedCondition(self.assign_constant(N::zero()));
+        let mut zero_subtree = vec![zero; SSZ_CHUNK_BITS];
+        let mut nodes = chunks.to_vec();
+        if nodes.is_empty() {
+            nodes.push(zero_subtree.clone());
+        }
+
+        let mut width = limit.max(1).next_power_of_two();
+        while width > 1 {
+            if nodes.len() % 2 == 1 {
+                nodes.push(zero_subtree.clone());
+            }
+            nodes = nodes
+                .chunks(2)
+                .map(|pair| self.sha256(&pair.concat()))
+                .collect();
+            if width > 2 {
+                zero_subtree = self.sha256(&[&zero_subtree[..], &zero_subtree[..]].concat());
+            }
+            width /= 2;
+        }
+
+        nodes.pop().unwrap()
+    }
+
+    fn ssz_bls_pubkey_root(
+        &mut self,
+        pubkey: &[AssignedCondition<N>],
+    ) -> Vec<AssignedCondition<N>> {
+        assert_eq!(pubkey.len(), BLS_PUBKEY_BYTES * 8);
+        let chunks = self.ssz_pack_bytes(pubkey);
+        self.ssz_merkleize(&chunks, 2)
+    }
+
+    fn ssz_beacon_block_header_root(
+        &mut self,
+        header: &AssignedBeaconBlockHeader<N>,
+    ) -> Vec<AssignedCondition<N>> {
+        let chunks = vec![
+            self.ssz_uint64_chunk(&header.slot),
+            self.ssz_uint64_chunk(&header.proposer_index),
+            header.parent_root.clone(),
+            header.state_root.clone(),
+            header.body_root.clone(),
+        ];
+        for chunk in chunks.iter() {
+            assert_eq!(chunk.len(), SSZ_CHUNK_BITS);
+        }
+        self.ssz_merkleize(&chunks, chunks.len())
+    }
+
+    // The committee size is taken from `pubkeys`, SYNC_COMMITTEE_SIZE on
+    // mainnet.
+    fn ssz_sync_committee_root(
+        &mut self,
+        committee: &AssignedSyncCommittee<N>,
+    ) -> Vec<AssignedCondition<N>> {
+        let roots = committee
+            .pubkeys
+            .iter()
+            .map(|pk| self.ssz_bls_pubkey_root(pk))
+            .collect::<Vec<_>>();
+        let pubkeys_root = self.ssz_merkleize(&roots, roots.len());
+        let aggregate_root = self.ssz_bls_pubkey_root(&committee.aggregate_pubkey);
+        self.ssz_merkleize(&[pubkeys_root, aggregate_root], 2)
+    }
+
+    // hash_tree_root(SigningData { object_root, domain }), the message the
+    // committee signs.
+    fn ssz_signing_root(
+        &mut self,
+        object_root: &[AssignedCondition<N>],
+        domain: &[AssignedCondition<N>],
+    ) -> Vec<AssignedCondition<N>> {
+        assert_eq!(object_root.len(), SSZ_CHUNK_BITS);
+        assert_eq!(domain.len(), SSZ_CHUNK_BITS);
+        self.ssz_merkleize(&[object_root.to_vec(), domain.to_vec()], 2)
+    }
+}
+
+impl<N: FieldExt> SszChipOps<N> for Context<N> {}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_ssz_roots_match_reference() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::{assign_bytes, to_bytes, NegativeTest};
+
+    let to_hex = |bits: &[AssignedCondition<Fr>]| {
+        to_bytes(bits)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>()
+    };
+    let header = |ctx: &mut Context<Fr>, slot: Fr| AssignedBeaconBlockHeader {
+        slot: ctx.assign(slot),
+        proposer_index: ctx.assign(Fr::from(2u64)),
+        parent_root: assign_bytes(ctx, &[1u8; 32]),
+        state_root: assign_bytes(ctx, &[2u8; 32]),
+        body_root: assign_bytes(ctx, &[3u8; 32]),
+    };
+
+    let mut test = NegativeTest::<Fr>::new();
+    let (parent_root, header_root) = test.gadget("header", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let header = header(&mut ctx, Fr::from(1u64));
+        (
+            header.parent_root.clone(),
+            ctx.ssz_beacon_block_header_root(&header),
+        )
+    });
+    assert_eq!(
+        to_hex(&header_root),
+        "bb3917003756216bccee4f87d53d4e52b948b8190b14a552cb1e78fcede06dd5"
+    );
+
+    let signing_root = test.gadget("signing root", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let domain = assign_bytes(&mut ctx, &[4u8; 32]);
+        ctx.ssz_signing_root(&header_root, &domain)
+    });
+    assert_eq!(
+        to_hex(&signing_root),
+        "bfa405b71046df864a884e70c4a173561c5664bf70f8acc9e32fea47929b49de"
+    );
+
+    let committee_root = test.gadget("committee", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let committee = AssignedSyncCommittee {
+            pubkeys: (1..=4u8)
+                .map(|i| assign_bytes(&mut ctx, &[i; BLS_PUBKEY_BYTES]))
+                .collect(),
+            aggregate_pubkey: assign_bytes(&mut ctx, &[9u8; BLS_PUBKEY_BYTES]),
+        };
+        ctx.ssz_sync_committee_root(&committee)
+    });
+    assert_eq!(
+        to_hex(&committee_root),
+        "f9474d9d145576a4fe7ece2dec0a3ac2d7d73b0c99a1ce3b0a66f474b47a8644"
+    );
+
+    assert_eq!(test.run(&[]), vec![]);
+    // a root bit changed after hashing no longer matches its copies
+    let bit = parent_root[0].0;
+    let failures = test.run(&[(bit.cell, Fr::one() - bit.val)]);
+    assert!(!failures.is_empty());
+    assert!(failures.iter().all(|(gadget, _)| *gadget == "header"));
+
+    // slots are uint64
+    let mut test = NegativeTest::<Fr>::new();
+    test.gadget("header", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let header = header(&mut ctx, Fr::from(u64::MAX) + Fr::one());
+        ctx.ssz_beacon_block_header_root(&header);
+    });
+    assert!(!test.run(&[]).is_empty());
+}
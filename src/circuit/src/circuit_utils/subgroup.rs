@@ -0,0 +1,159 @@
+/*
+  Subgroup checks for BLS12-381 through the curve endomorphisms instead of a
+  multiplication by the group order (Scott, "A note on group membership
+  tests for G1, G2 and GT on BLS pairing-friendly curves"):
+    G1: sigma(P) = -x^2 * P, sigma(x, y) = (beta * x, y),
+    G2: psi(P) = x * P,
+  for the BLS parameter x = -0xd201000000010000. The points must already be
+  on the curve.
+*/
+
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::pairing::bls12_381::{Fq, G1Affine};
+
+use super::ecc_chip::EccChipBaseOps;
+use super::frobenius::FrobeniusChipOps;
+use super::integer_chip::IntegerChipOps;
+use super::isogeny::fq;
+use crate::assign::{AssignedG2Affine, AssignedPoint};
+use crate::context::GeneralScalarEccContext;
+
+// |x|, x is negative.
+pub const BLS_X: u64 = 0xd201000000010000;
+
+// The cube root of unity sigma multiplies by, the one for which sigma acts
+// as -x^2 on G1.
+const BETA: &str = "00000000000000005f19672fdf76ce51ba69c6076a0f77eaddb3a93be6f89688de17d813620a00022e01fffffffefffe";
+
+pub trait SubgroupChipOps<N: FieldExt>: FrobeniusChipOps<N> {
+    fn g1_sigma(&mut self, a: &AssignedPoint<G1Affine, N>) -> AssignedPoint<G1Affine, N> {
+        let beta = self.base_integer_chip().assign_int_constant(fq(BETA));
+        let x = self.base_integer_chip().int_mul(&a.x, &beta);
+        AssignedPoint::new(x, a.y.clone(), a.z)
+    }
+
+    fn assert_in_g1_subgroup(&mut self, a: &AssignedPoint<G1Affine, N>) {
+        let sigma = self.g1_sigma(a);
+        let t = self.ecc_mul_small_constant(a, BLS_X);
+        let t = self.ecc_mul_small_constant(&t, BLS_X);
+        let t = self.ecc_neg(&t);
+        self.ecc_assert_equal(&sigma, &t);
+    }
+
+    fn assert_in_g2_subgroup(&mut self, a: &AssignedG2Affine<G1Affine, N>) {
+        let psi = self.g2_psi(a);
+        let t = self.ecc_g2_mul_small_constant(a, BLS_X);
+        let t = self.ecc_g2_neg(&t);
+        self.ecc_assert_g2_equal(&psi, &t);
+    }
+}
+
+impl<N: FieldExt, B: IntegerChipOps<Fq, N>> SubgroupChipOps<N>
+    for GeneralScalarEccContext<G1Affine, N, B>
+{
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_endomorphism_subgroup_checks() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381::{Fq2, Fr as BlsFr, G2Affine};
+    use halo2_proofs::pairing::bn256::Fr;
+    use halo2_proofs::pairing::group::Curve;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    use crate::assign::AssignedCondition;
+    use crate::circuit_utils::base_chip::BaseChipOps;
+    use crate::negative_test::NegativeTest;
+    use crate::utils::field_to_bn;
+
+    type Ctx = GeneralScalarEccContext<G1Affine, Fr>;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+
+    // Whether the constraints recorded by `gadget` are satisfied.
+    let satisfied = |gadget: &dyn Fn(&mut Ctx)| {
+        let mut test = NegativeTest::<Fr>::new();
+        test.gadget("subgroup", |ctx| gadget(&mut Ctx::new(ctx.clone())));
+        test.run(&[]).is_empty()
+    };
+    let sides = |ctx: &mut Ctx, a: &AssignedPoint<G1Affine, Fr>| {
+        let sigma = ctx.g1_sigma(a);
+        let t = ctx.ecc_mul_small_constant(a, BLS_X);
+        let t = ctx.ecc_mul_small_constant(&t, BLS_X);
+        let t = ctx.ecc_neg(&t);
+        (
+            ctx.base_integer_ctx.get_w(&sigma.x),
+            ctx.base_integer_ctx.get_w(&t.x),
+        )
+    };
+
+    let p = (G1Affine::generator() * BlsFr::random(&mut rng)).to_affine();
+    assert!(satisfied(&|ctx| {
+        let p = ctx.assign_non_zero_point(&p);
+        let (sigma, t) = sides(ctx, &p);
+        assert_eq!(sigma, t);
+        ctx.assert_in_g1_subgroup(&p);
+    }));
+
+    // (4, y) is on y^2 = x^3 + 4 but outside G1.
+    let y = fq("0a989badd40d6212b33cffc3f3763e9bc760f988c9926b26da9dd85e928483446346b8ed00e1de5d5ea93e354abe706c");
+    assert!(!satisfied(&|ctx| {
+        let x = ctx.base_integer_ctx.assign_w(&field_to_bn(&Fq::from(4u64)));
+        let y = ctx.base_integer_ctx.assign_w(&field_to_bn(&y));
+        let z = AssignedCondition(ctx.native_ctx.borrow_mut().assign_constant(Fr::zero()));
+        let p = AssignedPoint::new(x, y, z);
+        let (sigma, t) = sides(ctx, &p);
+        assert_ne!(sigma, t);
+        ctx.assert_in_g1_subgroup(&p);
+    }));
+
+    let g = (G2Affine::generator() * BlsFr::random(&mut rng)).to_affine();
+    let g = ((g.x.c0, g.x.c1), (g.y.c0, g.y.c1));
+    assert!(satisfied(&|ctx| {
+        let g = ctx.assign_non_identity_constant_g2(&g);
+        let psi = ctx.g2_psi(&g);
+        let t = ctx.ecc_g2_mul_small_constant(&g, BLS_X);
+        let t = ctx.ecc_g2_neg(&t);
+        assert_eq!(
+            ctx.base_integer_ctx.get_w(&psi.y.0),
+            ctx.base_integer_ctx.get_w(&t.y.0)
+        );
+        ctx.assert_in_g2_subgroup(&g);
+    }));
+
+    // The first (x, y) with x in Fq on the twist y^2 = x^3 + 4(u + 1), whose
+    // order is not r.
+    let b = Fq2 {
+        c0: Fq::from(4u64),
+        c1: Fq::from(4u64),
+    };
+    let (x, y) = (1u64..)
+        .find_map(|i| {
+            let x = Fq2 {
+                c0: Fq::from(i),
+                c1: Fq::zero(),
+            };
+            Option::<Fq2>::from((x.square() * x + b).sqrt()).map(|y| (x, y))
+        })
+        .unwrap();
+    assert_eq!(y.square(), x.square() * x + b);
+    assert!(!satisfied(&|ctx| {
+        let p = ctx.assign_non_identity_constant_g2(&((x.c0, x.c1), (y.c0, y.c1)));
+        let psi = ctx.g2_psi(&p);
+        let t = ctx.ecc_g2_mul_small_constant(&p, BLS_X);
+        let t = ctx.ecc_g2_neg(&t);
+        assert_ne!(
+            (
+                ctx.base_integer_ctx.get_w(&psi.x.0),
+                ctx.base_integer_ctx.get_w(&psi.x.1)
+            ),
+            (
+                ctx.base_integer_ctx.get_w(&t.x.0),
+                ctx.base_integer_ctx.get_w(&t.x.1)
+            )
+        );
+        ctx.assert_in_g2_subgroup(&p);
+    }));
+}
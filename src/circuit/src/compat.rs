@@ -0,0 +1,45 @@
+/*
+  The parts of the halo2 fork API the circuits depend on, in one place.
+
+  The fork takes cell values through closures returning `Result`, and its
+  fields implement `BaseExt` for byte (de)serialization, which `utils` uses
+  for every field and integer conversion. Chips assign through the helpers
+  below, so building against another halo2 line (e.g. the PSE one with its
+  `Value` closures and `halo2curves` fields) means reimplementing this
+  module and the two conversions in `utils`.
+*/
+
+pub use halo2_proofs::arithmetic::{BaseExt, CurveAffine, FieldExt};
+use halo2_proofs::circuit::{AssignedCell, Region, Table};
+use halo2_proofs::plonk::{Advice, Column, Error, Fixed, TableColumn};
+
+pub fn assign_advice<N: FieldExt>(
+    region: &mut Region<'_, N>,
+    annotation: &'static str,
+    column: Column<Advice>,
+    row: usize,
+    v: N,
+) -> Result<AssignedCell<N, N>, Error> {
+    region.assign_advice(|| annotation, column, row, || Ok(v))
+}
+
+pub fn assign_fixed<N: FieldExt>(
+    region: &mut Region<'_, N>,
+    annotation: &'static str,
+    column: Column<Fixed>,
+    row: usize,
+    v: N,
+) -> Result<(), Error> {
+    region.assign_fixed(|| annotation, column, row, || Ok(v))?;
+    Ok(())
+}
+
+pub fn assign_table_cell<N: FieldExt>(
+    table: &mut Table<'_, N>,
+    annotation: &'static str,
+    column: TableColumn,
+    offset: usize,
+    v: N,
+) -> Result<(), Error> {
+    table.assign_cell(|| annotation, column, offset, || Ok(v))
+}
@@ -2,31 +2,42 @@
   The implementation is ported from https://github.com/DelphinusLab/halo2ecc-s
 */
 
-use crate::assign::{AssignedValue, Cell, Chip, ValueSchema};
+use crate::assign::{
+    AssignedG2Affine, AssignedInteger, AssignedPoint, AssignedValue, Cell, Chip, ValueSchema,
+};
 use crate::circuit_utils::{
     base_chip::{BaseChip, FIXED_COLUMNS, MUL_COLUMNS, VAR_COLUMNS},
-    range_chip::{RangeChip, COMMON_RANGE_BITS, MAX_CHUNKS},
+    range_chip::{RangeChip, COMMON_RANGE_BITS, MAX_CHUNKS, OVERFLOW_BITS},
 };
+use crate::compat::{assign_advice, assign_fixed};
+use crate::error::CircuitError;
 use crate::range_info::RangeInfo;
+#[cfg(feature = "zeroize")]
+use crate::utils::zeroize_fields;
 use halo2_proofs::{
     arithmetic::{BaseExt, CurveAffine, FieldExt},
-    circuit::{AssignedCell, Region},
+    circuit::{self, AssignedCell, Region},
     plonk::Error,
 };
+use num_bigint::BigUint;
 use std::{
     cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::{Display, Formatter},
 };
 use std::{
     rc::Rc,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, MutexGuard},
 };
+use tiny_keccak::{Hasher, Keccak};
 
 #[derive(Debug, Clone)]
 pub struct Context<N: FieldExt> {
     pub records: Arc<Mutex<Records<N>>>,
     pub base_offset: usize,
     pub range_offset: usize,
+    // Cells of the constants assigned so far, by value, see `assign_constant`.
+    pub constants: BTreeMap<BigUint, AssignedValue<N>>,
 }
 
 impl<N: FieldExt> Display for Context<N> {
@@ -45,25 +56,203 @@ impl<N: FieldExt> Context<N> {
             records: Arc::new(Mutex::new(Records::default())),
             base_offset: 0,
             range_offset: 0,
+            constants: BTreeMap::new(),
+        }
+    }
+
+    // Records into `buffer`, whose backing stores are cleared and kept, so
+    // proving in a loop doesn't reallocate the whole witness every time.
+    pub fn new_reusing(mut buffer: Records<N>) -> Self {
+        buffer.clear();
+        Self {
+            records: Arc::new(Mutex::new(buffer)),
+            ..Self::new()
+        }
+    }
+}
+
+impl<N: FieldExt> Context<N> {
+    // No more rows are recorded past this point, so the permutations are
+    // compacted here once.
+    pub fn into_records(self) -> Result<Records<N>, CircuitError> {
+        let mut records = Arc::try_unwrap(self.records)
+            .map_err(|_| CircuitError::ContextStillShared)?
+            .into_inner()
+            .map_err(|_| CircuitError::RecordsPoisoned)?;
+        records.compact_permutations();
+        Ok(records)
+    }
+
+    fn lock_records(&self) -> Result<MutexGuard<'_, Records<N>>, CircuitError> {
+        self.records
+            .lock()
+            .map_err(|_| CircuitError::RecordsPoisoned)
+    }
+
+    pub fn checkpoint(&self) -> Result<ContextCheckpoint, CircuitError> {
+        Ok(ContextCheckpoint {
+            base_offset: self.base_offset,
+            range_offset: self.range_offset,
+            permutations: self.lock_records()?.permutations.len(),
+        })
+    }
+
+    // Everything recorded since `checkpoint`, to be replayed at the same offsets.
+    pub fn segment_since(
+        &self,
+        checkpoint: &ContextCheckpoint,
+    ) -> Result<RecordsSegment<N>, CircuitError> {
+        let records = self.lock_records()?;
+
+        Ok(RecordsSegment {
+            checkpoint: checkpoint.clone(),
+            base_adv_record: records.base_adv_record[checkpoint.base_offset..self.base_offset]
+                .to_vec(),
+            base_fix_record: records.base_fix_record[checkpoint.base_offset..self.base_offset]
+                .to_vec(),
+            base_height: records.base_height,
+            range_adv_record: records.range_adv_record[checkpoint.range_offset..self.range_offset]
+                .to_vec(),
+            range_fix_record: records.range_fix_record[checkpoint.range_offset..self.range_offset]
+                .to_vec(),
+            range_height: records.range_height,
+            permutations: records.permutations[checkpoint.permutations..].to_vec(),
+            constants: self
+                .constants
+                .iter()
+                .filter(|(_, c)| c.cell.row >= checkpoint.base_offset)
+                .map(|(v, c)| (v.clone(), *c))
+                .collect(),
+        })
+    }
+
+    // Binds `v` to row `row` of the instance column, see `exposed_cells`.
+    pub fn expose_public(&mut self, v: &AssignedValue<N>, row: usize) -> Result<(), CircuitError> {
+        let mut records = self.lock_records()?;
+        records.enable_permute(&v.cell);
+        records.exposed.push((v.cell, row));
+        Ok(())
+    }
+
+    // Exposes the limbs from `row` on, returns the next free row.
+    pub fn expose_public_integer<W: BaseExt>(
+        &mut self,
+        a: &AssignedInteger<W, N>,
+        row: usize,
+    ) -> Result<usize, CircuitError> {
+        for (i, limb) in a.limbs_le.iter().enumerate() {
+            self.expose_public(limb, row + i)?;
+        }
+        Ok(row + a.limbs_le.len())
+    }
+
+    // x then y limbs. The identity flag is not exposed, normalize the point
+    // first if it may be the identity.
+    pub fn expose_public_point<C: CurveAffine>(
+        &mut self,
+        p: &AssignedPoint<C, N>,
+        row: usize,
+    ) -> Result<usize, CircuitError> {
+        let row = self.expose_public_integer(&p.x, row)?;
+        self.expose_public_integer(&p.y, row)
+    }
+
+    pub fn expose_public_g2_point<C: CurveAffine>(
+        &mut self,
+        p: &AssignedG2Affine<C, N>,
+        row: usize,
+    ) -> Result<usize, CircuitError> {
+        let row = self.expose_public_integer(&p.x.0, row)?;
+        let row = self.expose_public_integer(&p.x.1, row)?;
+        let row = self.expose_public_integer(&p.y.0, row)?;
+        self.expose_public_integer(&p.y.1, row)
+    }
+
+    pub fn can_replay(&self, segment: &RecordsSegment<N>) -> Result<bool, CircuitError> {
+        Ok(self.checkpoint()? == segment.checkpoint)
+    }
+
+    pub fn replay(&mut self, segment: &RecordsSegment<N>) -> Result<(), CircuitError> {
+        if !self.can_replay(segment)? {
+            return Err(CircuitError::SegmentMismatch);
         }
+
+        self.lock_records()?.apply_segment(segment);
+        self.base_offset += segment.base_adv_record.len();
+        self.range_offset += segment.range_adv_record.len();
+        self.constants.extend(segment.constants.iter().cloned());
+        Ok(())
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextCheckpoint {
+    pub base_offset: usize,
+    pub range_offset: usize,
+    pub permutations: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordsSegment<N: FieldExt> {
+    pub checkpoint: ContextCheckpoint,
+
+    pub base_adv_record: Vec<[(N, bool); VAR_COLUMNS]>,
+    pub base_fix_record: Vec<[N; FIXED_COLUMNS]>,
+    pub base_height: usize,
+
+    pub range_adv_record: Vec<(N, bool)>,
+    pub range_fix_record: Vec<[N; 2]>,
+    pub range_height: usize,
+
+    pub permutations: Vec<(Cell, Cell)>,
+    // Constants first assigned in the segment, later uses copy their cells.
+    pub constants: Vec<(BigUint, AssignedValue<N>)>,
+}
+
+// The assigned cells of `exposed` with their instance rows, `lookup` finds
+// a cell in what `Records::assign_all` or `assign_all_low_memory` returned.
+pub fn exposed_cells(
+    exposed: &[(Cell, usize)],
+    lookup: impl Fn(&Cell) -> Result<circuit::Cell, CircuitError>,
+) -> Result<Vec<(circuit::Cell, usize)>, CircuitError> {
+    exposed
+        .iter()
+        .map(|(cell, row)| Ok((lookup(cell)?, *row)))
+        .collect()
+}
+
+// Looks `cell` up in the table returned by `Records::assign_all`.
+pub fn assigned_cell<N: FieldExt>(
+    cells: &[Vec<Vec<Option<AssignedCell<N, N>>>>],
+    cell: &Cell,
+) -> Result<circuit::Cell, CircuitError> {
+    cells
+        .get(cell.region as usize)
+        .and_then(|chip| chip.get(cell.col))
+        .and_then(|col| col.get(cell.row))
+        .and_then(|assigned| assigned.as_ref())
+        .map(|assigned| assigned.cell())
+        .ok_or(CircuitError::UnassignedCell(*cell))
+}
+
 #[derive(Debug, Clone)]
 pub struct IntegerContext<W: BaseExt, N: FieldExt> {
     pub ctx: Rc<RefCell<Context<N>>>,
     pub info: Arc<RangeInfo<W, N>>,
 }
 
-impl<W: BaseExt, N: FieldExt> From<IntegerContext<W, N>> for Context<N> {
-    fn from(value: IntegerContext<W, N>) -> Self {
-        Rc::try_unwrap(value.ctx).unwrap().into_inner()
+impl<W: BaseExt, N: FieldExt> TryFrom<IntegerContext<W, N>> for Context<N> {
+    type Error = CircuitError;
+
+    fn try_from(value: IntegerContext<W, N>) -> Result<Self, CircuitError> {
+        Ok(Rc::try_unwrap(value.ctx)
+            .map_err(|_| CircuitError::ContextStillShared)?
+            .into_inner())
     }
 }
 
 impl<W: BaseExt, N: FieldExt> IntegerContext<W, N> {
     pub fn new(ctx: Rc<RefCell<Context<N>>>) -> Self {
-        const OVERFLOW_BITS: u64 = 6;
         Self::new_with_options(ctx, COMMON_RANGE_BITS, OVERFLOW_BITS)
     }
 
@@ -77,36 +266,71 @@ impl<W: BaseExt, N: FieldExt> IntegerContext<W, N> {
             info: Arc::new(RangeInfo::<W, N>::new(common_range_bits, overflow_bits)),
         }
     }
+
+    pub fn new_with_limb_bits(
+        ctx: Rc<RefCell<Context<N>>>,
+        limb_bits: u64,
+        overflow_bits: u64,
+    ) -> Self {
+        Self {
+            ctx,
+            info: Arc::new(RangeInfo::<W, N>::new_with_limb_bits(
+                COMMON_RANGE_BITS,
+                limb_bits,
+                overflow_bits,
+            )),
+        }
+    }
 }
 
 pub struct NativeScalarEccContext<C: CurveAffine>(
     pub IntegerContext<<C as CurveAffine>::Base, <C as CurveAffine>::ScalarExt>,
 );
 
-impl<C: CurveAffine> From<NativeScalarEccContext<C>> for Context<C::Scalar> {
-    fn from(value: NativeScalarEccContext<C>) -> Self {
-        value.0.into()
+impl<C: CurveAffine> TryFrom<NativeScalarEccContext<C>> for Context<C::Scalar> {
+    type Error = CircuitError;
+
+    fn try_from(value: NativeScalarEccContext<C>) -> Result<Self, CircuitError> {
+        value.0.try_into()
     }
 }
 
-pub struct GeneralScalarEccContext<C: CurveAffine, N: FieldExt> {
-    pub base_integer_ctx: IntegerContext<<C as CurveAffine>::Base, N>,
+// `B` is the integer chip the ECC gadgets run on for coordinates, any
+// `IntegerChipOps` implementation can stand in for the default one.
+pub struct GeneralScalarEccContext<
+    C: CurveAffine,
+    N: FieldExt,
+    B = IntegerContext<<C as CurveAffine>::Base, N>,
+> {
+    pub base_integer_ctx: B,
     pub scalar_integer_ctx: IntegerContext<<C as CurveAffine>::ScalarExt, N>,
     pub native_ctx: Rc<RefCell<Context<N>>>,
 }
 
-impl<C: CurveAffine, N: FieldExt> From<GeneralScalarEccContext<C, N>> for Context<N> {
-    fn from(value: GeneralScalarEccContext<C, N>) -> Self {
+impl<C: CurveAffine, N: FieldExt, B> TryFrom<GeneralScalarEccContext<C, N, B>> for Context<N> {
+    type Error = CircuitError;
+
+    fn try_from(value: GeneralScalarEccContext<C, N, B>) -> Result<Self, CircuitError> {
         drop(value.base_integer_ctx);
         drop(value.scalar_integer_ctx);
-        Rc::try_unwrap(value.native_ctx).unwrap().into_inner()
+        Ok(Rc::try_unwrap(value.native_ctx)
+            .map_err(|_| CircuitError::ContextStillShared)?
+            .into_inner())
     }
 }
 
 impl<C: CurveAffine, N: FieldExt> GeneralScalarEccContext<C, N> {
     pub fn new(ctx: Rc<RefCell<Context<N>>>) -> Self {
+        let base_integer_ctx = IntegerContext::<C::Base, N>::new(ctx.clone());
+        Self::new_with_base_integer_ctx(ctx, base_integer_ctx)
+    }
+}
+
+impl<C: CurveAffine, N: FieldExt, B> GeneralScalarEccContext<C, N, B> {
+    // `base_integer_ctx` must record into `ctx`.
+    pub fn new_with_base_integer_ctx(ctx: Rc<RefCell<Context<N>>>, base_integer_ctx: B) -> Self {
         Self {
-            base_integer_ctx: IntegerContext::<C::Base, N>::new(ctx.clone()),
+            base_integer_ctx,
             scalar_integer_ctx: IntegerContext::<C::Scalar, N>::new(ctx.clone()),
             native_ctx: ctx,
         }
@@ -115,15 +339,99 @@ impl<C: CurveAffine, N: FieldExt> GeneralScalarEccContext<C, N> {
 
 #[derive(Debug, Default, Clone)]
 pub struct Records<N: FieldExt> {
-    pub base_adv_record: Vec<[(Option<N>, bool); VAR_COLUMNS]>,
-    pub base_fix_record: Vec<[Option<N>; FIXED_COLUMNS]>,
+    pub base_adv_record: Vec<[(N, bool); VAR_COLUMNS]>,
+    pub base_fix_record: Vec<[N; FIXED_COLUMNS]>,
     pub base_height: usize,
 
-    pub range_adv_record: Vec<(Option<N>, bool)>,
-    pub range_fix_record: Vec<[Option<N>; 2]>,
+    pub range_adv_record: Vec<(N, bool)>,
+    pub range_fix_record: Vec<[N; 2]>,
     pub range_height: usize,
 
     pub permutations: Vec<(Cell, Cell)>,
+    // (cell, instance row) pairs recorded by `Context::expose_public`.
+    pub exposed: Vec<(Cell, usize)>,
+    // Base cells written by `one_line`, only tracked when set, for the
+    // coverage audit to tell assigned zeros from empty cells. Replayed
+    // segments are not tracked.
+    pub assigned: Option<HashSet<Cell>>,
+}
+
+// Records shared by the synthesize calls made with one witness, keygen of
+// the verifying then the proving key for instance, recorded by the first
+// call and replayed by the others.
+#[derive(Debug, Default, Clone)]
+pub struct RecordsSnapshot<N: FieldExt> {
+    records: Arc<Mutex<Option<Arc<Records<N>>>>>,
+}
+
+impl<N: FieldExt> RecordsSnapshot<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_or_record(
+        &self,
+        record: impl FnOnce() -> Result<Records<N>, CircuitError>,
+    ) -> Result<Arc<Records<N>>, CircuitError> {
+        let mut records = self
+            .records
+            .lock()
+            .map_err(|_| CircuitError::RecordsPoisoned)?;
+        if let Some(records) = records.as_ref() {
+            return Ok(records.clone());
+        }
+
+        let recorded = Arc::new(record()?);
+        *records = Some(recorded.clone());
+        Ok(recorded)
+    }
+
+    pub fn is_recorded(&self) -> bool {
+        self.records
+            .lock()
+            .map(|records| records.is_some())
+            .unwrap_or(false)
+    }
+}
+
+// Records still held by another handle are only dropped.
+#[cfg(feature = "zeroize")]
+impl<N: FieldExt> zeroize::Zeroize for RecordsSnapshot<N> {
+    fn zeroize(&mut self) {
+        let records = self
+            .records
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        if let Some(mut records) = records.and_then(|r| Arc::try_unwrap(r).ok()) {
+            zeroize::Zeroize::zeroize(&mut records);
+        }
+    }
+}
+
+// Advice values hold the messages and keys the witness was generated from,
+// fixed values are circuit constants and stay.
+#[cfg(feature = "zeroize")]
+impl<N: FieldExt> zeroize::Zeroize for Records<N> {
+    fn zeroize(&mut self) {
+        zeroize_fields(self.base_adv_record.iter_mut().flatten().map(|(v, _)| v));
+        zeroize_fields(self.range_adv_record.iter_mut().map(|(v, _)| v));
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<N: FieldExt> zeroize::Zeroize for RecordsSegment<N> {
+    fn zeroize(&mut self) {
+        zeroize_fields(self.base_adv_record.iter_mut().flatten().map(|(v, _)| v));
+        zeroize_fields(self.range_adv_record.iter_mut().map(|(v, _)| v));
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<N: FieldExt> Drop for Records<N> {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
 }
 
 impl<N: FieldExt> Records<N> {
@@ -131,26 +439,19 @@ impl<N: FieldExt> Records<N> {
         &self,
         region: &mut Region<'_, N>,
         base_chip: &BaseChip<N>,
-    ) -> Result<Vec<Vec<Option<AssignedCell<N, N>>>>, Error> {
-        let mut cells = vec![];
-
-        cells.resize(VAR_COLUMNS, vec![None; self.base_height]);
-
+        mut on_permuted: impl FnMut(Cell, AssignedCell<N, N>),
+    ) -> Result<(), Error> {
         for (row, advs) in self.base_adv_record.iter().enumerate() {
             if row >= self.base_height {
                 break;
             }
 
             for (col, adv) in advs.iter().enumerate() {
-                if adv.0.is_some() {
-                    let cell = region.assign_advice(
-                        || "base",
-                        base_chip.config.base[col],
-                        row,
-                        || Ok(adv.0.unwrap()),
-                    )?;
+                if adv.1 || adv.0 != N::zero() {
+                    let cell =
+                        assign_advice(region, "base", base_chip.config.base[col], row, adv.0)?;
                     if adv.1 {
-                        cells[col][row] = Some(cell);
+                        on_permuted(Cell::new(Chip::BaseChip, col, row), cell);
                     }
                 }
             }
@@ -162,7 +463,7 @@ impl<N: FieldExt> Records<N> {
             }
 
             for (col, fix) in fixes.iter().enumerate() {
-                if fix.is_some() {
+                if *fix != N::zero() {
                     let col = if col < VAR_COLUMNS {
                         base_chip.config.coeff[col]
                     } else if col - VAR_COLUMNS < MUL_COLUMNS {
@@ -173,40 +474,41 @@ impl<N: FieldExt> Records<N> {
                         base_chip.config.constant
                     };
 
-                    region.assign_fixed(|| "fix", col, row, || Ok(fix.unwrap()))?;
+                    assign_fixed(region, "fix", col, row, *fix)?;
                 }
             }
         }
 
-        Ok(cells)
+        Ok(())
     }
 
     pub fn _assign_to_range_chip(
         &self,
         region: &mut Region<'_, N>,
         range_chip: &RangeChip<N>,
-    ) -> Result<Vec<Vec<Option<AssignedCell<N, N>>>>, Error> {
-        let mut cells = vec![vec![None; self.range_height]];
-
+        mut on_permuted: impl FnMut(Cell, AssignedCell<N, N>),
+    ) -> Result<(), Error> {
         for (row, fix) in self.range_fix_record.iter().enumerate() {
             if row >= self.range_height {
                 break;
             }
-            if fix[0].is_some() {
-                region.assign_fixed(
-                    || "range block first",
+            if fix[0] != N::zero() {
+                assign_fixed(
+                    region,
+                    "range block first",
                     range_chip.config.block_first,
                     row,
-                    || Ok(fix[0].unwrap()),
+                    fix[0],
                 )?;
             }
 
-            if fix[1].is_some() {
-                region.assign_fixed(
-                    || "range class",
+            if fix[1] != N::zero() {
+                assign_fixed(
+                    region,
+                    "range class",
                     range_chip.config.range_class,
                     row,
-                    || Ok(fix[1].unwrap()),
+                    fix[1],
                 )?;
             }
         }
@@ -215,20 +517,15 @@ impl<N: FieldExt> Records<N> {
             if row >= self.range_height {
                 break;
             }
-            if adv.0.is_some() {
-                let cell = region.assign_advice(
-                    || "range var",
-                    range_chip.config.value,
-                    row,
-                    || Ok(adv.0.unwrap()),
-                )?;
+            if adv.1 || adv.0 != N::zero() {
+                let cell = assign_advice(region, "range var", range_chip.config.value, row, adv.0)?;
                 if adv.1 {
-                    cells[0][row] = Some(cell);
+                    on_permuted(Cell::new(Chip::RangeChip, 0, row), cell);
                 }
             }
         }
 
-        Ok(cells)
+        Ok(())
     }
 
     pub fn _assign_permutation(
@@ -237,33 +534,301 @@ impl<N: FieldExt> Records<N> {
         cells: &Vec<Vec<Vec<Option<AssignedCell<N, N>>>>>,
     ) -> Result<(), Error> {
         for (left, right) in self.permutations.iter() {
-            let left = cells[left.region as usize][left.col][left.row]
-                .as_ref()
-                .unwrap()
-                .cell();
-            let right = cells[right.region as usize][right.col][right.row]
-                .as_ref()
-                .unwrap()
-                .cell();
-            region.constrain_equal(left, right)?;
+            region.constrain_equal(assigned_cell(cells, left)?, assigned_cell(cells, right)?)?;
         }
 
         Ok(())
     }
 
+    // The floor planner runs the region closure once to measure its shape
+    // before assigning it. Only the last row of every used column matters
+    // for the measurement, so avoid walking all records in that pass.
+    pub fn assign_shape(
+        &self,
+        region: &mut Region<'_, N>,
+        base_chip: &BaseChip<N>,
+        range_chip: &RangeChip<N>,
+    ) -> Result<(), Error> {
+        if self.base_height > 0 {
+            let row = self.base_height - 1;
+            for col in base_chip.config.base {
+                assign_advice(region, "base shape", col, row, N::zero())?;
+            }
+            for col in base_chip
+                .config
+                .coeff
+                .iter()
+                .chain(base_chip.config.mul_coeff.iter())
+                .chain([base_chip.config.next_coeff, base_chip.config.constant].iter())
+            {
+                assign_fixed(region, "fix shape", *col, row, N::zero())?;
+            }
+        }
+
+        if self.range_height > 0 {
+            let row = self.range_height - 1;
+            assign_advice(
+                region,
+                "range shape",
+                range_chip.config.value,
+                row,
+                N::zero(),
+            )?;
+            for col in [range_chip.config.block_first, range_chip.config.range_class] {
+                assign_fixed(region, "range fix shape", col, row, N::zero())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Whether both records lay out the same circuit: equal fixed columns,
+    // heights and copy constraints, whatever the advice values are. The
+    // verifying key only depends on this, so keygen on a default circuit
+    // must have the same shape as proving on a real one.
+    pub fn same_shape(&self, other: &Self) -> bool {
+        let base_permuted = |records: &Self| {
+            records.base_adv_record[..records.base_height]
+                .iter()
+                .map(|row| row.map(|(_, p)| p))
+                .collect::<Vec<_>>()
+        };
+        let range_permuted = |records: &Self| {
+            records.range_adv_record[..records.range_height]
+                .iter()
+                .map(|(_, p)| *p)
+                .collect::<Vec<_>>()
+        };
+
+        self.base_height == other.base_height
+            && self.range_height == other.range_height
+            && self.base_fix_record[..self.base_height]
+                == other.base_fix_record[..other.base_height]
+            && self.range_fix_record[..self.range_height]
+                == other.range_fix_record[..other.range_height]
+            && base_permuted(self) == base_permuted(other)
+            && range_permuted(self) == range_permuted(other)
+            && self.permutations == other.permutations
+            && self.exposed == other.exposed
+    }
+
+    // Empties the records, keeping their allocations. Advice values are
+    // wiped first when they hold secrets.
+    pub fn clear(&mut self) {
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(self);
+
+        self.base_adv_record.clear();
+        self.base_fix_record.clear();
+        self.base_height = 0;
+        self.range_adv_record.clear();
+        self.range_fix_record.clear();
+        self.range_height = 0;
+        self.permutations.clear();
+        self.exposed.clear();
+        if let Some(assigned) = self.assigned.as_mut() {
+            assigned.clear();
+        }
+    }
+
+    // Rewrites the permutations as one pair per cell joining its copy class,
+    // linked to the first cell of the class in recording order. Duplicates,
+    // cycles and chains through intermediate copies collapse to
+    // `cells - classes` pairs, in an order only the recorded one decides.
+    pub fn compact_permutations(&mut self) {
+        fn find(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        let mut index = HashMap::new();
+        let mut cells = vec![];
+        let mut parent = vec![];
+        for (l, r) in self.permutations.iter() {
+            let [l, r] = [l, r].map(|cell| {
+                *index.entry(*cell).or_insert_with(|| {
+                    cells.push(*cell);
+                    parent.push(cells.len() - 1);
+                    cells.len() - 1
+                })
+            });
+
+            let (l, r) = (find(&mut parent, l), find(&mut parent, r));
+            // the root stays the first cell seen
+            parent[usize::max(l, r)] = usize::min(l, r);
+        }
+
+        self.permutations = (0..cells.len())
+            .filter_map(|i| {
+                let root = find(&mut parent, i);
+                (root != i).then(|| (cells[root], cells[i]))
+            })
+            .collect();
+    }
+
+    // keccak256 of every record up to the heights, witness values included.
+    // Witness generation runs on one thread and never iterates a hash map,
+    // so the same inputs give the same digest on every machine.
+    pub fn digest(&self) -> [u8; 32] {
+        self.hash(true)
+    }
+
+    // keccak256 of what `same_shape` compares: heights, fixed cells, which
+    // cells are permuted, the permutations and the exposed cells. Equal for
+    // any two inputs of a circuit that keygen and proving agree on.
+    pub fn shape_digest(&self) -> [u8; 32] {
+        self.hash(false)
+    }
+
+    fn hash(&self, with_witness: bool) -> [u8; 32] {
+        let mut bytes = vec![];
+        let write_value = |bytes: &mut Vec<u8>, v: &N| v.write(bytes).unwrap();
+        let write_cell = |bytes: &mut Vec<u8>, cell: &Cell| {
+            bytes.push(cell.region as u8);
+            bytes.extend((cell.col as u64).to_le_bytes());
+            bytes.extend((cell.row as u64).to_le_bytes());
+        };
+
+        bytes.extend((self.base_height as u64).to_le_bytes());
+        for (advs, fixes) in self.base_adv_record[..self.base_height]
+            .iter()
+            .zip(self.base_fix_record.iter())
+        {
+            for (v, permuted) in advs.iter() {
+                if with_witness {
+                    write_value(&mut bytes, v);
+                }
+                bytes.push(*permuted as u8);
+            }
+            for v in fixes.iter() {
+                write_value(&mut bytes, v);
+            }
+        }
+
+        bytes.extend((self.range_height as u64).to_le_bytes());
+        for ((v, permuted), fixes) in self.range_adv_record[..self.range_height]
+            .iter()
+            .zip(self.range_fix_record.iter())
+        {
+            if with_witness {
+                write_value(&mut bytes, v);
+            }
+            bytes.push(*permuted as u8);
+            for v in fixes.iter() {
+                write_value(&mut bytes, v);
+            }
+        }
+
+        for (l, r) in self.permutations.iter() {
+            write_cell(&mut bytes, l);
+            write_cell(&mut bytes, r);
+        }
+        for (cell, row) in self.exposed.iter() {
+            write_cell(&mut bytes, cell);
+            bytes.extend((*row as u64).to_le_bytes());
+        }
+
+        let mut hasher = Keccak::v256();
+        hasher.update(&bytes);
+        let mut digest = [0u8; 32];
+        hasher.finalize(&mut digest);
+        digest
+    }
+
     pub fn assign_all(
         &self,
         region: &mut Region<'_, N>,
         base_chip: &BaseChip<N>,
         range_chip: &RangeChip<N>,
     ) -> Result<Vec<Vec<Vec<Option<AssignedCell<N, N>>>>>, Error> {
-        let base_cells = self._assign_to_base_chip(region, base_chip)?;
-        let range_cells = self._assign_to_range_chip(region, range_chip)?;
+        let mut base_cells = vec![vec![None; self.base_height]; VAR_COLUMNS];
+        self._assign_to_base_chip(region, base_chip, |cell, assigned| {
+            base_cells[cell.col][cell.row] = Some(assigned)
+        })?;
+
+        let mut range_cells = vec![vec![None; self.range_height]];
+        self._assign_to_range_chip(region, range_chip, |cell, assigned| {
+            range_cells[cell.col][cell.row] = Some(assigned)
+        })?;
+
         let cells = vec![base_cells, range_cells];
         self._assign_permutation(region, &cells)?;
         Ok(cells)
     }
 
+    // Low-memory variant of `assign_all`: only permuted cells are kept, in a
+    // sparse map instead of a dense table of every row, and each chip's
+    // records are freed as soon as they are assigned.
+    pub fn assign_all_low_memory(
+        mut self,
+        region: &mut Region<'_, N>,
+        base_chip: &BaseChip<N>,
+        range_chip: &RangeChip<N>,
+    ) -> Result<HashMap<Cell, circuit::Cell>, Error> {
+        let mut cells = HashMap::new();
+
+        self._assign_to_base_chip(region, base_chip, |cell, assigned| {
+            cells.insert(cell, assigned.cell());
+        })?;
+        #[cfg(feature = "zeroize")]
+        zeroize_fields(self.base_adv_record.iter_mut().flatten().map(|(v, _)| v));
+        self.base_adv_record = vec![];
+        self.base_fix_record = vec![];
+
+        self._assign_to_range_chip(region, range_chip, |cell, assigned| {
+            cells.insert(cell, assigned.cell());
+        })?;
+        #[cfg(feature = "zeroize")]
+        zeroize_fields(self.range_adv_record.iter_mut().map(|(v, _)| v));
+        self.range_adv_record = vec![];
+        self.range_fix_record = vec![];
+
+        for (left, right) in self.permutations.iter() {
+            let left = cells.get(left).ok_or(CircuitError::UnassignedCell(*left))?;
+            let right = cells
+                .get(right)
+                .ok_or(CircuitError::UnassignedCell(*right))?;
+            region.constrain_equal(*left, *right)?;
+        }
+
+        Ok(cells)
+    }
+
+    pub fn apply_segment(&mut self, segment: &RecordsSegment<N>) {
+        let base_start = segment.checkpoint.base_offset;
+        let base_end = base_start + segment.base_adv_record.len();
+        if base_end > self.base_adv_record.len() {
+            self.base_adv_record
+                .resize(base_end, [(N::zero(), false); VAR_COLUMNS]);
+            self.base_fix_record
+                .resize(base_end, [N::zero(); FIXED_COLUMNS]);
+        }
+        self.base_adv_record[base_start..base_end].copy_from_slice(&segment.base_adv_record);
+        self.base_fix_record[base_start..base_end].copy_from_slice(&segment.base_fix_record);
+        self.base_height = usize::max(self.base_height, segment.base_height);
+
+        let range_start = segment.checkpoint.range_offset;
+        let range_end = range_start + segment.range_adv_record.len();
+        if segment.range_height > self.range_adv_record.len() {
+            self.range_adv_record
+                .resize(segment.range_height, (N::zero(), false));
+            self.range_fix_record
+                .resize(segment.range_height, [N::zero(); 2]);
+        }
+        self.range_adv_record[range_start..range_end].copy_from_slice(&segment.range_adv_record);
+        self.range_fix_record[range_start..range_end].copy_from_slice(&segment.range_fix_record);
+        self.range_height = usize::max(self.range_height, segment.range_height);
+
+        for (left, right) in segment.permutations.iter() {
+            self.enable_permute(left);
+            self.enable_permute(right);
+            self.permutations.push((*left, *right));
+        }
+    }
+
     pub fn enable_permute(&mut self, cell: &Cell) {
         match cell.region {
             Chip::BaseChip => self.base_adv_record[cell.row][cell.col].1 = true,
@@ -285,8 +850,9 @@ impl<N: FieldExt> Records<N> {
         if offset >= self.base_adv_record.len() {
             let to_len = (offset + EXTEND_SIZE) & !(EXTEND_SIZE - 1);
             self.base_adv_record
-                .resize(to_len, [(None, false); VAR_COLUMNS]);
-            self.base_fix_record.resize(to_len, [None; FIXED_COLUMNS]);
+                .resize(to_len, [(N::zero(), false); VAR_COLUMNS]);
+            self.base_fix_record
+                .resize(to_len, [N::zero(); FIXED_COLUMNS]);
         }
 
         if offset >= self.base_height {
@@ -305,20 +871,23 @@ impl<N: FieldExt> Records<N> {
                 }
                 _ => {}
             }
-            self.base_fix_record[offset][i] = Some(coeff);
-            self.base_adv_record[offset][i].0 = Some(base.value());
+            self.base_fix_record[offset][i] = coeff;
+            self.base_adv_record[offset][i].0 = base.value();
+            if let Some(assigned) = self.assigned.as_mut() {
+                assigned.insert(Cell::new(Chip::BaseChip, i, offset));
+            }
         }
 
         let (mul_coeffs, next) = mul_next_coeffs;
         for (i, mul_coeff) in mul_coeffs.into_iter().enumerate() {
-            self.base_fix_record[offset][VAR_COLUMNS + i] = Some(mul_coeff);
+            self.base_fix_record[offset][VAR_COLUMNS + i] = mul_coeff;
         }
 
-        if next.is_some() {
+        if let Some(next) = next {
             self.base_fix_record[offset][VAR_COLUMNS + MUL_COLUMNS] = next;
         }
 
-        if constant.is_some() {
+        if let Some(constant) = constant {
             self.base_fix_record[offset][VAR_COLUMNS + MUL_COLUMNS + 1] = constant;
         }
     }
@@ -349,8 +918,11 @@ impl<N: FieldExt> Records<N> {
             }
             _ => {}
         }
-        self.base_fix_record[offset][i] = Some(coeff);
-        self.base_adv_record[offset][i].0 = Some(base.value());
+        self.base_fix_record[offset][i] = coeff;
+        self.base_adv_record[offset][i].0 = base.value();
+        if let Some(assigned) = self.assigned.as_mut() {
+            assigned.insert(Cell::new(Chip::BaseChip, i, offset));
+        }
     }
 
     fn ensure_range_record_size(&mut self, offset: usize) {
@@ -358,8 +930,8 @@ impl<N: FieldExt> Records<N> {
 
         if offset >= self.range_adv_record.len() {
             let to_len = (offset + EXTEND_SIZE) & !(EXTEND_SIZE - 1);
-            self.range_adv_record.resize(to_len, (None, false));
-            self.range_fix_record.resize(to_len, [None; 2]);
+            self.range_adv_record.resize(to_len, (N::zero(), false));
+            self.range_fix_record.resize(to_len, [N::zero(); 2]);
         }
 
         if offset >= self.range_height {
@@ -375,12 +947,15 @@ impl<N: FieldExt> Records<N> {
     ) -> AssignedValue<N> {
         self.ensure_range_record_size(offset + 1);
 
-        self.range_fix_record[offset][1] = Some(N::from(leading_bits));
-        self.range_adv_record[offset].0 = Some(v);
+        self.range_fix_record[offset][1] = N::from(leading_bits);
+        self.range_adv_record[offset].0 = v;
 
         AssignedValue::new(Chip::RangeChip, 0, offset, v)
     }
 
+    // `chunks.len()` comes from the range info of the limb being assigned,
+    // never from its value, so the fixed cells written only depend on the
+    // call site. The block always takes MAX_CHUNKS + 1 rows.
     pub fn assign_range_value(
         &mut self,
         offset: usize,
@@ -390,20 +965,93 @@ impl<N: FieldExt> Records<N> {
         assert!(chunks.len() as u64 <= MAX_CHUNKS);
         self.ensure_range_record_size(offset + 1 + MAX_CHUNKS as usize);
 
-        self.range_fix_record[offset][0] = Some(N::one());
-        self.range_adv_record[offset].0 = Some(v);
-
-        // a row placeholder
-        self.range_fix_record[offset + MAX_CHUNKS as usize][0] = Some(N::zero());
+        self.range_fix_record[offset][0] = N::one();
+        self.range_adv_record[offset].0 = v;
 
         for i in 0..chunks.len() - 1 {
-            self.range_fix_record[offset + 1 + i][1] = Some(N::from(COMMON_RANGE_BITS as u64));
+            self.range_fix_record[offset + 1 + i][1] = N::from(COMMON_RANGE_BITS as u64);
         }
-        self.range_fix_record[offset + chunks.len()][1] = Some(N::from(leading_bits));
+        self.range_fix_record[offset + chunks.len()][1] = N::from(leading_bits);
 
         for i in 0..chunks.len() {
-            self.range_adv_record[offset + 1 + i].0 = Some(chunks[i]);
+            self.range_adv_record[offset + 1 + i].0 = chunks[i];
         }
         AssignedValue::new(Chip::RangeChip, 0, offset, v)
     }
 }
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_compact_permutations() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    let cell = |row| Cell::new(Chip::BaseChip, 0, row);
+    let mut records = Records::<Fr>::default();
+    records.permutations = vec![
+        (cell(0), cell(1)),
+        (cell(1), cell(2)),
+        (cell(2), cell(0)),
+        (cell(1), cell(0)),
+        (cell(3), cell(4)),
+        (cell(4), cell(2)),
+        (cell(5), cell(6)),
+    ];
+    records.compact_permutations();
+
+    assert_eq!(
+        records.permutations,
+        vec![
+            (cell(0), cell(1)),
+            (cell(0), cell(2)),
+            (cell(0), cell(3)),
+            (cell(0), cell(4)),
+            (cell(5), cell(6)),
+        ]
+    );
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_new_reusing_keeps_allocations() {
+    use crate::circuit_utils::base_chip::BaseChipOps;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    let record = |ctx: &mut Context<Fr>| {
+        for i in 0..100u64 {
+            let a = ctx.assign(Fr::from(i));
+            ctx.add(&a, &a);
+        }
+    };
+
+    let mut ctx = Context::new();
+    record(&mut ctx);
+    let records = ctx.into_records().unwrap();
+    let digest = records.digest();
+    let capacity = records.base_adv_record.capacity();
+
+    let mut ctx = Context::new_reusing(records);
+    record(&mut ctx);
+    let records = ctx.into_records().unwrap();
+    assert_eq!(records.digest(), digest);
+    assert_eq!(records.base_adv_record.capacity(), capacity);
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_replay_rejects_moved_segment() {
+    use crate::circuit_utils::base_chip::BaseChipOps;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    let mut ctx = Context::<Fr>::new();
+    let checkpoint = ctx.checkpoint().unwrap();
+    ctx.assign(Fr::from(3u64));
+    let segment = ctx.segment_since(&checkpoint).unwrap();
+
+    let mut other = Context::<Fr>::new();
+    other.assign(Fr::from(5u64));
+    assert_eq!(other.replay(&segment), Err(CircuitError::SegmentMismatch));
+
+    let mut other = Context::<Fr>::new();
+    assert_eq!(other.replay(&segment), Ok(()));
+    assert_eq!(other.base_offset, ctx.base_offset);
+}
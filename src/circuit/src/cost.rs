@@ -0,0 +1,371 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::mem::size_of;
+
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::plonk::ConstraintSystem;
+
+use crate::assign::Cell;
+use crate::circuit_utils::base_chip::{FIXED_COLUMNS, VAR_COLUMNS};
+use crate::circuit_utils::range_chip::COMMON_RANGE_BITS;
+use crate::context::Records;
+use crate::error::CircuitError;
+
+// Rows at the end of every column kept free for blinding factors, with
+// some slack over what the range chip rotations need.
+const RESERVED_ROWS: usize = 16;
+
+// The tagged range table holds every class 0..=COMMON_RANGE_BITS.
+const RANGE_TABLE_ROWS: usize = (1 << (COMMON_RANGE_BITS + 1)) - 1;
+
+/// Cost of a circuit measured from its recorded witness, without keygen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitCost {
+    pub base_rows: usize,
+    pub range_rows: usize,
+    pub permutations: usize,
+    pub min_k: u32,
+    // Memory held by the witness records before assignment.
+    pub records_bytes: usize,
+}
+
+impl CircuitCost {
+    pub fn from_records<N: FieldExt>(records: &Records<N>) -> Self {
+        let rows = [records.base_height, records.range_height, RANGE_TABLE_ROWS]
+            .into_iter()
+            .max()
+            .unwrap()
+            + RESERVED_ROWS;
+
+        let records_bytes = records.base_adv_record.len() * size_of::<[(N, bool); VAR_COLUMNS]>()
+            + records.base_fix_record.len() * size_of::<[N; FIXED_COLUMNS]>()
+            + records.range_adv_record.len() * size_of::<(N, bool)>()
+            + records.range_fix_record.len() * size_of::<[N; 2]>()
+            + records.permutations.len() * size_of::<(Cell, Cell)>();
+
+        Self {
+            base_rows: records.base_height,
+            range_rows: records.range_height,
+            permutations: records.permutations.len(),
+            min_k: rows.next_power_of_two().trailing_zeros(),
+            records_bytes,
+        }
+    }
+
+    pub fn check(&self, k: u32) -> Result<(), CircuitError> {
+        if self.min_k > k {
+            return Err(CircuitError::RowOverflow {
+                rows: usize::max(self.base_rows, self.range_rows),
+                max: (1 << k) - RESERVED_ROWS,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for CircuitCost {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "base rows: {}, range rows: {}, permutations: {}, min k: {}, records: {} MiB",
+            self.base_rows,
+            self.range_rows,
+            self.permutations,
+            self.min_k,
+            self.records_bytes >> 20
+        )
+    }
+}
+
+// EIP-1108 precompile prices and the EIP-2028 calldata price.
+const EC_ADD_GAS: u64 = 150;
+const EC_MUL_GAS: u64 = 6000;
+const PAIRING_BASE_GAS: u64 = 45000;
+const PAIRING_PER_PAIR_GAS: u64 = 34000;
+const CALLDATA_BYTE_GAS: u64 = 16;
+
+// Compressed bn256 G1 points and scalars both take 32 bytes.
+const ELEMENT_BYTES: usize = 32;
+
+/// Cost of verifying one proof, counted from the constraint system for the
+/// KZG backend with the GWC multiopen argument.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VerifierCost {
+    pub proof_bytes: usize,
+    pub commitments: usize,
+    pub evaluations: usize,
+    // Points the verifier multiplies, the instance MSM included.
+    pub msm_size: usize,
+    pub pairings: usize,
+    pub instances: usize,
+    // Precompile and calldata gas only, a lower bound for an EVM verifier
+    // which also pays for field arithmetic and the transcript.
+    pub gas: u64,
+}
+
+impl VerifierCost {
+    pub fn from_cs<N: FieldExt>(cs: &ConstraintSystem<N>, instances: usize) -> Self {
+        let lookups = cs.lookups().len();
+        let permutation_columns = cs.permutation().get_columns().len();
+        // one grand product per chunk of degree - 2 columns
+        let chunk_len = cs.degree() - 2;
+        let permutation_chunks = (permutation_columns + chunk_len - 1) / chunk_len;
+
+        // advice, permuted input, permuted table and product of every lookup,
+        // permutation products, the vanishing random poly and quotient pieces
+        let commitments =
+            cs.num_advice_columns() + 3 * lookups + permutation_chunks + 1 + (cs.degree() - 1);
+
+        // sigmas, the products at x and xω, and at the last row for all but
+        // the last chunk; the lookup products at x and xω, the permuted input
+        // at x and x/ω, the permuted table at x; the vanishing random poly
+        let evaluations = cs.advice_queries().len()
+            + cs.instance_queries().len()
+            + cs.fixed_queries().len()
+            + permutation_columns
+            + 3 * permutation_chunks
+            - 1
+            + 5 * lookups
+            + 1;
+
+        let mut rotations = cs
+            .advice_queries()
+            .iter()
+            .map(|(_, r)| r.0)
+            .chain(cs.instance_queries().iter().map(|(_, r)| r.0))
+            .chain(cs.fixed_queries().iter().map(|(_, r)| r.0))
+            .collect::<BTreeSet<_>>();
+        rotations.extend([0, 1]);
+        if lookups > 0 {
+            rotations.insert(-1);
+        }
+        if permutation_chunks > 1 {
+            rotations.insert(-(cs.blinding_factors() as i32 + 1));
+        }
+        // one opening witness per distinct point
+        let opening_witnesses = rotations.len();
+
+        let proof_bytes = (commitments + opening_witnesses + evaluations) * ELEMENT_BYTES;
+
+        // proof commitments, fixed and sigma commitments from the key, and
+        // the generator the evaluations are folded on
+        let msm_size = commitments
+            + opening_witnesses
+            + cs.num_fixed_columns()
+            + permutation_columns
+            + 1
+            + instances;
+        let pairings = 2;
+
+        let calldata_bytes = proof_bytes + instances * ELEMENT_BYTES;
+        let gas = msm_size as u64 * (EC_MUL_GAS + EC_ADD_GAS)
+            + PAIRING_BASE_GAS
+            + pairings as u64 * PAIRING_PER_PAIR_GAS
+            + calldata_bytes as u64 * CALLDATA_BYTE_GAS;
+
+        Self {
+            proof_bytes,
+            commitments,
+            evaluations,
+            msm_size,
+            pairings,
+            instances,
+            gas,
+        }
+    }
+}
+
+impl Display for VerifierCost {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "proof: {} bytes, commitments: {}, evaluations: {}, msm size: {}, pairings: {}, instances: {}, gas: {}",
+            self.proof_bytes,
+            self.commitments,
+            self.evaluations,
+            self.msm_size,
+            self.pairings,
+            self.instances,
+            self.gas
+        )
+    }
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_verifier_cost_grows_with_instances() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::circuit_utils::base_chip::BaseChip;
+    use crate::circuit_utils::range_chip::RangeChip;
+
+    let mut cs = ConstraintSystem::<Fr>::default();
+    BaseChip::configure(&mut cs);
+    RangeChip::<Fr>::configure(&mut cs);
+
+    let plain = VerifierCost::from_cs(&cs, 100);
+    let hashed = VerifierCost::from_cs(&cs, 1);
+    assert_eq!(plain.proof_bytes, hashed.proof_bytes);
+    assert_eq!(plain.msm_size - hashed.msm_size, 99);
+    assert!(plain.gas > hashed.gas);
+    assert!(plain.commitments > VAR_COLUMNS + 2 * cs.lookups().len());
+}
+
+// Rows and copy constraints of the main gadgets, operand assignment
+// included, against row_counts.snapshot. A missing snapshot fails like a
+// changed one, UPDATE_ROW_COUNTS=1 writes the counts instead of checking.
+// Ignored until the first snapshot is generated with
+// `UPDATE_ROW_COUNTS=1 cargo test -p kzg_ceremony_circuit test_gadget_row_counts -- --ignored`
+// and committed next to Cargo.toml.
+#[cfg(all(
+    feature = "bn254-native",
+    feature = "bls12381-target",
+    not(feature = "small-range-table")
+))]
+#[test]
+#[ignore = "row_counts.snapshot is not committed yet"]
+fn test_gadget_row_counts_match_snapshot() {
+    use std::cell::RefCell;
+    use std::path::Path;
+    use std::rc::Rc;
+
+    use halo2_proofs::pairing::bls12_381::{Fq, G1Affine, G2Affine};
+    use halo2_proofs::pairing::bn256::Fr;
+    use num_bigint::BigUint;
+
+    use crate::circuit_utils::compress::CompressChipOps;
+    use crate::circuit_utils::ecc_chip::{EccChipBaseOps, EccChipScalarOps};
+    use crate::circuit_utils::fq2::Fq2ChipOps;
+    use crate::circuit_utils::hash_to_curve::HashToCurveG1ChipOps;
+    use crate::circuit_utils::integer_chip::IntegerChipOps;
+    use crate::circuit_utils::sha256::Sha256ChipOps;
+    use crate::context::{Context, GeneralScalarEccContext};
+    use crate::dst::{Dst, DstChipOps};
+
+    type Ctx = GeneralScalarEccContext<G1Affine, Fr>;
+
+    let g1 = G1Affine::generator();
+    let g2 = G2Affine::generator();
+    let g2 = ((g2.x.c0, g2.x.c1), (g2.y.c0, g2.y.c1));
+    let fq = |ctx: &mut Ctx, v: u64| ctx.base_integer_ctx.assign_w(&BigUint::from(v));
+
+    let gadgets: Vec<(&str, Box<dyn Fn(&mut Ctx)>)> = vec![
+        (
+            "int_add",
+            Box::new(move |ctx: &mut Ctx| {
+                let (a, b) = (fq(ctx, 3), fq(ctx, 5));
+                ctx.base_integer_ctx.int_add(&a, &b);
+            }),
+        ),
+        (
+            "int_mul",
+            Box::new(move |ctx: &mut Ctx| {
+                let (a, b) = (fq(ctx, 3), fq(ctx, 5));
+                ctx.base_integer_ctx.int_mul(&a, &b);
+            }),
+        ),
+        (
+            "int_unsafe_invert",
+            Box::new(move |ctx: &mut Ctx| {
+                let a = fq(ctx, 3);
+                ctx.base_integer_ctx.int_unsafe_invert(&a);
+            }),
+        ),
+        (
+            "fq2_mul",
+            Box::new(move |ctx: &mut Ctx| {
+                let a = ctx.fq2_assign((Fq::from(3), Fq::from(5)));
+                let b = ctx.fq2_assign((Fq::from(7), Fq::from(11)));
+                ctx.fq2_mul(&a, &b);
+            }),
+        ),
+        (
+            "ecc_add",
+            Box::new(move |ctx: &mut Ctx| {
+                let p = ctx.assign_non_zero_point(&g1);
+                let q = ctx.ecc_neg(&p);
+                let q = ctx.to_point_with_curvature(q);
+                let q = ctx.ecc_double(&q);
+                let p = ctx.to_point_with_curvature(p);
+                ctx.ecc_add(&p, &q);
+            }),
+        ),
+        (
+            "ecc_mul",
+            Box::new(move |ctx: &mut Ctx| {
+                let p = ctx.assign_non_zero_point(&g1);
+                let s = ctx.scalar_integer_ctx.assign_w(&BigUint::from(7u64));
+                ctx.ecc_mul(&p, s);
+            }),
+        ),
+        (
+            "ecc_g2_add",
+            Box::new(move |ctx: &mut Ctx| {
+                let p = ctx.assign_non_identity_constant_g2(&g2);
+                let t = ctx.to_g2_point_with_curvature(p.clone());
+                let q = ctx.ecc_g2_double(&t);
+                ctx.ecc_g2_add(&t, &q);
+            }),
+        ),
+        (
+            "ecc_g2_mul",
+            Box::new(move |ctx: &mut Ctx| {
+                let p = ctx.assign_non_identity_constant_g2(&g2);
+                let s = ctx.scalar_integer_ctx.assign_w(&BigUint::from(7u64));
+                ctx.ecc_g2_mul(&p, &s);
+            }),
+        ),
+        (
+            "ecc_g2_compress",
+            Box::new(move |ctx: &mut Ctx| {
+                let p = ctx.assign_non_identity_constant_g2(&g2);
+                ctx.ecc_g2_compress(&p);
+            }),
+        ),
+        (
+            "sha256_64_bytes",
+            Box::new(move |ctx: &mut Ctx| {
+                let mut native = ctx.native_ctx.borrow_mut();
+                let message = native.assign_constant_bytes(&[0x61; 64]);
+                native.sha256(&message);
+            }),
+        ),
+        (
+            "hash_to_curve_g1",
+            Box::new(move |ctx: &mut Ctx| {
+                ctx.hash_to_curve_g1(&[], &Dst::ethereum());
+            }),
+        ),
+    ];
+
+    let actual = gadgets
+        .iter()
+        .map(|(name, gadget)| {
+            let mut ctx = Ctx::new(Rc::new(RefCell::new(Context::new())));
+            gadget(&mut ctx);
+            let c = ctx.native_ctx.borrow().checkpoint().unwrap();
+            format!(
+                "{} {} {} {}\n",
+                name, c.base_offset, c.range_offset, c.permutations
+            )
+        })
+        .collect::<String>();
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("row_counts.snapshot");
+    if std::env::var_os("UPDATE_ROW_COUNTS").is_some() {
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "reading {} failed: {}, write it with UPDATE_ROW_COUNTS=1 and commit it",
+            path.display(),
+            e
+        )
+    });
+    assert_eq!(
+        expected, actual,
+        "gadget row counts changed, rerun with UPDATE_ROW_COUNTS=1 and commit row_counts.snapshot if intended"
+    );
+}
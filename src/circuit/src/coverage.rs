@@ -0,0 +1,159 @@
+/*
+  Constraint coverage audit: find witness cells that are assigned but that
+  no gate ties to the rest of the circuit, directly or through copies.
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::ops::{Deref, DerefMut};
+
+use halo2_proofs::arithmetic::FieldExt;
+
+use crate::assign::{Cell, Chip};
+use crate::circuit_utils::base_chip::{MUL_COLUMNS, VAR_COLUMNS};
+use crate::context::Records;
+use crate::recorder::GadgetRecorder;
+
+fn find(parent: &HashMap<Cell, Cell>, mut cell: Cell) -> Cell {
+    while let Some(p) = parent.get(&cell) {
+        cell = *p;
+    }
+    cell
+}
+
+// Base chip cells whose copy class contains no cell with a non-zero gate
+// coefficient. Range chip cells are always covered by the lookups or the
+// block sum gate, and a permuted cell outside every pair is taken to be
+// exposed as an instance. Zero cells that were never permuted are only
+// reported when the records track assigned cells, see `Records::assigned`,
+// otherwise they can't be told apart from empty ones.
+pub fn unconstrained_cells<N: FieldExt>(records: &Records<N>) -> Vec<Cell> {
+    let fixed = |row: usize, i: usize| records.base_fix_record[row][i] != N::zero();
+    let in_gate = |row: usize, col: usize| {
+        fixed(row, col)
+            || (col < MUL_COLUMNS * 2 && fixed(row, VAR_COLUMNS + col / 2))
+            || (col == VAR_COLUMNS - 1 && row > 0 && fixed(row - 1, VAR_COLUMNS + MUL_COLUMNS))
+    };
+
+    let mut parent = HashMap::new();
+    let mut paired = HashSet::new();
+    for (l, r) in records.permutations.iter() {
+        paired.insert(*l);
+        paired.insert(*r);
+        let (l, r) = (find(&parent, *l), find(&parent, *r));
+        if l != r {
+            parent.insert(l, r);
+        }
+    }
+
+    let mut assigned = vec![];
+    let mut covered = HashSet::new();
+    for (row, advs) in records.base_adv_record[..records.base_height]
+        .iter()
+        .enumerate()
+    {
+        for (col, (v, permuted)) in advs.iter().enumerate() {
+            let cell = Cell::new(Chip::BaseChip, col, row);
+            let assigned = records
+                .assigned
+                .as_ref()
+                .map_or(false, |assigned| assigned.contains(&cell));
+            if !*permuted && *v == N::zero() && !assigned {
+                continue;
+            }
+
+            if in_gate(row, col) || (*permuted && !paired.contains(&cell)) {
+                covered.insert(find(&parent, cell));
+            }
+            assigned.push(cell);
+        }
+    }
+    for (row, (_, permuted)) in records.range_adv_record[..records.range_height]
+        .iter()
+        .enumerate()
+    {
+        if *permuted {
+            covered.insert(find(&parent, Cell::new(Chip::RangeChip, 0, row)));
+        }
+    }
+
+    assigned
+        .into_iter()
+        .filter(|cell| !covered.contains(&find(&parent, *cell)))
+        .collect()
+}
+
+/// Records gadgets by name and reports unconstrained cells per invocation.
+pub struct CoverageAudit<N: FieldExt> {
+    recorder: GadgetRecorder<N>,
+}
+
+impl<N: FieldExt> Default for CoverageAudit<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// `ctx`, `gadget` and `gadget_at` are the recorder's.
+impl<N: FieldExt> Deref for CoverageAudit<N> {
+    type Target = GadgetRecorder<N>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.recorder
+    }
+}
+
+impl<N: FieldExt> DerefMut for CoverageAudit<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.recorder
+    }
+}
+
+impl<N: FieldExt> CoverageAudit<N> {
+    pub fn new() -> Self {
+        let recorder = GadgetRecorder::new();
+        recorder.ctx.borrow().records.lock().unwrap().assigned = Some(HashSet::new());
+        Self { recorder }
+    }
+
+    // Unconstrained cells grouped by the gadget invocation that assigned
+    // them, in recording order. Cells outside every gadget go to "unknown".
+    pub fn report(&self) -> Vec<(&'static str, Vec<Cell>)> {
+        let records = self.ctx.borrow().records.lock().unwrap().clone();
+        let mut report: Vec<(&'static str, Vec<Cell>)> = vec![];
+
+        for cell in unconstrained_cells(&records) {
+            let name = self.gadget_at(Chip::BaseChip, cell.row);
+            match report.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, cells)) => cells.push(cell),
+                None => report.push((name, vec![cell])),
+            }
+        }
+
+        report
+    }
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_coverage_reports_dangling_cell() {
+    use crate::circuit_utils::base_chip::BaseChipOps;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    let mut audit = CoverageAudit::<Fr>::new();
+    let (a, b) = audit.gadget("assign", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        (ctx.assign(Fr::from(3u64)), ctx.assign(Fr::from(5u64)))
+    });
+    audit.gadget("mul", |ctx| ctx.borrow_mut().mul(&a, &b));
+    assert!(audit.report().is_empty());
+
+    let dangling = audit.gadget("dangling", |ctx| ctx.borrow_mut().assign(Fr::from(7u64)));
+    assert_eq!(audit.report(), vec![("dangling", vec![dangling.cell])]);
+
+    // an unconstrained zero is found as well
+    let zero = audit.gadget("zero", |ctx| ctx.borrow_mut().assign(Fr::zero()));
+    assert_eq!(
+        audit.report(),
+        vec![("dangling", vec![dangling.cell]), ("zero", vec![zero.cell])]
+    );
+}
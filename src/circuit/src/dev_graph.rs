@@ -0,0 +1,27 @@
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::dev::CircuitLayout;
+use halo2_proofs::plonk;
+use plotters::prelude::*;
+
+use crate::cost::CircuitCost;
+
+// Renders the column layout of `circuit` to a PNG at `path`. The view is
+// clipped to the rows the records actually use, the rest of the 2^k rows
+// only hold the range table.
+pub fn render_layout<N: FieldExt, C: plonk::Circuit<N>>(
+    k: u32,
+    circuit: &C,
+    cost: &CircuitCost,
+    title: &str,
+    path: &str,
+) {
+    let root = BitMapBackend::new(path, (1024, 3072)).into_drawing_area();
+    root.fill(&WHITE).unwrap();
+    let root = root.titled(title, ("sans-serif", 60)).unwrap();
+
+    CircuitLayout::default()
+        .view_height(0..usize::max(cost.base_rows, cost.range_rows))
+        .mark_equality_cells(true)
+        .render(k, circuit, &root)
+        .unwrap();
+}
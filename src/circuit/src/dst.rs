@@ -0,0 +1,214 @@
+use halo2_proofs::arithmetic::FieldExt;
+
+use crate::assign::AssignedCondition;
+use crate::circuit_utils::base_chip::BaseChipOps;
+use crate::circuit_utils::sha256::{Sha256ChipOps, SHA256_BLOCK_BYTES};
+use crate::context::Context;
+
+pub const ETHEREUM_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+pub const DRAND_CHAINED_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+pub const DRAND_UNCHAINED_G1_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
+// Signature tags of the three schemes, with signatures on G1.
+pub const BASIC_G1_DST: &[u8] = DRAND_UNCHAINED_G1_DST;
+pub const AUG_G1_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_AUG_";
+pub const POP_G1_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_";
+pub const POP_PROOF_G1_DST: &[u8] = b"BLS_POP_BLS12381G1_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Domain separation tag consumed by `expand_message_xmd` during hash-to-curve.
+///
+/// The tag is a circuit parameter rather than a witness: it is fixed at key
+/// generation and assigned as constants, so circuits built with different
+/// tags have different verifying keys.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dst(Vec<u8>);
+
+impl Dst {
+    pub fn new(tag: &[u8]) -> Self {
+        // RFC 9380 requires longer tags to be pre-hashed.
+        assert!(!tag.is_empty() && tag.len() <= 255);
+        Self(tag.to_vec())
+    }
+
+    pub fn ethereum() -> Self {
+        Self::new(ETHEREUM_DST)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    // DST_prime = DST || I2OSP(len(DST), 1)
+    pub fn prime(&self) -> Vec<u8> {
+        let mut bytes = self.0.clone();
+        bytes.push(self.0.len() as u8);
+        bytes
+    }
+}
+
+/// The schemes of the BLS signature standard. Each fixes the signature tag
+/// and what keeps aggregates safe from rogue keys: distinct messages,
+/// messages prefixed with the pubkey, or proofs of possession of the keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlsScheme {
+    Basic,
+    Aug,
+    Pop,
+}
+
+impl BlsScheme {
+    /// Signature tag for signatures on G1.
+    pub fn dst_g1(&self) -> Dst {
+        match self {
+            BlsScheme::Basic => Dst::new(BASIC_G1_DST),
+            BlsScheme::Aug => Dst::new(AUG_G1_DST),
+            BlsScheme::Pop => Dst::new(POP_G1_DST),
+        }
+    }
+
+    /// Tag of the proofs of possession, for the scheme that needs them.
+    pub fn pop_dst_g1(&self) -> Option<Dst> {
+        match self {
+            BlsScheme::Pop => Some(Dst::new(POP_PROOF_G1_DST)),
+            _ => None,
+        }
+    }
+
+    pub fn augments_message(&self) -> bool {
+        *self == BlsScheme::Aug
+    }
+
+    pub fn requires_distinct_messages(&self) -> bool {
+        *self == BlsScheme::Basic
+    }
+
+    /// The proofs are checked when the keys are registered, not by the
+    /// signature circuits.
+    pub fn requires_proof_of_possession(&self) -> bool {
+        *self == BlsScheme::Pop
+    }
+}
+
+pub trait DstChipOps<N: FieldExt>: Sha256ChipOps<N> {
+    // DST_prime as constant bits in the hash chip layout.
+    fn assign_dst_prime_bits(&mut self, dst: &Dst) -> Vec<AssignedCondition<N>> {
+        self.assign_constant_bytes(&dst.prime())
+    }
+
+    fn assign_constant_bytes(&mut self, bytes: &[u8]) -> Vec<AssignedCondition<N>> {
+        let zero = AssignedCondition(self.assign_constant(N::zero()));
+        let one = AssignedCondition(self.assign_constant(N::one()));
+        bytes
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| if (byte >> i) & 1 == 1 { one } else { zero }))
+            .collect()
+    }
+
+    // expand_message_xmd with SHA-256 (RFC 9380, 5.3.1) of a byte aligned
+    // message in the hash chip layout, returns `len_in_bytes` bytes.
+    fn expand_message_xmd(
+        &mut self,
+        message: &[AssignedCondition<N>],
+        dst: &Dst,
+        len_in_bytes: usize,
+    ) -> Vec<AssignedCondition<N>> {
+        assert_eq!(message.len() % 8, 0);
+        let ell = (len_in_bytes + 31) / 32;
+        assert!(ell > 0 && ell <= 255 && len_in_bytes <= 65535);
+
+        let dst_prime = self.assign_dst_prime_bits(dst);
+
+        let mut msg_prime = self.assign_constant_bytes(&[0u8; SHA256_BLOCK_BYTES]);
+        msg_prime.extend_from_slice(message);
+        msg_prime.extend(self.assign_constant_bytes(&(len_in_bytes as u16).to_be_bytes()));
+        msg_prime.extend(self.assign_constant_bytes(&[0u8]));
+        msg_prime.extend_from_slice(&dst_prime);
+        let b0 = self.sha256(&msg_prime);
+
+        let mut b = b0.clone();
+        let mut uniform_bytes = vec![];
+        for i in 1..=ell {
+            let mut input = if i == 1 {
+                b0.clone()
+            } else {
+                b0.iter()
+                    .zip(b.iter())
+                    .map(|(x, y)| self.xor(x, y))
+                    .collect()
+            };
+            input.extend(self.assign_constant_bytes(&[i as u8]));
+            input.extend_from_slice(&dst_prime);
+            b = self.sha256(&input);
+            uniform_bytes.extend_from_slice(&b);
+        }
+
+        uniform_bytes.truncate(len_in_bytes * 8);
+        uniform_bytes
+    }
+
+    // Expands every message under its own tag, so signatures over different
+    // domains (fork versions, ciphersuites) can be checked in one batch.
+    fn expand_messages_xmd(
+        &mut self,
+        messages: &[(&[AssignedCondition<N>], &Dst)],
+        len_in_bytes: usize,
+    ) -> Vec<Vec<AssignedCondition<N>>> {
+        messages
+            .iter()
+            .map(|(message, dst)| self.expand_message_xmd(message, dst, len_in_bytes))
+            .collect()
+    }
+}
+
+impl<N: FieldExt> DstChipOps<N> for Context<N> {}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_expand_message_xmd_matches_rfc_vectors() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::{assign_bytes, to_bytes};
+
+    let quux = Dst::new(b"QUUX-V01-CS02-with-expander-SHA256-128");
+    let ethereum = Dst::ethereum();
+    let vectors = [
+        (
+            "abc",
+            &quux,
+            0x20,
+            "d8ccab23b5985ccea865c6c97b6e5b8350e794e603b4b97902f53a8a0d605615",
+        ),
+        (
+            "",
+            &quux,
+            0x80,
+            "af84c27ccfd45d41914fdff5df25293e221afc53d8ad2ac06d5e3e29485dadbe\
+             e0d121587713a3e0dd4d5e69e93eb7cd4f5df4cd103e188cf60cb02edc3edf18\
+             eda8576c412b18ffb658e3dd6ec849469b979d444cf7b26911a08e63cf31f9dc\
+             c541708d3491184472c2c29bb749d4286b004ceb5ee6b9a7fa5b646c993f0ced",
+        ),
+        (
+            "abc",
+            &ethereum,
+            0x40,
+            "51a9eea22e64c8f8d7fac643577aa8a56aaedded5921c3878472c3aa915e1407\
+             dd27705c7319c0d42395c8eb74196d47254551a45c5e2f520b455f7420b2deb4",
+        ),
+    ];
+
+    let mut ctx = Context::<Fr>::new();
+    let messages = vectors
+        .iter()
+        .map(|(message, _, _, _)| assign_bytes(&mut ctx, message.as_bytes()))
+        .collect::<Vec<_>>();
+
+    for ((_, dst, len, expected), message) in vectors.iter().zip(messages.iter()) {
+        let uniform_bytes = ctx
+            .expand_messages_xmd(&[(&message[..], *dst)], *len)
+            .remove(0);
+        let uniform_bytes = to_bytes(&uniform_bytes)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        assert_eq!(uniform_bytes, *expected);
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt::{Display, Formatter};
+
+use halo2_proofs::plonk;
+
+use crate::assign::Cell;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CircuitError {
+    // The `CancellationToken` of the circuit was cancelled.
+    Cancelled,
+    // Some gadget context still holds the `Rc` being unwrapped.
+    ContextStillShared,
+    // A thread panicked while holding the records lock.
+    RecordsPoisoned,
+    // A thread panicked while holding the witness cache lock.
+    WitnessCachePoisoned,
+    // A recorded segment is replayed at other offsets than it was taken at.
+    SegmentMismatch,
+    RowOverflow { rows: usize, max: usize },
+    // A permutation or instance refers to a cell that was never assigned.
+    UnassignedCell(Cell),
+}
+
+impl Display for CircuitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CircuitError::Cancelled => write!(f, "witness generation was cancelled"),
+            CircuitError::ContextStillShared => write!(f, "context is still shared"),
+            CircuitError::RecordsPoisoned => write!(f, "records lock is poisoned"),
+            CircuitError::WitnessCachePoisoned => write!(f, "witness cache lock is poisoned"),
+            CircuitError::SegmentMismatch => {
+                write!(f, "segment does not start at the current offsets")
+            }
+            CircuitError::RowOverflow { rows, max } => {
+                write!(f, "{} rows used, at most {} available", rows, max)
+            }
+            CircuitError::UnassignedCell(cell) => write!(f, "cell {:?} is not assigned", cell),
+        }
+    }
+}
+
+impl std::error::Error for CircuitError {}
+
+// halo2 errors carry no payload, the cause is lost past this point.
+impl From<CircuitError> for plonk::Error {
+    fn from(_: CircuitError) -> Self {
+        plonk::Error::Synthesis
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstanceError {
+    // The instances end before the value starting at `row` is complete.
+    UnexpectedEnd { row: usize },
+    // The cell at `row` is out of range for what is read there.
+    NonCanonical { row: usize },
+    // Not on the curve or not in the subgroup.
+    InvalidPoint { row: usize },
+    // Cells are left from `row` on.
+    TrailingData { row: usize },
+}
+
+impl Display for InstanceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstanceError::UnexpectedEnd { row } => {
+                write!(f, "instances end inside the value at row {}", row)
+            }
+            InstanceError::NonCanonical { row } => {
+                write!(f, "instance at row {} is not canonical", row)
+            }
+            InstanceError::InvalidPoint { row } => {
+                write!(f, "instances at row {} are not a valid point", row)
+            }
+            InstanceError::TrailingData { row } => {
+                write!(f, "unexpected instances from row {}", row)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InstanceError {}
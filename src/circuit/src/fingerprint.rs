@@ -0,0 +1,84 @@
+use std::fmt::{Display, Formatter};
+
+use halo2_proofs::arithmetic::{CurveAffine, FieldExt};
+use halo2_proofs::plonk::{ConstraintSystem, VerifyingKey};
+use tiny_keccak::{Hasher, Keccak};
+
+/// Bumped whenever a change to a circuit changes its constraints, so keys
+/// built from different revisions never share a fingerprint.
+// 2: constant dedup, permutation compaction, single-row range classes and
+// the G2 mul loop fix.
+pub const CIRCUIT_VERSION: u32 = 2;
+
+/// Identifies the circuit revision a key was built from. Prover and
+/// verifier agree on it iff they run the same circuit with the same key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitFingerprint {
+    pub version: u32,
+    // keccak256 of the gates, lookups, queries and permutation columns
+    pub gates: [u8; 32],
+    // keccak256 of the serialized verifying key
+    pub vk: [u8; 32],
+}
+
+fn keccak256(bytes: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(bytes);
+    let mut digest = [0u8; 32];
+    hasher.finalize(&mut digest);
+    digest
+}
+
+// The debug output lists every gate expression, column and query in
+// configuration order, so it is stable across runs and machines.
+pub fn gates_hash<N: FieldExt>(cs: &ConstraintSystem<N>) -> [u8; 32] {
+    keccak256(format!("{:?}", cs).as_bytes())
+}
+
+pub fn vk_hash<C: CurveAffine>(vk: &VerifyingKey<C>) -> [u8; 32] {
+    let mut bytes = vec![];
+    vk.write(&mut bytes)
+        .expect("writing to a vector should not fail");
+    keccak256(&bytes)
+}
+
+impl CircuitFingerprint {
+    pub fn new<C: CurveAffine>(cs: &ConstraintSystem<C::Scalar>, vk: &VerifyingKey<C>) -> Self {
+        Self {
+            version: CIRCUIT_VERSION,
+            gates: gates_hash(cs),
+            vk: vk_hash(vk),
+        }
+    }
+}
+
+impl Display for CircuitFingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "v{}-", self.version)?;
+        for b in self.gates.iter().chain(self.vk.iter()) {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_gates_hash_is_stable() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::circuit_utils::base_chip::BaseChip;
+    use crate::circuit_utils::range_chip::RangeChip;
+
+    let configure = |with_range: bool| {
+        let mut cs = ConstraintSystem::<Fr>::default();
+        BaseChip::configure(&mut cs);
+        if with_range {
+            RangeChip::<Fr>::configure(&mut cs);
+        }
+        gates_hash(&cs)
+    };
+
+    assert_eq!(configure(true), configure(true));
+    assert_ne!(configure(true), configure(false));
+}
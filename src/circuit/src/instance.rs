@@ -0,0 +1,146 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use halo2_proofs::arithmetic::FieldExt;
+use num_bigint::BigUint;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::assign::{AssignedCondition, AssignedValue};
+use crate::circuit_utils::base_chip::BaseChipOps;
+use crate::circuit_utils::keccak::KeccakChipOps;
+use crate::circuit_utils::poseidon::{PoseidonChipOps, PoseidonSpec};
+use crate::context::Context;
+use crate::utils::{bn_to_field, field_to_bn};
+
+/// How the public inputs of a circuit are exposed through the instance column.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InstanceCommitment {
+    /// Every input limb is exposed as its own instance cell.
+    Plain,
+    /// All input limbs are absorbed into an in-circuit Poseidon sponge and
+    /// only the digest is exposed.
+    Poseidon,
+    /// The inputs are hashed in-circuit as `keccak256(abi.encodePacked(uint256[]))`
+    /// and the digest is exposed as its high and low 128 bits, so an EVM
+    /// verifier can recompute it natively. Costs about 160k rows per 136
+    /// bytes of input, i.e. per 4.25 input limbs, so even at `K = 24` it only
+    /// fits circuits with a few hundred input limbs: not the G1 or G2 mul
+    /// circuits.
+    Keccak,
+}
+
+impl InstanceCommitment {
+    /// Instance cells exposed for `inputs` public input limbs.
+    pub fn instance_num(&self, inputs: usize) -> usize {
+        match self {
+            InstanceCommitment::Plain => inputs,
+            InstanceCommitment::Poseidon => 1,
+            InstanceCommitment::Keccak => 2,
+        }
+    }
+}
+
+impl Default for InstanceCommitment {
+    fn default() -> Self {
+        InstanceCommitment::Plain
+    }
+}
+
+/// Replaces the assigned public inputs with the cells that must be bound to
+/// the instance column under `commitment`.
+pub fn commit_assigned_instances<N: FieldExt>(
+    ctx: &Rc<RefCell<Context<N>>>,
+    commitment: InstanceCommitment,
+    instances: Vec<AssignedValue<N>>,
+) -> Vec<AssignedValue<N>> {
+    match commitment {
+        InstanceCommitment::Plain => instances,
+        InstanceCommitment::Poseidon => {
+            let spec = PoseidonSpec::<N>::new();
+            vec![ctx.borrow_mut().poseidon_hash(&spec, &instances)]
+        }
+        InstanceCommitment::Keccak => {
+            let mut ctx = ctx.borrow_mut();
+            let zero = AssignedCondition(ctx.assign_constant(N::zero()));
+
+            let mut message = vec![];
+            for instance in instances.iter() {
+                // canonical, or the prover could pick which of v and v + p
+                // gets hashed
+                let mut bits_le = ctx.decompose_canonical_bits_le(instance);
+                bits_le.resize(256, zero);
+                // big-endian bytes, little-endian bits in each byte
+                for byte in bits_le.chunks(8).rev() {
+                    message.extend_from_slice(byte);
+                }
+            }
+
+            let digest = ctx.keccak256(&message);
+            digest
+                .chunks(128)
+                .map(|half| {
+                    let schema = half
+                        .chunks(8)
+                        .rev()
+                        .flatten()
+                        .enumerate()
+                        .map(|(i, b)| (&b.0, bn_to_field(&(BigUint::from(1u64) << i))))
+                        .collect();
+                    ctx.sum_with_constant(schema, None)
+                })
+                .collect()
+        }
+    }
+}
+
+/// Off-circuit counterpart of `commit_assigned_instances`, to be applied on
+/// the output of `generate_instance` before proving or verifying.
+pub fn commit_instances<N: FieldExt>(commitment: InstanceCommitment, instances: Vec<N>) -> Vec<N> {
+    match commitment {
+        InstanceCommitment::Plain => instances,
+        InstanceCommitment::Poseidon => vec![PoseidonSpec::<N>::new().hash(&instances)],
+        InstanceCommitment::Keccak => {
+            let mut hasher = Keccak::v256();
+            for instance in instances.iter() {
+                let mut bytes = field_to_bn(instance).to_bytes_le();
+                bytes.resize(32, 0);
+                bytes.reverse();
+                hasher.update(&bytes);
+            }
+            let mut digest = [0u8; 32];
+            hasher.finalize(&mut digest);
+
+            digest
+                .chunks(16)
+                .map(|half| bn_to_field(&BigUint::from_bytes_be(half)))
+                .collect()
+        }
+    }
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_keccak_commitment_satisfies_constraints() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    use crate::negative_test::NegativeTest;
+
+    let instances = vec![Fr::from(1u64), -Fr::from(2u64), Fr::from(0x1234u64)];
+    let expected = commit_instances(InstanceCommitment::Keccak, instances.clone());
+
+    let mut test = NegativeTest::<Fr>::new();
+    let digest = test.gadget("keccak_commitment", |ctx| {
+        let assigned = instances
+            .iter()
+            .map(|v| ctx.borrow_mut().assign(*v))
+            .collect();
+        commit_assigned_instances(ctx, InstanceCommitment::Keccak, assigned)
+    });
+    assert_eq!(digest.iter().map(|d| d.val).collect::<Vec<_>>(), expected);
+    assert_eq!(test.run(&[]), vec![]);
+
+    // a digest bit flipped after the fact is caught
+    let tampered = (digest[0].cell, digest[0].val + Fr::one());
+    assert!(!test.run(&[tampered]).is_empty());
+}
@@ -0,0 +1,240 @@
+use halo2_proofs::pairing::bls12_381;
+use halo2_proofs::pairing::bn256::Fr;
+use num_bigint::BigUint;
+
+use crate::circuit_utils::range_chip::{COMMON_RANGE_BITS, MAX_CHUNKS};
+use crate::error::InstanceError;
+use crate::utils::{bn_to_field, field_to_bn, split_fp};
+
+/// Bits per limb of a BLS12-381 base field element in the instance column.
+pub const FQ_LIMB_BITS: usize = (COMMON_RANGE_BITS * MAX_CHUNKS) as usize;
+/// Limbs per base field element, least significant first.
+pub const FQ_LIMBS: usize = (381 + FQ_LIMB_BITS - 1) / FQ_LIMB_BITS;
+/// Message bytes per instance cell, read big-endian.
+pub const MESSAGE_CHUNK_BYTES: usize = 16;
+
+const FQ_BYTES: usize = 48;
+
+/// Lays out keys, messages and signatures the way the circuits expose them:
+/// - integers below 2^64 take one cell,
+/// - base field elements take `FQ_LIMBS` limbs of `FQ_LIMB_BITS` bits, least
+///   significant first,
+/// - G1 points are x then y, G2 points x.c0, x.c1, y.c0, y.c1,
+/// - messages are their length in bytes, then `MESSAGE_CHUNK_BYTES` byte
+///   chunks read big-endian, the last one zero padded.
+#[derive(Clone, Debug, Default)]
+pub struct InstanceBuilder {
+    instances: Vec<Fr>,
+}
+
+impl InstanceBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_u64(&mut self, v: u64) -> &mut Self {
+        self.instances.push(Fr::from(v));
+        self
+    }
+
+    pub fn push_fq(&mut self, v: &bls12_381::Fq) -> &mut Self {
+        self.instances.extend(split_fp(*v));
+        self
+    }
+
+    pub fn push_g1(&mut self, p: &bls12_381::G1Affine) -> &mut Self {
+        self.push_fq(&p.x).push_fq(&p.y)
+    }
+
+    pub fn push_g2(&mut self, p: &bls12_381::G2Affine) -> &mut Self {
+        self.push_fq(&p.x.c0)
+            .push_fq(&p.x.c1)
+            .push_fq(&p.y.c0)
+            .push_fq(&p.y.c1)
+    }
+
+    pub fn push_message(&mut self, message: &[u8]) -> &mut Self {
+        self.push_u64(message.len() as u64);
+        for chunk in message.chunks(MESSAGE_CHUNK_BYTES) {
+            let mut bytes = chunk.to_vec();
+            bytes.resize(MESSAGE_CHUNK_BYTES, 0);
+            self.instances
+                .push(bn_to_field(&BigUint::from_bytes_be(&bytes)));
+        }
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn build(self) -> Vec<Fr> {
+        self.instances
+    }
+}
+
+/// Reads back what `InstanceBuilder` wrote, rejecting any cell a builder
+/// could not have produced.
+#[derive(Clone, Debug)]
+pub struct InstanceReader<'a> {
+    instances: &'a [Fr],
+    row: usize,
+}
+
+impl<'a> InstanceReader<'a> {
+    pub fn new(instances: &'a [Fr]) -> Self {
+        Self { instances, row: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [Fr], InstanceError> {
+        let cells = self
+            .instances
+            .get(self.row..self.row + n)
+            .ok_or(InstanceError::UnexpectedEnd { row: self.row })?;
+        self.row += n;
+        Ok(cells)
+    }
+
+    fn read_bits(&mut self, bits: usize) -> Result<BigUint, InstanceError> {
+        let row = self.row;
+        let v = field_to_bn(&self.take(1)?[0]);
+        if v.bits() as usize > bits {
+            return Err(InstanceError::NonCanonical { row });
+        }
+        Ok(v)
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, InstanceError> {
+        Ok(self.read_bits(64)?.iter_u64_digits().next().unwrap_or(0))
+    }
+
+    fn read_fq_bytes(&mut self) -> Result<[u8; FQ_BYTES], InstanceError> {
+        let row = self.row;
+        let mut v = BigUint::from(0u64);
+        for i in 0..FQ_LIMBS {
+            v += self.read_bits(FQ_LIMB_BITS)? << (i * FQ_LIMB_BITS);
+        }
+
+        let modulus = field_to_bn(&-bls12_381::Fq::one()) + 1u64;
+        if v >= modulus {
+            return Err(InstanceError::NonCanonical { row });
+        }
+
+        let v = v.to_bytes_be();
+        let mut bytes = [0u8; FQ_BYTES];
+        bytes[FQ_BYTES - v.len()..].copy_from_slice(&v);
+        Ok(bytes)
+    }
+
+    pub fn read_fq(&mut self) -> Result<bls12_381::Fq, InstanceError> {
+        let mut bytes = self.read_fq_bytes()?;
+        bytes.reverse();
+        Ok(bn_to_field(&BigUint::from_bytes_le(&bytes)))
+    }
+
+    // Points go through the uncompressed encoding, whose decoding checks the
+    // curve equation and the subgroup.
+    pub fn read_g1(&mut self) -> Result<bls12_381::G1Affine, InstanceError> {
+        let row = self.row;
+        let mut bytes = [0u8; 2 * FQ_BYTES];
+        bytes[..FQ_BYTES].copy_from_slice(&self.read_fq_bytes()?);
+        bytes[FQ_BYTES..].copy_from_slice(&self.read_fq_bytes()?);
+
+        Option::from(bls12_381::G1Affine::from_uncompressed(&bytes))
+            .ok_or(InstanceError::InvalidPoint { row })
+    }
+
+    pub fn read_g2(&mut self) -> Result<bls12_381::G2Affine, InstanceError> {
+        let row = self.row;
+        let [x_c0, x_c1, y_c0, y_c1] = [
+            self.read_fq_bytes()?,
+            self.read_fq_bytes()?,
+            self.read_fq_bytes()?,
+            self.read_fq_bytes()?,
+        ];
+
+        // c1 comes first in the encoding
+        let mut bytes = [0u8; 4 * FQ_BYTES];
+        for (i, fq) in [x_c1, x_c0, y_c1, y_c0].iter().enumerate() {
+            bytes[i * FQ_BYTES..(i + 1) * FQ_BYTES].copy_from_slice(fq);
+        }
+
+        Option::from(bls12_381::G2Affine::from_uncompressed(&bytes))
+            .ok_or(InstanceError::InvalidPoint { row })
+    }
+
+    pub fn read_message(&mut self) -> Result<Vec<u8>, InstanceError> {
+        let len = self.read_u64()? as usize;
+        let chunks = (len + MESSAGE_CHUNK_BYTES - 1) / MESSAGE_CHUNK_BYTES;
+
+        let mut message = vec![];
+        for _ in 0..chunks {
+            let v = self.read_bits(MESSAGE_CHUNK_BYTES * 8)?.to_bytes_be();
+            let mut chunk = vec![0u8; MESSAGE_CHUNK_BYTES - v.len()];
+            chunk.extend(v);
+            message.extend(chunk);
+        }
+
+        // the padding must be zero
+        if message[len..].iter().any(|b| *b != 0) {
+            return Err(InstanceError::NonCanonical { row: self.row - 1 });
+        }
+        message.truncate(len);
+        Ok(message)
+    }
+
+    /// Fails unless every instance was read.
+    pub fn finish(self) -> Result<(), InstanceError> {
+        if self.row != self.instances.len() {
+            return Err(InstanceError::TrailingData { row: self.row });
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_instance_builder_round_trip() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::group::Curve;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    let pubkey = (bls12_381::G1Affine::generator() * bls12_381::Fr::random(&mut rng)).to_affine();
+    let signature =
+        (bls12_381::G2Affine::generator() * bls12_381::Fr::random(&mut rng)).to_affine();
+    let message = b"a message longer than one chunk".to_vec();
+
+    let mut builder = InstanceBuilder::new();
+    builder
+        .push_u64(7)
+        .push_g1(&pubkey)
+        .push_message(&message)
+        .push_g2(&signature);
+    assert_eq!(builder.len(), 1 + 8 + 3 + 16);
+    let instances = builder.build();
+
+    let mut reader = InstanceReader::new(&instances);
+    assert_eq!(reader.read_u64(), Ok(7));
+    assert_eq!(reader.read_g1(), Ok(pubkey));
+    assert_eq!(reader.read_message(), Ok(message));
+    assert_eq!(reader.read_g2(), Ok(signature));
+    assert_eq!(reader.finish(), Ok(()));
+
+    // a limb over FQ_LIMB_BITS
+    let mut tampered = instances.clone();
+    tampered[1] += bn_to_field::<Fr>(&(BigUint::from(1u64) << FQ_LIMB_BITS));
+    let mut reader = InstanceReader::new(&tampered);
+    reader.read_u64().unwrap();
+    assert_eq!(
+        reader.read_g1(),
+        Err(InstanceError::NonCanonical { row: 1 })
+    );
+
+    let mut reader = InstanceReader::new(&instances[..5]);
+    reader.read_u64().unwrap();
+    assert!(matches!(
+        reader.read_g1(),
+        Err(InstanceError::UnexpectedEnd { .. })
+    ));
+}
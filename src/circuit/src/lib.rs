@@ -1,21 +1,55 @@
+#[cfg(feature = "bn254-native")]
+pub mod aggregation;
 pub mod assign;
+pub mod cancel;
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
 pub mod circuit_g1_mul;
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
 pub mod circuit_g2_mul;
 pub mod circuit_utils;
+pub mod compat;
 pub mod context;
+pub mod cost;
+pub mod coverage;
+#[cfg(feature = "dev-graph")]
+pub mod dev_graph;
+pub mod dst;
+pub mod error;
+pub mod fingerprint;
+pub mod instance;
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+pub mod instance_builder;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod negative_test;
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+pub mod preset;
+pub mod progress;
+#[cfg(all(
+    test,
+    feature = "small-range-table",
+    feature = "bn254-native",
+    feature = "bls12381-target"
+))]
+mod proptests;
 pub mod range_info;
+pub mod recorder;
 pub mod utils;
+#[cfg(feature = "bls12381-target")]
+pub mod witness_cache;
 
 pub use halo2_proofs;
-use halo2_proofs::pairing::bn256;
-use halo2_proofs::pairing::bn256::Bn256;
-use halo2_proofs::poly::commitment::Params;
-
+#[cfg(feature = "zeroize")]
+pub use zeroize;
 pub const K: u32 = 24;
 
 // This is just for test.
+#[cfg(feature = "bn254-native")]
 #[test]
 fn test_untrusted_setup() {
+    use halo2_proofs::pairing::bn256;
+    use halo2_proofs::pairing::bn256::Bn256;
+    use halo2_proofs::poly::commitment::Params;
+
     let params = Params::<bn256::G1Affine>::unsafe_setup::<Bn256>(K);
 
     let mut params_buffer = vec![];
@@ -0,0 +1,207 @@
+/*
+  MockProver harness for soundness tests: record gadgets, tamper with some of
+  their witness cells and check which gadgets the failures land in.
+*/
+
+use std::ops::{Deref, DerefMut};
+
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner};
+use halo2_proofs::dev::{MockProver, VerifyFailure};
+use halo2_proofs::plonk::{self, ConstraintSystem, Error};
+
+use crate::assign::{AssignedCondition, Cell, Chip};
+use crate::circuit_utils::base_chip::{BaseChip, BaseChipConfig, BaseChipOps};
+use crate::circuit_utils::range_chip::{RangeChip, RangeChipConfig};
+use crate::context::{Context, Records};
+use crate::cost::CircuitCost;
+use crate::recorder::GadgetRecorder;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    // Display of the failing constraint, it names the gate.
+    Gate(String),
+    Lookup(usize),
+    Permutation,
+}
+
+#[derive(Clone)]
+struct RecordsCircuit<N: FieldExt> {
+    records: Records<N>,
+}
+
+impl<N: FieldExt> plonk::Circuit<N> for RecordsCircuit<N> {
+    type Config = (BaseChipConfig, RangeChipConfig);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<N>) -> Self::Config {
+        (BaseChip::configure(meta), RangeChip::<N>::configure(meta))
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<N>,
+    ) -> Result<(), Error> {
+        let base_chip = BaseChip::new(config.0);
+        let range_chip = RangeChip::<N>::new(config.1);
+        range_chip.init_table(&mut layouter)?;
+
+        layouter.assign_region(
+            || "records",
+            |mut region| {
+                self.records
+                    .assign_all(&mut region, &base_chip, &range_chip)?;
+                Ok(())
+            },
+        )
+    }
+}
+
+pub struct NegativeTest<N: FieldExt> {
+    recorder: GadgetRecorder<N>,
+}
+
+impl<N: FieldExt> Default for NegativeTest<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// `ctx`, `gadget` and `gadget_at` are the recorder's.
+impl<N: FieldExt> Deref for NegativeTest<N> {
+    type Target = GadgetRecorder<N>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.recorder
+    }
+}
+
+impl<N: FieldExt> DerefMut for NegativeTest<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.recorder
+    }
+}
+
+impl<N: FieldExt> NegativeTest<N> {
+    pub fn new() -> Self {
+        Self {
+            recorder: GadgetRecorder::new(),
+        }
+    }
+
+    // Overwrites the witness of `cells` and returns every failure together
+    // with the gadget owning the failing row.
+    pub fn run(&self, mutations: &[(Cell, N)]) -> Vec<(&'static str, FailureKind)> {
+        let mut records = self.ctx.borrow().records.lock().unwrap().clone();
+        for (cell, v) in mutations {
+            match cell.region {
+                Chip::BaseChip => records.base_adv_record[cell.row][cell.col].0 = *v,
+                Chip::RangeChip => records.range_adv_record[cell.row].0 = *v,
+            }
+        }
+
+        let k = CircuitCost::from_records(&records).min_k;
+        let circuit = RecordsCircuit { records };
+        let prover = match MockProver::run(k, &circuit, vec![vec![]]) {
+            Ok(prover) => prover,
+            Err(e) => panic!("{:#?}", e),
+        };
+
+        match prover.verify() {
+            Ok(()) => vec![],
+            Err(failures) => failures
+                .iter()
+                .filter_map(|failure| match failure {
+                    VerifyFailure::ConstraintNotSatisfied {
+                        constraint, row, ..
+                    } => {
+                        let constraint = constraint.to_string();
+                        let chip = if constraint.contains("base_gate") {
+                            Chip::BaseChip
+                        } else {
+                            Chip::RangeChip
+                        };
+                        Some((self.gadget_at(chip, *row), FailureKind::Gate(constraint)))
+                    }
+                    VerifyFailure::Lookup {
+                        lookup_index, row, ..
+                    } => Some((
+                        self.gadget_at(Chip::RangeChip, *row),
+                        FailureKind::Lookup(*lookup_index),
+                    )),
+                    VerifyFailure::Permutation { row, .. } => {
+                        let gadget = match self.gadget_at(Chip::BaseChip, *row) {
+                            "unknown" => self.gadget_at(Chip::RangeChip, *row),
+                            gadget => gadget,
+                        };
+                        Some((gadget, FailureKind::Permutation))
+                    }
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+}
+
+// Checks every recorded run has the shape of the first, the guard for
+// circuits whose layout must not depend on their inputs.
+pub fn assert_input_independent_shape<N: FieldExt>(runs: impl IntoIterator<Item = Records<N>>) {
+    let mut runs = runs.into_iter();
+    let first = runs.next().expect("at least one run");
+    for (i, other) in runs.enumerate() {
+        assert!(
+            first.shape_digest() == other.shape_digest() && first.same_shape(&other),
+            "run {} differs in shape from the first",
+            i + 1
+        );
+    }
+}
+
+// Witness bits of `bytes` in the layout of the hash chips, bits
+// little-endian inside each byte and bytes in order.
+pub fn assign_bytes<N: FieldExt>(ctx: &mut Context<N>, bytes: &[u8]) -> Vec<AssignedCondition<N>> {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1))
+        .map(|b| ctx.assign_bit(N::from(b as u64)))
+        .collect()
+}
+
+// Bytes of bits in that layout.
+pub fn to_bytes<N: FieldExt>(bits: &[AssignedCondition<N>]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|b| {
+            b.iter().enumerate().fold(0u8, |acc, (i, bit)| {
+                acc | ((bit.0.val == N::one()) as u8) << i
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "bn254-native")]
+#[test]
+fn test_negative_harness_locates_gadget() {
+    use crate::circuit_utils::base_chip::BaseChipOps;
+    use halo2_proofs::pairing::bn256::Fr;
+
+    let mut test = NegativeTest::<Fr>::new();
+    let (a, b) = test.gadget("assign", |ctx| {
+        let mut ctx = ctx.borrow_mut();
+        (ctx.assign(Fr::from(3u64)), ctx.assign(Fr::from(5u64)))
+    });
+    let c = test.gadget("mul", |ctx| ctx.borrow_mut().mul(&a, &b));
+    test.gadget("add", |ctx| ctx.borrow_mut().add(&c, &a));
+
+    assert_eq!(test.run(&[]), vec![]);
+
+    let failures = test.run(&[(c.cell, Fr::from(16u64))]);
+    assert!(!failures.is_empty());
+    assert!(failures
+        .iter()
+        .any(|(gadget, kind)| *gadget == "mul" && matches!(kind, FailureKind::Gate(_))));
+}
@@ -0,0 +1,92 @@
+use crate::circuit_utils::base_chip::{FIXED_COLUMNS, VAR_COLUMNS};
+use crate::circuit_utils::range_chip::COMMON_RANGE_BITS;
+use crate::{circuit_g1_mul, circuit_g2_mul, K};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresetCircuit {
+    G1Mul,
+    G2Mul,
+}
+
+/// Parameter combinations that are known to fit and have been benchmarked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    G1MulK24,
+    G1MulK24LowMemory,
+    G2MulK24,
+    G2MulK24LowMemory,
+}
+
+/// Everything a preset fixes. Columns and range bits are compiled in, they
+/// are reported so deployments can compare builds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PresetParams {
+    pub circuit: PresetCircuit,
+    pub k: u32,
+    pub var_columns: usize,
+    pub fixed_columns: usize,
+    pub range_bits: u64,
+    pub batch_size: usize,
+    pub low_memory: bool,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::G1MulK24,
+        Preset::G1MulK24LowMemory,
+        Preset::G2MulK24,
+        Preset::G2MulK24LowMemory,
+    ];
+
+    pub fn params(&self) -> PresetParams {
+        let (circuit, batch_size, low_memory) = match self {
+            Preset::G1MulK24 => (PresetCircuit::G1Mul, circuit_g1_mul::LENGTH, false),
+            Preset::G1MulK24LowMemory => (PresetCircuit::G1Mul, circuit_g1_mul::LENGTH, true),
+            Preset::G2MulK24 => (PresetCircuit::G2Mul, circuit_g2_mul::LENGTH, false),
+            Preset::G2MulK24LowMemory => (PresetCircuit::G2Mul, circuit_g2_mul::LENGTH, true),
+        };
+
+        PresetParams {
+            circuit,
+            k: K,
+            var_columns: VAR_COLUMNS,
+            fixed_columns: FIXED_COLUMNS,
+            range_bits: COMMON_RANGE_BITS,
+            batch_size,
+            low_memory,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::G1MulK24 => "g1-mul-k24",
+            Preset::G1MulK24LowMemory => "g1-mul-k24-low-memory",
+            Preset::G2MulK24 => "g2-mul-k24",
+            Preset::G2MulK24LowMemory => "g2-mul-k24-low-memory",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+}
+
+#[test]
+fn test_presets_fit_k() {
+    use halo2_proofs::pairing::bn256::Fr;
+
+    for preset in Preset::ALL {
+        assert_eq!(Preset::from_name(preset.name()), Some(preset));
+
+        let params = preset.params();
+        let cost = match params.circuit {
+            PresetCircuit::G1Mul => circuit_g1_mul::Circuit::<Fr>::default()
+                .with_preset(preset)
+                .estimate(),
+            PresetCircuit::G2Mul => circuit_g2_mul::Circuit::<Fr>::default()
+                .with_preset(preset)
+                .estimate(),
+        };
+        assert_eq!(cost.unwrap().check(params.k), Ok(()));
+    }
+}
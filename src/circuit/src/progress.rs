@@ -0,0 +1,63 @@
+/*
+  Progress of proof generation, so long running jobs can be followed by
+  their operators. The witness phases are reported from the circuits'
+  synthesize. Everything after the assignment (advice commitments, lookup
+  and permutation arguments, opening) runs inside halo2 and ends with
+  `ProofCreated`.
+*/
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProvingEvent {
+    WitnessStarted,
+    // Rows used in each region and copy constraints of the recorded witness.
+    WitnessRecorded {
+        base_rows: usize,
+        range_rows: usize,
+        permutations: usize,
+    },
+    // The witness is in the layouter, halo2 commits to it from here on.
+    WitnessAssigned,
+    ProofCreated {
+        bytes: usize,
+    },
+}
+
+pub trait ProgressObserver: Send + Sync {
+    fn on_event(&self, event: ProvingEvent);
+}
+
+impl<F: Fn(ProvingEvent) + Send + Sync> ProgressObserver for F {
+    fn on_event(&self, event: ProvingEvent) {
+        self(event)
+    }
+}
+
+// Clones report to the same observer, e.g. the circuit handed to halo2 and
+// the one kept by the caller.
+#[derive(Clone)]
+pub struct Progress(Arc<dyn ProgressObserver>);
+
+impl Progress {
+    pub fn new(observer: impl ProgressObserver + 'static) -> Self {
+        Self(Arc::new(observer))
+    }
+
+    pub fn report(&self, event: ProvingEvent) {
+        self.0.on_event(event)
+    }
+}
+
+impl Debug for Progress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Progress")
+    }
+}
+
+pub(crate) fn report(progress: &Option<Progress>, event: ProvingEvent) {
+    if let Some(progress) = progress {
+        progress.report(event);
+    }
+}
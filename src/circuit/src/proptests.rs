@@ -0,0 +1,130 @@
+/*
+  Property tests of the non-native field gadgets against arkworks. Every case
+  records the gadgets, compares their values with ark-bls12-381 and runs the
+  records under MockProver, so they need the small-range-table feature:
+
+    cargo test -p kzg_ceremony_circuit --features small-range-table proptests
+
+  Operands are uniform or within a few units of 0, (p - 1) / 2 and p - 1,
+  where carries and reductions are most likely to go wrong.
+*/
+
+use ark_ff::{BigInteger, Field as ArkField, PrimeField as ArkPrimeField};
+use halo2_proofs::arithmetic::Field;
+use halo2_proofs::pairing::bls12_381::{Fq, G1Affine};
+use halo2_proofs::pairing::bn256::Fr;
+use num_bigint::BigUint;
+use proptest::prelude::*;
+
+use crate::assign::AssignedFq2;
+use crate::circuit_utils::fq2::Fq2ChipOps;
+use crate::circuit_utils::integer_chip::IntegerChipOps;
+use crate::context::{GeneralScalarEccContext, IntegerContext};
+use crate::negative_test::NegativeTest;
+use crate::utils::{bn_to_field, field_to_bn};
+
+type ArkFq = ark_bls12_381::Fq;
+type ArkFq2 = ark_bls12_381::Fq2;
+
+const CASES: u32 = 8;
+
+fn to_ark(x: &Fq) -> ArkFq {
+    ArkFq::from_le_bytes_mod_order(&field_to_bn(x).to_bytes_le())
+}
+
+fn from_ark(x: &ArkFq) -> Fq {
+    bn_to_field(&BigUint::from_bytes_le(&x.into_repr().to_bytes_le()))
+}
+
+fn fq() -> impl Strategy<Value = Fq> {
+    let p_minus_one = field_to_bn(&-Fq::one());
+    let p = &p_minus_one + 1u64;
+    let half = &p_minus_one >> 1;
+    let near = |base: BigUint| (0..8u64).prop_map(move |k| bn_to_field(&(&base - k)));
+
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 64)
+            .prop_map(move |b| bn_to_field(&(BigUint::from_bytes_le(&b) % &p))),
+        (0..8u64).prop_map(Fq::from),
+        near(half),
+        near(p_minus_one),
+    ]
+}
+
+fn fq2_values(ctx: &GeneralScalarEccContext<G1Affine, Fr>, x: &AssignedFq2<Fq, Fr>) -> ArkFq2 {
+    ArkFq2::new(
+        to_ark(&ctx.base_integer_ctx.get_w(&x.0)),
+        to_ark(&ctx.base_integer_ctx.get_w(&x.1)),
+    )
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(CASES))]
+
+    #[test]
+    fn integer_chip_matches_arkworks(a in fq(), b in fq()) {
+        let mut test = NegativeTest::<Fr>::new();
+        let [sum, diff, prod, neg, square] = test.gadget("int_ops", |ctx| {
+            let mut ctx = IntegerContext::<Fq, Fr>::new(ctx.clone());
+            let x = ctx.assign_w(&field_to_bn(&a));
+            let y = ctx.assign_w(&field_to_bn(&b));
+            let sum = ctx.int_add(&x, &y);
+            let diff = ctx.int_sub(&x, &y);
+            let prod = ctx.int_mul(&x, &y);
+            let neg = ctx.int_neg(&x);
+            let square = ctx.int_square(&x);
+            [sum, diff, prod, neg, square].map(|v| to_ark(&ctx.get_w(&v)))
+        });
+
+        let (x, y) = (to_ark(&a), to_ark(&b));
+        prop_assert_eq!(sum, x + y);
+        prop_assert_eq!(diff, x - y);
+        prop_assert_eq!(prod, x * y);
+        prop_assert_eq!(neg, -x);
+        prop_assert_eq!(square, x.square());
+
+        if b != Fq::zero() {
+            let inv = test.gadget("int_unsafe_invert", |ctx| {
+                let mut ctx = IntegerContext::<Fq, Fr>::new(ctx.clone());
+                let y = ctx.assign_w(&field_to_bn(&b));
+                let inv = ctx.int_unsafe_invert(&y);
+                to_ark(&ctx.get_w(&inv))
+            });
+            prop_assert_eq!(Some(inv), y.inverse());
+        }
+
+        prop_assert_eq!(test.run(&[]), vec![]);
+    }
+
+    #[test]
+    fn fq2_chip_matches_arkworks(a in (fq(), fq()), b in (fq(), fq())) {
+        let mut test = NegativeTest::<Fr>::new();
+        let (values, inv) = test.gadget("fq2_ops", |ctx| {
+            let mut ctx = GeneralScalarEccContext::<G1Affine, Fr>::new(ctx.clone());
+            let x = ctx.fq2_assign(a);
+            let y = ctx.fq2_assign(b);
+            let values = [
+                ctx.fq2_add(&x, &y),
+                ctx.fq2_sub(&x, &y),
+                ctx.fq2_mul(&x, &y),
+                ctx.fq2_square(&x),
+                ctx.fq2_conjugate(&x),
+            ]
+            .map(|v| fq2_values(&ctx, &v));
+            let (is_zero, inv) = ctx.fq2_invert(&y);
+            (values, (is_zero.0.val == Fr::one(), fq2_values(&ctx, &inv)))
+        });
+
+        let x = ArkFq2::new(to_ark(&a.0), to_ark(&a.1));
+        let y = ArkFq2::new(to_ark(&b.0), to_ark(&b.1));
+        let mut conjugate = x;
+        conjugate.conjugate();
+        prop_assert_eq!(values, [x + y, x - y, x * y, x.square(), conjugate]);
+        match y.inverse() {
+            Some(y_inv) => prop_assert_eq!(inv, (false, y_inv)),
+            None => prop_assert!(inv.0),
+        }
+
+        prop_assert_eq!(test.run(&[]), vec![]);
+    }
+}
@@ -12,10 +12,23 @@ use crate::circuit_utils::range_chip::COMMON_RANGE_BITS;
 use crate::circuit_utils::range_chip::MAX_CHUNKS;
 use crate::utils::{bn_to_field, field_to_bn};
 
+// How the limb products of a non-native multiplication are laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MulSchedule {
+    // One product per pair of limbs, limbs^2 in total.
+    Schoolbook,
+    // a_i * b_j + a_j * b_i = (a_i + a_j) * (b_i + b_j) - a_i * b_i - a_j * b_j,
+    // limbs * (limbs + 1) / 2 products for extra additions.
+    Karatsuba,
+}
+
 #[derive(Debug, Clone)]
 pub struct RangeInfo<W: BaseExt, N: FieldExt> {
     pub limbs: u64,
     pub limb_bits: u64,
+    // Chunk layout of the non-leading limbs.
+    pub limb_chunks: u64,
+    pub limb_leading_bits: u64,
 
     pub w_ceil_leading_chunks: u64,
     pub n_floor_leading_chunks: u64,
@@ -48,41 +61,66 @@ pub struct RangeInfo<W: BaseExt, N: FieldExt> {
     pub reduce_check_limbs: u64,
     pub mul_check_limbs: u64,
     pub w_modulus_of_ceil_times: Vec<Option<Vec<N>>>,
+    pub mul_schedule: MulSchedule,
 
     pub _phantom: PhantomData<W>,
 }
 
 impl<W: BaseExt, N: FieldExt> RangeInfo<W, N> {
+    // Leading chunk bits and chunk count of a limb holding `bits` bits.
     fn bits_to_leading_bits_and_chunks(bits: u64, common_bits: u64) -> (u64, u64) {
-        let leading_chunk_bits = bits % common_bits;
-        if leading_chunk_bits == 0 {
-            (common_bits, bits / common_bits % MAX_CHUNKS)
-        } else {
-            (leading_chunk_bits, bits / common_bits % MAX_CHUNKS + 1)
-        }
+        assert!(bits > 0);
+        let chunks = (bits + common_bits - 1) / common_bits;
+        assert!(chunks <= MAX_CHUNKS);
+        (bits - (chunks - 1) * common_bits, chunks)
     }
 
     pub fn new(common_bits: u64, overflow_bits: u64) -> Self {
+        Self::new_with_limb_bits(common_bits, common_bits * MAX_CHUNKS, overflow_bits)
+    }
+
+    // Limbs of `limb_bits` bits, e.g. 4 x 99 or 5 x 79 for a 381-bit field.
+    // A limb is still range checked in one block, so it can't exceed
+    // `MAX_CHUNKS` chunks of `common_bits`, and the limbs must hold the
+    // quotient d of a multiplication, not only w.
+    pub fn new_with_limb_bits(common_bits: u64, limb_bits: u64, overflow_bits: u64) -> Self {
+        assert!(limb_bits <= common_bits * MAX_CHUNKS);
+        let (limb_leading_bits, limb_chunks) =
+            Self::bits_to_leading_bits_and_chunks(limb_bits, common_bits);
+
         let w_max = field_to_bn(&-W::one());
         let w_ceil_bits = w_max.bits();
         assert!(BigUint::from(1u64) << w_ceil_bits > w_max);
         assert!(BigUint::from(1u64) << (w_ceil_bits - 1) < w_max);
-        let (w_ceil_leading_bits, w_ceil_leading_chunks) =
-            Self::bits_to_leading_bits_and_chunks(w_ceil_bits, common_bits);
+        let limbs = (w_ceil_bits + limb_bits - 1) / limb_bits;
+        let (w_ceil_leading_bits, w_ceil_leading_chunks) = Self::bits_to_leading_bits_and_chunks(
+            w_ceil_bits - (limbs - 1) * limb_bits,
+            common_bits,
+        );
 
         let n_max = field_to_bn(&-N::one());
         let n_floor_bits = n_max.bits() - 1;
         assert!(BigUint::from(1u64) << n_floor_bits < n_max);
         assert!(BigUint::from(1u64) << (n_floor_bits + 1) >= n_max);
-        let (n_floor_leading_bits, n_floor_leading_chunks) =
-            Self::bits_to_leading_bits_and_chunks(n_floor_bits, common_bits);
+        let n_floor_limbs = (n_floor_bits + limb_bits - 1) / limb_bits;
+        let (n_floor_leading_bits, n_floor_leading_chunks) = Self::bits_to_leading_bits_and_chunks(
+            n_floor_bits - (n_floor_limbs - 1) * limb_bits,
+            common_bits,
+        );
 
+        // d is assigned on as many limbs as w, its leading limb takes the rest.
+        // The limb products of the mul check are bounded by limb_modulus^2,
+        // so that leading limb can't be wider than the others.
         let d_bits = Self::d_bits(overflow_bits);
+        assert!(
+            d_bits <= limbs * limb_bits,
+            "{} limbs of {} bits can't hold the {} bits of d",
+            limbs,
+            limb_bits,
+            d_bits
+        );
         let (d_leading_bits, d_leading_chunks) =
-            Self::bits_to_leading_bits_and_chunks(d_bits, common_bits);
-
-        let limb_bits = common_bits * MAX_CHUNKS;
-        let limbs = (w_ceil_bits + limb_bits - 1) / limb_bits;
+            Self::bits_to_leading_bits_and_chunks(d_bits - (limbs - 1) * limb_bits, common_bits);
 
         let max_d = BigUint::from(1u64) << d_bits;
         let limb_mask = (BigUint::from(1u64) << limb_bits) - 1u64;
@@ -133,6 +171,8 @@ impl<W: BaseExt, N: FieldExt> RangeInfo<W, N> {
 
             limbs,
             limb_bits,
+            limb_chunks,
+            limb_leading_bits,
             w_ceil_leading_chunks,
             n_floor_leading_chunks,
             d_leading_chunks,
@@ -146,6 +186,7 @@ impl<W: BaseExt, N: FieldExt> RangeInfo<W, N> {
             reduce_check_limbs: (w_ceil_bits + overflow_bits - n_floor_bits + limb_bits - 1)
                 / limb_bits,
             w_modulus_of_ceil_times,
+            mul_schedule: MulSchedule::Schoolbook,
 
             _phantom: PhantomData,
         };
@@ -158,6 +199,13 @@ impl<W: BaseExt, N: FieldExt> RangeInfo<W, N> {
         res
     }
 
+    pub fn with_mul_schedule(self, mul_schedule: MulSchedule) -> Self {
+        Self {
+            mul_schedule,
+            ..self
+        }
+    }
+
     fn pre_check(&self) {
         let common_modulus = 1u64 << COMMON_RANGE_BITS;
 
@@ -291,6 +339,7 @@ impl<W: BaseExt, N: FieldExt> RangeInfo<W, N> {
     }
 }
 
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
 #[test]
 fn test_range_info() {
     {
@@ -315,4 +364,61 @@ fn test_range_info() {
         let info = RangeInfo::<Bls12_381_Fq, Fr>::new(18, 6);
         println!("info {:?}", info);
     }
+
+    {
+        use halo2_proofs::pairing::bls12_381::Fr as Bls12_381_Fr;
+        use halo2_proofs::pairing::bn256::Fq;
+
+        RangeInfo::<Fq, Bls12_381_Fr>::new(18, 6);
+    }
+}
+
+#[cfg(all(
+    feature = "bn254-native",
+    feature = "bls12381-target",
+    not(feature = "small-range-table")
+))]
+#[test]
+fn test_range_info_with_limb_bits() {
+    use halo2_proofs::arithmetic::Field;
+    use halo2_proofs::pairing::bls12_381::Fq as Bls12_381_Fq;
+    use halo2_proofs::pairing::bn256::Fr;
+    use rand::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+    use std::panic;
+
+    use crate::circuit_utils::integer_chip::IntegerChipOps;
+    use crate::context::IntegerContext;
+    use crate::negative_test::NegativeTest;
+
+    let default = RangeInfo::<Bls12_381_Fq, Fr>::new(18, 6);
+    assert_eq!(default.limbs, 4);
+    assert_eq!((default.limb_chunks, default.limb_leading_bits), (6, 18));
+
+    let mut rng = XorShiftRng::seed_from_u64(0x0102030405060708);
+    for (limb_bits, limbs, chunks) in [(99, 4, 6), (79, 5, 5)] {
+        let info = RangeInfo::<Bls12_381_Fq, Fr>::new_with_limb_bits(18, limb_bits, 6);
+        assert_eq!(info.limbs, limbs);
+        assert_eq!(info.limb_chunks, chunks);
+
+        let test = NegativeTest::<Fr>::new();
+        let mut ctx =
+            IntegerContext::<Bls12_381_Fq, Fr>::new_with_limb_bits(test.ctx.clone(), limb_bits, 6);
+        let a = Bls12_381_Fq::random(&mut rng);
+        let b = Bls12_381_Fq::random(&mut rng);
+        let assigned_a = ctx.assign_w(&field_to_bn(&a));
+        let assigned_b = ctx.assign_w(&field_to_bn(&b));
+        let c = ctx.int_mul(&assigned_a, &assigned_b);
+        assert_eq!(c.limbs_le.len() as u64, limbs);
+        assert_eq!(ctx.get_w(&c), a * b);
+        assert_eq!(test.run(&[]), vec![]);
+    }
+
+    // 4 x 96 and 5 x 77 cover w but leave d's leading limb wider than a limb
+    for limb_bits in [96, 77] {
+        let res = panic::catch_unwind(|| {
+            RangeInfo::<Bls12_381_Fq, Fr>::new_with_limb_bits(18, limb_bits, 6)
+        });
+        assert!(res.is_err());
+    }
 }
@@ -0,0 +1,68 @@
+/*
+  Records gadget invocations on a shared context together with the rows they
+  assigned, so findings on rows can be traced back to the gadget at fault.
+*/
+
+use std::cell::RefCell;
+use std::ops::Range;
+use std::rc::Rc;
+
+use halo2_proofs::arithmetic::FieldExt;
+
+use crate::assign::Chip;
+use crate::context::Context;
+
+pub struct GadgetRecorder<N: FieldExt> {
+    pub ctx: Rc<RefCell<Context<N>>>,
+    // (name, base rows, range rows) of every recorded gadget
+    gadgets: Vec<(&'static str, Range<usize>, Range<usize>)>,
+}
+
+impl<N: FieldExt> Default for GadgetRecorder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N: FieldExt> GadgetRecorder<N> {
+    pub fn new() -> Self {
+        Self {
+            ctx: Rc::new(RefCell::new(Context::new())),
+            gadgets: vec![],
+        }
+    }
+
+    pub fn gadget<T>(
+        &mut self,
+        name: &'static str,
+        f: impl FnOnce(&Rc<RefCell<Context<N>>>) -> T,
+    ) -> T {
+        let (base_start, range_start) = {
+            let ctx = self.ctx.borrow();
+            (ctx.base_offset, ctx.range_offset)
+        };
+
+        let res = f(&self.ctx);
+
+        let ctx = self.ctx.borrow();
+        self.gadgets.push((
+            name,
+            base_start..ctx.base_offset,
+            range_start..ctx.range_offset,
+        ));
+        res
+    }
+
+    // Name of the gadget that assigned `row` of `chip`, "unknown" for rows
+    // outside every gadget.
+    pub fn gadget_at(&self, chip: Chip, row: usize) -> &'static str {
+        self.gadgets
+            .iter()
+            .find(|(_, base, range)| match chip {
+                Chip::BaseChip => base.contains(&row),
+                Chip::RangeChip => range.contains(&row),
+            })
+            .map(|(name, _, _)| *name)
+            .unwrap_or("unknown")
+    }
+}
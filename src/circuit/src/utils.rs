@@ -2,9 +2,12 @@
   The implementation is ported from https://github.com/DelphinusLab/halo2ecc-s
 */
 
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
 use crate::circuit_utils::range_chip::{COMMON_RANGE_BITS, MAX_CHUNKS};
 use halo2_proofs::arithmetic::BaseExt;
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
 use halo2_proofs::pairing::bls12_381;
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
 use halo2_proofs::pairing::bn256::Fr;
 use num_bigint::BigUint;
 
@@ -23,16 +26,49 @@ pub fn bn_to_field<F: BaseExt>(bn: &BigUint) -> F {
     F::read(&mut bytes).unwrap()
 }
 
-fn split_fp(el: bls12_381::Fq) -> Vec<Fr> {
+// Field elements don't implement `Zeroize`, so wipe them the way it does:
+// volatile writes the compiler can't elide, then a fence.
+#[cfg(feature = "zeroize")]
+pub fn zeroize_fields<'a, F: BaseExt + 'a>(values: impl IntoIterator<Item = &'a mut F>) {
+    for v in values {
+        unsafe { std::ptr::write_volatile(v, F::zero()) };
+    }
+    std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+}
+
+// Field elements wiped with `zeroize_fields` when dropped, unwinding
+// included.
+#[cfg(feature = "zeroize")]
+pub struct ZeroizingFields<F: BaseExt>(pub Vec<F>);
+
+#[cfg(feature = "zeroize")]
+impl<F: BaseExt> Drop for ZeroizingFields<F> {
+    fn drop(&mut self) {
+        zeroize_fields(self.0.iter_mut());
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<F: BaseExt> std::ops::Deref for ZeroizingFields<F> {
+    type Target = Vec<F>;
+
+    fn deref(&self) -> &Vec<F> {
+        &self.0
+    }
+}
+
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
+pub(crate) fn split_fp(el: bls12_381::Fq) -> Vec<Fr> {
     let bits = COMMON_RANGE_BITS * MAX_CHUNKS;
     let bit_mask = (BigUint::from(1u64) << bits) - 1u64;
 
     let bu = field_to_bn(&el);
-    (0..4)
+    (0..(381 + bits - 1) / bits)
         .map(|i| bn_to_field::<Fr>(&((&bu >> (i * bits)) & &bit_mask)))
         .collect()
 }
 
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
 pub fn split_g1_point(point: &bls12_381::G1Affine) -> Vec<Fr> {
     let mut limbs = vec![];
     for el in vec![point.x, point.y].iter() {
@@ -42,6 +78,7 @@ pub fn split_g1_point(point: &bls12_381::G1Affine) -> Vec<Fr> {
     limbs
 }
 
+#[cfg(all(feature = "bn254-native", feature = "bls12381-target"))]
 pub fn split_g2_point(point: &bls12_381::G2Affine) -> Vec<Fr> {
     let mut limbs = vec![];
     for el in vec![point.x.c0, point.x.c1, point.y.c0, point.y.c1].iter() {
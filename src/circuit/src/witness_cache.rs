@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use halo2_proofs::arithmetic::{Field, FieldExt};
+use halo2_proofs::pairing::bls12_381;
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::assign::{AssignedFq2, AssignedG2Affine, AssignedInteger};
+use crate::circuit_utils::ecc_chip::{EccChipBaseOps, EccChipScalarOps};
+use crate::circuit_utils::fq2::Fq2ChipOps;
+use crate::circuit_utils::integer_chip::IntegerChipOps;
+use crate::context::{GeneralScalarEccContext, RecordsSegment};
+use crate::error::CircuitError;
+use crate::utils::field_to_bn;
+#[cfg(feature = "zeroize")]
+use crate::utils::zeroize_fields;
+
+#[derive(Debug, Clone)]
+pub struct PubkeyWitness<N: FieldExt> {
+    pub b: AssignedFq2<bls12_381::Fq, N>,
+    pub pubkey: AssignedG2Affine<bls12_381::G1Affine, N>,
+    pub tau: AssignedInteger<bls12_381::Fr, N>,
+}
+
+/// Caches the rows produced by `assign_pubkey_with_tau`, keyed by the pubkey
+/// and a digest of tau, which the rows also hold.
+///
+/// Every proof of one contribution re-checks `pubkey = tau * G2`, which is the
+/// same sub-witness at the same offsets each time, so it can be replayed
+/// instead of recomputed. A cached entry is only replayed when the context is
+/// at the offsets it was recorded at.
+#[derive(Debug, Default)]
+pub struct WitnessCache<N: FieldExt> {
+    entries: Mutex<CacheEntries<N>>,
+}
+
+type CacheEntries<N> = HashMap<Vec<u8>, (RecordsSegment<N>, PubkeyWitness<N>)>;
+
+impl<N: FieldExt> WitnessCache<N> {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock_entries(&self) -> Result<MutexGuard<'_, CacheEntries<N>>, CircuitError> {
+        self.entries
+            .lock()
+            .map_err(|_| CircuitError::WitnessCachePoisoned)
+    }
+
+    pub fn len(&self) -> Result<usize, CircuitError> {
+        Ok(self.lock_entries()?.len())
+    }
+
+    pub fn clear(&self) -> Result<(), CircuitError> {
+        self.lock_entries()?.clear();
+        Ok(())
+    }
+}
+
+// Cached segments hold the advice of `tau * G2`, wipe them once the
+// contribution is proven.
+#[cfg(feature = "zeroize")]
+impl<N: FieldExt> zeroize::Zeroize for WitnessCache<N> {
+    fn zeroize(&mut self) {
+        let entries = self
+            .entries
+            .get_mut()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        for (segment, witness) in entries.values_mut() {
+            zeroize::Zeroize::zeroize(segment);
+            zeroize_fields(
+                witness
+                    .tau
+                    .limbs_le
+                    .iter_mut()
+                    .chain([&mut witness.tau.native])
+                    .map(|v| &mut v.val),
+            );
+        }
+        entries.clear();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<N: FieldExt> Drop for WitnessCache<N> {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+// Assign the pubkey and tau, and constrain `pubkey = tau * G2`.
+pub fn assign_pubkey_with_tau<N: FieldExt>(
+    ctx: &mut GeneralScalarEccContext<bls12_381::G1Affine, N>,
+    cache: Option<&WitnessCache<N>>,
+    pubkey: &bls12_381::G2Affine,
+    tau: &bls12_381::Fr,
+) -> Result<PubkeyWitness<N>, CircuitError> {
+    let key = {
+        let mut digest = [0u8; 32];
+        let mut hasher = Keccak::v256();
+        hasher.update(&field_to_bn(tau).to_bytes_le());
+        hasher.finalize(&mut digest);
+        [&pubkey.to_compressed()[..], &digest].concat()
+    };
+
+    if let Some(cache) = cache {
+        let entries = cache.lock_entries()?;
+        if let Some((segment, witness)) = entries.get(&key) {
+            if ctx.native_ctx.borrow().can_replay(segment)? {
+                ctx.native_ctx.borrow_mut().replay(segment)?;
+                return Ok(witness.clone());
+            }
+        }
+    }
+
+    let checkpoint = ctx.native_ctx.borrow().checkpoint()?;
+
+    let four = bls12_381::Fq::one().double().double();
+    let b = ctx.fq2_assign_constant((four, four));
+    let pubkey_assigned = ctx.assign_non_identity_g2(
+        &((pubkey.x.c0, pubkey.x.c1), (pubkey.y.c0, pubkey.y.c1)),
+        b.clone(),
+    );
+
+    let tau = ctx.scalar_integer_ctx.assign_w(&field_to_bn(tau));
+    let generator = ctx.assign_non_identity_constant_g2({
+        let g = bls12_381::G2Affine::generator();
+        &((g.x.c0, g.x.c1), (g.y.c0, g.y.c1))
+    });
+
+    let expected_pubkey = ctx.ecc_g2_mul(&generator, &tau);
+    ctx.ecc_assert_g2_equal(&pubkey_assigned, &expected_pubkey);
+
+    let witness = PubkeyWitness {
+        b,
+        pubkey: pubkey_assigned,
+        tau,
+    };
+
+    if let Some(cache) = cache {
+        let segment = ctx.native_ctx.borrow().segment_since(&checkpoint)?;
+        cache
+            .lock_entries()?
+            .insert(key, (segment, witness.clone()));
+    }
+
+    Ok(witness)
+}
@@ -0,0 +1,279 @@
+use kzg_ceremony_prover::params::map_params;
+use kzg_ceremony_prover::serialization::{scalar_from_string, BatchContributionJson, Decode};
+use kzg_ceremony_prover::{
+    prove_with_keys, read_params, verify_proofs_with_keys, ProvingKeys, VerifyingKeys,
+};
+use serde::Deserialize;
+use std::env;
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+const USAGE: &str = "Usage:
+    bls-halo2-server <params> <addr> [<workers> [<queue>]]
+
+Serves over HTTP on `addr`, e.g. 127.0.0.1:8080:
+    POST /prove    {\"oldContributions\", \"newContributions\", \"taus\"}
+    POST /verify   {\"oldContributions\", \"newContributions\", \"proof\"}
+    GET  /metrics  counters in the Prometheus text format
+The contributions are in the format of the CLI, `proof` is the bundles JSON
+returned by /prove, or the bare proofs of earlier releases. `workers` requests run at once (default 1, each proof
+already uses every core) and up to `queue` more wait (default 16), further
+requests get 503. Bodies over 64 MiB get 413. The keys are built once, before
+the server starts listening.";
+
+// Far above the contributions of the Ethereum ceremony with their proofs.
+const MAX_BODY_BYTES: usize = 64 << 20;
+
+#[derive(Deserialize)]
+struct ProveRequest {
+    #[serde(rename = "oldContributions")]
+    old_contributions: BatchContributionJson,
+    #[serde(rename = "newContributions")]
+    new_contributions: BatchContributionJson,
+    taus: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct VerifyRequest {
+    #[serde(rename = "oldContributions")]
+    old_contributions: BatchContributionJson,
+    #[serde(rename = "newContributions")]
+    new_contributions: BatchContributionJson,
+    proof: serde_json::Value,
+}
+
+#[derive(Default)]
+struct Metrics {
+    queued: AtomicU64,
+    running: AtomicU64,
+    rejected: AtomicU64,
+    proved: AtomicU64,
+    prove_failed: AtomicU64,
+    prove_millis: AtomicU64,
+    verified: AtomicU64,
+    verify_failed: AtomicU64,
+}
+
+impl Metrics {
+    fn render(&self) -> String {
+        let metrics = [
+            ("queued_requests", &self.queued),
+            ("running_requests", &self.running),
+            ("rejected_requests_total", &self.rejected),
+            ("proofs_total", &self.proved),
+            ("proofs_failed_total", &self.prove_failed),
+            ("proving_milliseconds_total", &self.prove_millis),
+            ("verifications_total", &self.verified),
+            ("verifications_failed_total", &self.verify_failed),
+        ];
+        metrics
+            .iter()
+            .map(|(name, v)| format!("bls_halo2_{} {}\n", name, v.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+fn respond(request: Request, status: u16, body: String) {
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    let response = Response::from_string(body)
+        .with_status_code(status)
+        .with_header(content_type);
+    // the client may be gone, nothing to do then
+    let _ = request.respond(response);
+}
+
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+// Runs `f`, the prover panics on inputs it can't handle.
+fn catch<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|e| {
+        e.downcast_ref::<String>()
+            .cloned()
+            .or_else(|| e.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "unknown failure".to_string())
+    })
+}
+
+fn handle(
+    mut request: Request,
+    proving_keys: &ProvingKeys,
+    verifying_keys: &VerifyingKeys,
+    metrics: &Metrics,
+) {
+    // the declared length is checked before queuing, this bounds chunked bodies
+    let mut body = String::new();
+    let res = request
+        .as_reader()
+        .take(MAX_BODY_BYTES as u64 + 1)
+        .read_to_string(&mut body);
+    if let Err(e) = res {
+        return respond(request, 400, error_body(&e.to_string()));
+    }
+    if body.len() > MAX_BODY_BYTES {
+        return respond(request, 413, error_body("request body is too large"));
+    }
+
+    match request.url() {
+        "/prove" => {
+            let req = serde_json::from_str::<ProveRequest>(&body);
+            #[cfg(feature = "zeroize")]
+            kzg_ceremony_circuit::zeroize::Zeroize::zeroize(&mut body);
+            #[allow(unused_mut)]
+            let mut req = match req {
+                Ok(req) => req,
+                Err(e) => return respond(request, 400, error_body(&e.to_string())),
+            };
+
+            let start = Instant::now();
+            let proof = catch(|| {
+                let old = req.old_contributions.decode();
+                let new = req.new_contributions.decode();
+                let taus = req.taus.iter().map(scalar_from_string).collect::<Vec<_>>();
+                // wiped when dropped, also when proving panics
+                #[cfg(feature = "zeroize")]
+                let taus = kzg_ceremony_circuit::utils::ZeroizingFields(taus);
+                prove_with_keys(&old, &new, &taus, proving_keys)
+            });
+            #[cfg(feature = "zeroize")]
+            kzg_ceremony_circuit::zeroize::Zeroize::zeroize(&mut req.taus);
+            metrics
+                .prove_millis
+                .fetch_add(start.elapsed().as_millis() as u64, Ordering::Relaxed);
+
+            match proof {
+                Ok(proof) => {
+                    metrics.proved.fetch_add(1, Ordering::Relaxed);
+                    let proof = serde_json::to_string(&proof).expect("Serialize proof failed");
+                    respond(request, 200, proof)
+                }
+                Err(e) => {
+                    metrics.prove_failed.fetch_add(1, Ordering::Relaxed);
+                    respond(request, 422, error_body(&e))
+                }
+            }
+        }
+        "/verify" => {
+            let req: VerifyRequest = match serde_json::from_str(&body) {
+                Ok(req) => req,
+                Err(e) => return respond(request, 400, error_body(&e.to_string())),
+            };
+
+            let res = catch(|| {
+                let old = req.old_contributions.decode();
+                let new = req.new_contributions.decode();
+                verify_proofs_with_keys(&old, &new, req.proof.to_string(), verifying_keys)
+            });
+
+            match res {
+                Ok(()) => {
+                    metrics.verified.fetch_add(1, Ordering::Relaxed);
+                    respond(
+                        request,
+                        200,
+                        serde_json::json!({ "valid": true }).to_string(),
+                    )
+                }
+                Err(e) => {
+                    metrics.verify_failed.fetch_add(1, Ordering::Relaxed);
+                    let body = serde_json::json!({ "valid": false, "error": e });
+                    respond(request, 422, body.to_string())
+                }
+            }
+        }
+        _ => unreachable!("only /prove and /verify are queued"),
+    }
+}
+
+fn worker(
+    jobs: Arc<Mutex<Receiver<Request>>>,
+    proving_keys: Arc<ProvingKeys>,
+    verifying_keys: Arc<VerifyingKeys>,
+    metrics: Arc<Metrics>,
+) {
+    loop {
+        let request = match jobs.lock().unwrap().recv() {
+            Ok(request) => request,
+            Err(_) => return,
+        };
+
+        metrics.queued.fetch_sub(1, Ordering::Relaxed);
+        metrics.running.fetch_add(1, Ordering::Relaxed);
+        handle(request, &proving_keys, &verifying_keys, &metrics);
+        metrics.running.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (params, addr, workers, queue) = match &args[..] {
+        [params, addr, rest @ ..] if rest.len() <= 2 => {
+            let parse = |i: usize, default: usize| {
+                rest.get(i).map_or(default, |n| {
+                    n.parse::<usize>()
+                        .expect("workers and queue must be integers")
+                })
+            };
+            (params, addr, usize::max(parse(0, 1), 1), parse(1, 16))
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    // every request shares the keys, none is built per request
+    let params = read_params(&map_params(params).expect("Read params failed"));
+    let proving_keys = Arc::new(ProvingKeys::build(params.clone()));
+    let verifying_keys = Arc::new(VerifyingKeys::build(params));
+    let metrics = Arc::new(Metrics::default());
+    let server = Server::http(addr.as_str()).unwrap_or_else(|e| {
+        eprintln!("Listening on {} failed: {}", addr, e);
+        process::exit(1);
+    });
+
+    // requests past the running and queued ones are turned away
+    let (sender, receiver) = mpsc::sync_channel::<Request>(queue);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..workers {
+        let receiver = receiver.clone();
+        let proving_keys = proving_keys.clone();
+        let verifying_keys = verifying_keys.clone();
+        let metrics = metrics.clone();
+        thread::spawn(move || worker(receiver, proving_keys, verifying_keys, metrics));
+    }
+
+    println!("Listening on {}", addr);
+    for request in server.incoming_requests() {
+        match (request.method(), request.url()) {
+            (Method::Get, "/metrics") => {
+                let _ = request.respond(Response::from_string(metrics.render()));
+            }
+            (Method::Post, "/prove") | (Method::Post, "/verify")
+                if request.body_length().map_or(false, |n| n > MAX_BODY_BYTES) =>
+            {
+                respond(request, 413, error_body("request body is too large"));
+            }
+            (Method::Post, "/prove") | (Method::Post, "/verify") => {
+                metrics.queued.fetch_add(1, Ordering::Relaxed);
+                match sender.try_send(request) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(request)) | Err(TrySendError::Disconnected(request)) => {
+                        metrics.queued.fetch_sub(1, Ordering::Relaxed);
+                        metrics.rejected.fetch_add(1, Ordering::Relaxed);
+                        respond(request, 503, error_body("prover is busy"));
+                    }
+                }
+            }
+            _ => respond(request, 404, error_body("not found")),
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use kzg_ceremony_circuit::circuit_g1_mul::{Circuit as G1_Circuit, LENGTH as G1_LENGTH};
+use kzg_ceremony_circuit::circuit_g2_mul::{Circuit as G2_Circuit, LENGTH as G2_LENGTH};
+use kzg_ceremony_circuit::halo2_proofs::pairing::bn256::{Bn256, Fr, G1Affine};
+use kzg_ceremony_circuit::halo2_proofs::poly::commitment::Params;
+use kzg_ceremony_prover::params::{map_params, ParamsCache};
+use kzg_ceremony_prover::serialization::{scalar_from_string, BatchContributionJson, Decode};
+use kzg_ceremony_prover::{prove, verify_proofs};
+use std::env;
+use std::fs;
+use std::process;
+
+const USAGE: &str = "Usage:
+    bls-halo2 setup <k> <params_out>
+    bls-halo2 fetch-params <k> [<sha256>]
+    bls-halo2 prove <params> <old_contributions.json> <new_contributions.json> <taus.json> <proof_out>
+    bls-halo2 verify <params> <old_contributions.json> <new_contributions.json> <proof.json>
+    bls-halo2 estimate <num_g1_powers> <num_g2_powers>
+
+`taus.json` is a JSON array of hex encoded secrets, one per contribution.
+`setup` writes insecure test parameters and must not be used in production.
+`fetch-params` downloads the params for `k` from the perpetual powers of tau
+into $BLS_HALO2_PARAMS_DIR (default ~/.cache/bls-halo2) and prints their path,
+the source can be overridden with $BLS_HALO2_PARAMS_URL. The params are checked
+for consistency, and against `sha256`, before they are cached. Without it, the
+default source is checked against the digest pinned for `k`.
+`prove` and `verify` take their params as they are, use the fetched ones.
+`prove` writes a versioned bundle per proof, `verify` also takes the bare
+proofs of earlier releases.";
+
+fn read_contributions(path: &str) -> BatchContributionJson {
+    let json = fs::read_to_string(path).expect("Read contributions failed");
+    serde_json::from_str(&json).expect("Deserialize contributions failed")
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+
+    match args[..] {
+        ["setup", k, params_out] => {
+            let k = k.parse::<u32>().expect("k must be an integer");
+            let params = Params::<G1Affine>::unsafe_setup::<Bn256>(k);
+            let mut params_buffer = vec![];
+            params
+                .write(&mut params_buffer)
+                .expect("Write params failed");
+            fs::write(params_out, &params_buffer).expect("Write params failed");
+        }
+        ["fetch-params", k, ref digest @ ..] if digest.len() <= 1 => {
+            let k = k.parse::<u32>().expect("k must be an integer");
+            let path = ParamsCache::from_env()
+                .and_then(|cache| cache.fetch_with_digest(k, digest.first().copied()))
+                .unwrap_or_else(|e| {
+                    eprintln!("Fetch params failed: {}", e);
+                    process::exit(1);
+                });
+            println!("{}", path.display());
+        }
+        ["prove", params, old, new, taus, proof_out] => {
+            let params = map_params(params).expect("Read params failed");
+            let old = read_contributions(old).decode();
+            let new = read_contributions(new).decode();
+            #[allow(unused_mut)]
+            let mut taus_hex: Vec<String> =
+                serde_json::from_str(&fs::read_to_string(taus).expect("Read taus failed"))
+                    .expect("Deserialize taus failed");
+            #[allow(unused_mut)]
+            let mut taus = taus_hex.iter().map(scalar_from_string).collect::<Vec<_>>();
+
+            let proof = prove(&old, &new, &taus, &params);
+
+            #[cfg(feature = "zeroize")]
+            {
+                kzg_ceremony_circuit::zeroize::Zeroize::zeroize(&mut taus_hex);
+                kzg_ceremony_circuit::utils::zeroize_fields(taus.iter_mut());
+            }
+            let proof = serde_json::to_string(&proof).expect("Serialize proof failed");
+            fs::write(proof_out, proof).expect("Write proof failed");
+        }
+        ["verify", params, old, new, proof] => {
+            let params = map_params(params).expect("Read params failed");
+            let old = read_contributions(old).decode();
+            let new = read_contributions(new).decode();
+            let proof = fs::read_to_string(proof).expect("Read proof failed");
+
+            verify_proofs(&old, &new, proof, &params);
+            println!("Proofs verified");
+        }
+        ["estimate", num_g1_powers, num_g2_powers] => {
+            // the first G2 power is the generator, it takes no proof
+            let (num_g1_powers, num_g2_powers) = match (
+                num_g1_powers.parse::<usize>(),
+                num_g2_powers.parse::<usize>(),
+            ) {
+                (Ok(g1), Ok(g2)) if g2 > 0 => (g1, g2),
+                _ => {
+                    eprintln!("The powers must be integers, with at least one G2 power");
+                    eprintln!("{}", USAGE);
+                    process::exit(1);
+                }
+            };
+
+            let g1_cost = G1_Circuit::<Fr>::default()
+                .estimate()
+                .expect("Estimate failed");
+            println!("G1: {} proofs, {}", num_g1_powers / G1_LENGTH, g1_cost);
+            let g2_cost = G2_Circuit::<Fr>::default()
+                .estimate()
+                .expect("Estimate failed");
+            println!(
+                "G2: {} proofs, {}",
+                (num_g2_powers - 1) / G2_LENGTH,
+                g2_cost
+            );
+        }
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,329 @@
+/*
+  Self-describing proof bundles: a proof with its instances and everything a
+  verifier needs to pick the right key, as JSON.
+
+  The format is stable: a bundle with a given `format` keeps parsing in every
+  later release, new fields only come with a new format number. Bundles of a
+  newer format, or made for another circuit revision, size or commitment
+  scheme, are rejected with the mismatching field instead of failing the
+  verification.
+*/
+
+use kzg_ceremony_circuit::fingerprint::CIRCUIT_VERSION;
+use kzg_ceremony_circuit::halo2_proofs::pairing::bn256::Fr;
+use kzg_ceremony_circuit::halo2_proofs::pairing::group::ff::PrimeField;
+use kzg_ceremony_circuit::instance::InstanceCommitment;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+pub const BUNDLE_FORMAT: u32 = 1;
+// Curve of the KZG params and of the proof.
+pub const BUNDLE_CURVE: &str = "bn254";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BundleCircuit {
+    #[serde(rename = "g1-mul")]
+    G1Mul,
+    #[serde(rename = "g2-mul")]
+    G2Mul,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BundleScheme {
+    #[serde(rename = "plain")]
+    Plain,
+    #[serde(rename = "poseidon")]
+    Poseidon,
+}
+
+// The Keccak commitment doesn't fit the bundled circuits at any supported k.
+impl TryFrom<InstanceCommitment> for BundleScheme {
+    type Error = BundleError;
+
+    fn try_from(commitment: InstanceCommitment) -> Result<Self, BundleError> {
+        match commitment {
+            InstanceCommitment::Plain => Ok(BundleScheme::Plain),
+            InstanceCommitment::Poseidon => Ok(BundleScheme::Poseidon),
+            InstanceCommitment::Keccak => Err(BundleError::UnsupportedCommitment(commitment)),
+        }
+    }
+}
+
+impl From<BundleScheme> for InstanceCommitment {
+    fn from(scheme: BundleScheme) -> Self {
+        match scheme {
+            BundleScheme::Plain => InstanceCommitment::Plain,
+            BundleScheme::Poseidon => InstanceCommitment::Poseidon,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofBundle {
+    pub format: u32,
+    pub circuit: BundleCircuit,
+    #[serde(rename = "circuitVersion")]
+    pub circuit_version: u32,
+    pub scheme: BundleScheme,
+    pub curve: String,
+    pub k: u32,
+    // 0x prefixed hex, as the points of the contributions
+    pub proof: String,
+    // 0x prefixed hex of the little-endian field elements
+    pub instances: Vec<String>,
+}
+
+// The proofs of a batch of contributions, the G1 then the G2 bundles of each
+// contribution, as `Proof` has them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundledProof(pub Vec<(Vec<ProofBundle>, Vec<ProofBundle>)>);
+
+#[derive(Debug)]
+pub enum BundleError {
+    Json(serde_json::Error),
+    // Made by a newer release.
+    UnsupportedFormat {
+        found: u32,
+    },
+    Mismatch {
+        field: &'static str,
+        expected: String,
+        found: String,
+    },
+    MalformedProof,
+    MalformedInstance {
+        index: usize,
+    },
+    UnsupportedCommitment(InstanceCommitment),
+    // The instances are not those of the verified contributions.
+    InstancesMismatch,
+}
+
+impl Display for BundleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BundleError::Json(e) => write!(f, "{}", e),
+            BundleError::UnsupportedFormat { found } => write!(
+                f,
+                "bundle format {} is not supported, up to {} is",
+                found, BUNDLE_FORMAT
+            ),
+            BundleError::Mismatch {
+                field,
+                expected,
+                found,
+            } => write!(f, "bundle {} is {}, expected {}", field, found, expected),
+            BundleError::MalformedProof => write!(f, "bundle proof is malformed"),
+            BundleError::MalformedInstance { index } => {
+                write!(f, "bundle instance {} is malformed", index)
+            }
+            BundleError::UnsupportedCommitment(commitment) => {
+                write!(
+                    f,
+                    "no bundled circuit supports the {:?} commitment",
+                    commitment
+                )
+            }
+            BundleError::InstancesMismatch => {
+                write!(f, "bundle instances are not those of the contributions")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BundleError {}
+
+impl From<serde_json::Error> for BundleError {
+    fn from(e: serde_json::Error) -> Self {
+        BundleError::Json(e)
+    }
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    hex::decode(s.strip_prefix("0x")?).ok()
+}
+
+fn mismatch(field: &'static str, expected: impl ToString, found: impl ToString) -> BundleError {
+    BundleError::Mismatch {
+        field,
+        expected: expected.to_string(),
+        found: found.to_string(),
+    }
+}
+
+impl ProofBundle {
+    pub fn new(
+        circuit: BundleCircuit,
+        commitment: InstanceCommitment,
+        k: u32,
+        proof: &[u8],
+        instances: &[Fr],
+    ) -> Result<Self, BundleError> {
+        Ok(Self {
+            format: BUNDLE_FORMAT,
+            circuit,
+            circuit_version: CIRCUIT_VERSION,
+            scheme: commitment.try_into()?,
+            curve: BUNDLE_CURVE.to_string(),
+            k,
+            proof: format!("0x{}", hex::encode(proof)),
+            instances: instances
+                .iter()
+                .map(|x| format!("0x{}", hex::encode(x.to_repr())))
+                .collect(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("Serialize bundle failed")
+    }
+
+    // The format is read first, so bundles of a later format are reported as
+    // such rather than as missing or unknown fields. Earlier formats keep
+    // parsing.
+    pub fn from_json(json: &str) -> Result<Self, BundleError> {
+        #[derive(Deserialize)]
+        struct Format {
+            format: u32,
+        }
+
+        let Format { format } = serde_json::from_str(json)?;
+        if format > BUNDLE_FORMAT {
+            return Err(BundleError::UnsupportedFormat { found: format });
+        }
+        Ok(serde_json::from_str(json)?)
+    }
+
+    // Checks the bundle is for the circuit and key the verifier holds.
+    pub fn check(
+        &self,
+        circuit: BundleCircuit,
+        commitment: InstanceCommitment,
+        k: u32,
+    ) -> Result<(), BundleError> {
+        // bundles parsed along with others skip `from_json`
+        if self.format > BUNDLE_FORMAT {
+            return Err(BundleError::UnsupportedFormat { found: self.format });
+        }
+        if self.circuit != circuit {
+            return Err(mismatch(
+                "circuit",
+                format!("{:?}", circuit),
+                format!("{:?}", self.circuit),
+            ));
+        }
+        if self.circuit_version != CIRCUIT_VERSION {
+            return Err(mismatch(
+                "circuit version",
+                CIRCUIT_VERSION,
+                self.circuit_version,
+            ));
+        }
+        let scheme = BundleScheme::try_from(commitment)?;
+        if self.scheme != scheme {
+            return Err(mismatch(
+                "scheme",
+                format!("{:?}", scheme),
+                format!("{:?}", self.scheme),
+            ));
+        }
+        if self.curve != BUNDLE_CURVE {
+            return Err(mismatch("curve", BUNDLE_CURVE, &self.curve));
+        }
+        if self.k != k {
+            return Err(mismatch("k", k, self.k));
+        }
+        Ok(())
+    }
+
+    pub fn proof_bytes(&self) -> Result<Vec<u8>, BundleError> {
+        decode_hex(&self.proof).ok_or(BundleError::MalformedProof)
+    }
+
+    // Instances as passed to `verify_proof`, non-canonical ones are rejected.
+    pub fn instance_values(&self) -> Result<Vec<Fr>, BundleError> {
+        self.instances
+            .iter()
+            .enumerate()
+            .map(|(index, x)| {
+                decode_hex(x)
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .and_then(|repr: [u8; 32]| Option::from(Fr::from_repr(repr)))
+                    .ok_or(BundleError::MalformedInstance { index })
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_bundle_round_trip_and_mismatch() {
+    let instances = [Fr::from(1u64), -Fr::from(2u64)];
+    let bundle = ProofBundle::new(
+        BundleCircuit::G1Mul,
+        InstanceCommitment::Plain,
+        22,
+        &[1, 2, 3],
+        &instances,
+    )
+    .unwrap();
+
+    let parsed = ProofBundle::from_json(&bundle.to_json()).unwrap();
+    assert_eq!(parsed, bundle);
+    assert!(parsed
+        .check(BundleCircuit::G1Mul, InstanceCommitment::Plain, 22)
+        .is_ok());
+    assert_eq!(parsed.proof_bytes().unwrap(), vec![1, 2, 3]);
+    assert_eq!(parsed.instance_values().unwrap(), instances.to_vec());
+
+    assert!(matches!(
+        parsed.check(BundleCircuit::G2Mul, InstanceCommitment::Plain, 22),
+        Err(BundleError::Mismatch {
+            field: "circuit",
+            ..
+        })
+    ));
+    assert!(matches!(
+        parsed.check(BundleCircuit::G1Mul, InstanceCommitment::Poseidon, 22),
+        Err(BundleError::Mismatch {
+            field: "scheme",
+            ..
+        })
+    ));
+    assert!(matches!(
+        parsed.check(BundleCircuit::G1Mul, InstanceCommitment::Plain, 23),
+        Err(BundleError::Mismatch { field: "k", .. })
+    ));
+
+    let future = bundle.to_json().replacen(
+        &format!("\"format\":{}", BUNDLE_FORMAT),
+        &format!("\"format\":{}", BUNDLE_FORMAT + 1),
+        1,
+    );
+    assert!(matches!(
+        ProofBundle::from_json(&future),
+        Err(BundleError::UnsupportedFormat { .. })
+    ));
+    let future: ProofBundle = serde_json::from_str(&future).unwrap();
+    assert!(matches!(
+        future.check(BundleCircuit::G1Mul, InstanceCommitment::Plain, 22),
+        Err(BundleError::UnsupportedFormat { .. })
+    ));
+    let past = bundle.to_json().replacen(
+        &format!("\"format\":{}", BUNDLE_FORMAT),
+        &format!("\"format\":{}", BUNDLE_FORMAT - 1),
+        1,
+    );
+    assert!(ProofBundle::from_json(&past).is_ok());
+
+    assert!(matches!(
+        parsed.check(BundleCircuit::G1Mul, InstanceCommitment::Keccak, 22),
+        Err(BundleError::UnsupportedCommitment(_))
+    ));
+
+    let mut bad = bundle;
+    bad.instances[1] = format!("0x{}", "ff".repeat(32));
+    assert!(matches!(
+        bad.instance_values(),
+        Err(BundleError::MalformedInstance { index: 1 })
+    ));
+}
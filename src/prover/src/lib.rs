@@ -1,4 +1,5 @@
-use crate::serialization::{BatchContribution, Proof};
+use crate::bundle::{BundleCircuit, BundleError, BundledProof, ProofBundle};
+use crate::serialization::{BatchContribution, Contribution, Proof};
 use ark_std::{end_timer, start_timer};
 use kzg_ceremony_circuit::circuit_g1_mul::{
     verify_proof as g1_verify_proof, Circuit as G1_Circuit, Instance as G1_Instance,
@@ -13,26 +14,87 @@ use kzg_ceremony_circuit::halo2_proofs::pairing::bn256::Fr;
 use kzg_ceremony_circuit::halo2_proofs::pairing::group::Curve;
 use kzg_ceremony_circuit::halo2_proofs::pairing::{bls12_381, bn256};
 use kzg_ceremony_circuit::halo2_proofs::poly::commitment::Params;
+use kzg_ceremony_circuit::instance::InstanceCommitment;
+use kzg_ceremony_circuit::witness_cache::WitnessCache;
 use kzg_ceremony_circuit::{circuit_g1_mul, circuit_g2_mul};
 use rayon::prelude::*;
+use std::sync::Arc;
 
+pub mod bundle;
+pub mod params;
 pub mod serialization;
 
+// Params and proving keys of both circuits, built once for any number of
+// proofs.
+pub struct ProvingKeys {
+    pub params: Arc<Params<bn256::G1Affine>>,
+    pub g1: G1_PK,
+    pub g2: G2_PK,
+}
+
+impl ProvingKeys {
+    pub fn build(params: Arc<Params<bn256::G1Affine>>) -> Self {
+        println!("Building G1 Proving Key..");
+        let g1 = G1_PK::build(&params);
+
+        println!("Building G2 Proving Key..");
+        let g2 = G2_PK::build(&params);
+
+        Self { params, g1, g2 }
+    }
+}
+
+pub struct VerifyingKeys {
+    pub params: Arc<Params<bn256::G1Affine>>,
+    pub g1: G1_VK,
+    pub g2: G2_VK,
+}
+
+impl VerifyingKeys {
+    pub fn build(params: Arc<Params<bn256::G1Affine>>) -> Self {
+        println!("Building G1 Verification Key..");
+        let g1 = G1_VK::build(&params);
+
+        println!("Building G2 Verification Key..");
+        let g2 = G2_VK::build(&params);
+
+        Self { params, g1, g2 }
+    }
+}
+
+pub fn read_params(params: &[u8]) -> Arc<Params<bn256::G1Affine>> {
+    Arc::new(Params::<bn256::G1Affine>::read(params).expect("Read params failed"))
+}
+
+// The proofs have the plain instances, which every bundled circuit supports.
+fn bundle(circuit: BundleCircuit, k: u32, proof: &[u8], instances: &[Fr]) -> ProofBundle {
+    ProofBundle::new(circuit, InstanceCommitment::Plain, k, proof, instances)
+        .expect("Bundle proof failed")
+}
+
 pub fn prove(
     old_contributions: &BatchContribution,
     new_contributions: &BatchContribution,
     taus: &Vec<Scalar>,
-    params: &Vec<u8>,
-) -> Proof {
-    println!("Proving");
-
-    let params = Params::<bn256::G1Affine>::read(&params[..]).expect("Read params failed");
+    params: &[u8],
+) -> BundledProof {
+    let keys = ProvingKeys::build(read_params(params));
+    prove_with_keys(old_contributions, new_contributions, taus, &keys)
+}
 
-    println!("Building G1 Proving Key..");
-    let g1_pk = G1_PK::build(&params);
+pub fn prove_with_keys(
+    old_contributions: &BatchContribution,
+    new_contributions: &BatchContribution,
+    taus: &Vec<Scalar>,
+    keys: &ProvingKeys,
+) -> BundledProof {
+    println!("Proving");
 
-    println!("Building G2 Proving Key..");
-    let g2_pk = G2_PK::build(&params);
+    let ProvingKeys {
+        params,
+        g1: g1_pk,
+        g2: g2_pk,
+    } = keys;
 
     println!("Generating proofs...");
     let mut proofs = vec![];
@@ -49,6 +111,7 @@ pub fn prove(
         println!("Processing contributions {}...", i);
 
         let pubkey = (bls12_381::G2Affine::generator() * tau).to_affine();
+        let witness_cache = Arc::new(WitnessCache::new());
 
         println!("Processing G1 proofs...");
         let number_g1_powers = old_contribution.num_g1_powers as usize;
@@ -72,6 +135,13 @@ pub fn prove(
                 pubkey: Some(pubkey),
                 points: old_points.iter().map(|p| Some(*p)).collect::<Vec<_>>(),
                 new_points: new_points.iter().map(|p| Some(*p)).collect::<Vec<_>>(),
+                instance_commitment: InstanceCommitment::Plain,
+                witness_cache: Some(witness_cache.clone()),
+                low_memory: false,
+                snapshot: None,
+                range_table: None,
+                progress: None,
+                cancel: None,
                 _mark: Default::default(),
             };
 
@@ -81,8 +151,13 @@ pub fn prove(
                 old_points: old_points.to_vec(),
                 new_points: new_points.to_vec(),
             });
-            let proof_g1 = circuit_g1_mul::create_proofs(&params, g1_circuit, &g1_pk, &instances);
-            proofs_g1.push(proof_g1);
+            let proof_g1 = circuit_g1_mul::create_proofs(params, g1_circuit, g1_pk, &instances);
+            proofs_g1.push(bundle(
+                BundleCircuit::G1Mul,
+                params.k,
+                &proof_g1,
+                &instances,
+            ));
         }
 
         println!("Processing G2 proofs...");
@@ -105,6 +180,13 @@ pub fn prove(
                 pubkey: Some(pubkey),
                 points: old_points.iter().map(|p| Some(*p)).collect::<Vec<_>>(),
                 new_points: new_points.iter().map(|p| Some(*p)).collect::<Vec<_>>(),
+                instance_commitment: InstanceCommitment::Plain,
+                witness_cache: Some(witness_cache.clone()),
+                low_memory: false,
+                snapshot: None,
+                range_table: None,
+                progress: None,
+                cancel: None,
                 _mark: Default::default(),
             };
 
@@ -114,34 +196,57 @@ pub fn prove(
                 old_points: old_points.to_vec(),
                 new_points: new_points.to_vec(),
             });
-            let proof_g2 = circuit_g2_mul::create_proofs(&params, g2_circuit, &g2_pk, &instances);
-            proofs_g2.push(proof_g2);
+            let proof_g2 = circuit_g2_mul::create_proofs(params, g2_circuit, g2_pk, &instances);
+            proofs_g2.push(bundle(
+                BundleCircuit::G2Mul,
+                params.k,
+                &proof_g2,
+                &instances,
+            ));
         }
 
         proofs.push((proofs_g1, proofs_g2));
     }
 
-    Proof(proofs)
+    BundledProof(proofs)
 }
 
 pub fn verify_proofs(
     old_contributions: &BatchContribution,
     new_contributions: &BatchContribution,
     proofs: String,
-    params: Vec<u8>,
+    params: &[u8],
 ) {
-    println!("Verifying");
-
-    let proofs: Proof = serde_json::from_str(&proofs).expect("Deserialize proof failed");
-
     println!("Reading params...");
-    let params = Params::<bn256::G1Affine>::read(&params[..]).expect("Read params failed");
+    let keys = VerifyingKeys::build(read_params(params));
+    verify_proofs_with_keys(old_contributions, new_contributions, proofs, &keys)
+}
 
-    println!("Building G1 Verification Key..");
-    let g1_vk = G1_VK::build(&params);
+// `proofs` is the JSON of bundles, as `prove` returns them, or of the bare
+// `Proof` of earlier releases.
+pub fn verify_proofs_with_keys(
+    old_contributions: &BatchContribution,
+    new_contributions: &BatchContribution,
+    proofs: String,
+    keys: &VerifyingKeys,
+) {
+    println!("Verifying");
 
-    println!("Building G2 Verification Key..");
-    let g2_vk = G2_VK::build(&params);
+    let proofs = match serde_json::from_str::<BundledProof>(&proofs) {
+        Ok(bundles) => unbundle(
+            old_contributions,
+            new_contributions,
+            &bundles,
+            keys.params.k,
+        )
+        .unwrap_or_else(|e| panic!("Invalid proof bundle: {}", e)),
+        Err(_) => serde_json::from_str::<Proof>(&proofs).expect("Deserialize proof failed"),
+    };
+    let VerifyingKeys {
+        params,
+        g1: g1_vk,
+        g2: g2_vk,
+    } = keys;
 
     assert_eq!(proofs.0.len(), new_contributions.contributions.len());
     let timer = start_timer!(|| "Verify proofs");
@@ -151,37 +256,10 @@ pub fn verify_proofs(
             .iter()
             .zip(new_contributions.contributions.iter()),
     ) {
-        let pubkey = new_contribution.pot_pubkey;
-
         let num_chunks = new_contribution.num_g1_powers as usize / G1_LENGTH;
         assert_eq!(proof.0.len(), num_chunks);
         assert_eq!(new_contribution.num_g1_powers as usize % G1_LENGTH, 0);
 
-        let data = proof
-            .0
-            .iter()
-            .zip(
-                old_contribution
-                    .powers_of_tau
-                    .g1_powers
-                    .chunks(G1_LENGTH)
-                    .zip(new_contribution.powers_of_tau.g1_powers.chunks(G1_LENGTH)),
-            )
-            .collect::<Vec<_>>();
-
-        data.par_iter()
-            .enumerate()
-            .for_each(|(i, &(proof_g1, (old_points, new_points)))| {
-                let instances = circuit_g1_mul::generate_instance(&G1_Instance {
-                    from_index: i * G1_LENGTH,
-                    pubkey,
-                    old_points: old_points.to_vec(),
-                    new_points: new_points.to_vec(),
-                });
-
-                g1_verify_proof(&params, &g1_vk, &proof_g1, &instances).unwrap();
-            });
-
         let num_chunks = new_contribution.num_g2_powers as usize / G2_LENGTH;
         assert_eq!(proof.1.len(), num_chunks);
         assert_eq!(new_contribution.num_g2_powers as usize % G2_LENGTH, 1);
@@ -191,28 +269,182 @@ pub fn verify_proofs(
             new_contribution.powers_of_tau.g2_powers[0]
         );
 
-        let data = proof
+        let (g1_instances, g2_instances) =
+            contribution_instances(old_contribution, new_contribution);
+
+        proof
+            .0
+            .par_iter()
+            .zip(g1_instances.par_iter())
+            .for_each(|(proof_g1, instances)| {
+                g1_verify_proof(params, g1_vk, proof_g1, instances).unwrap();
+            });
+
+        proof
             .1
-            .iter()
-            .zip(
-                old_contribution.powers_of_tau.g2_powers[1..]
-                    .chunks(G2_LENGTH)
-                    .zip(new_contribution.powers_of_tau.g2_powers[1..].chunks(G2_LENGTH)),
-            )
-            .collect::<Vec<_>>();
-
-        data.par_iter()
-            .enumerate()
-            .for_each(|(i, &(proof_g2, (old_points, new_points)))| {
-                let instances = circuit_g2_mul::generate_instance(&G2_Instance {
-                    from_index: i * G2_LENGTH + 1,
-                    pubkey,
-                    old_points: old_points.to_vec(),
-                    new_points: new_points.to_vec(),
-                });
-
-                g2_verify_proof(&params, &g2_vk, &proof_g2, &instances).unwrap();
+            .par_iter()
+            .zip(g2_instances.par_iter())
+            .for_each(|(proof_g2, instances)| {
+                g2_verify_proof(params, g2_vk, proof_g2, instances).unwrap();
             });
     }
     end_timer!(timer);
 }
+
+// Instances of the G1 and of the G2 proofs of a contribution, in proof order.
+fn contribution_instances(
+    old_contribution: &Contribution,
+    new_contribution: &Contribution,
+) -> (Vec<Vec<Fr>>, Vec<Vec<Fr>>) {
+    let pubkey = new_contribution.pot_pubkey;
+
+    let g1_instances = old_contribution
+        .powers_of_tau
+        .g1_powers
+        .chunks(G1_LENGTH)
+        .zip(new_contribution.powers_of_tau.g1_powers.chunks(G1_LENGTH))
+        .enumerate()
+        .map(|(i, (old_points, new_points))| {
+            circuit_g1_mul::generate_instance(&G1_Instance {
+                from_index: i * G1_LENGTH,
+                pubkey,
+                old_points: old_points.to_vec(),
+                new_points: new_points.to_vec(),
+            })
+        })
+        .collect();
+
+    let g2_instances = old_contribution.powers_of_tau.g2_powers[1..]
+        .chunks(G2_LENGTH)
+        .zip(new_contribution.powers_of_tau.g2_powers[1..].chunks(G2_LENGTH))
+        .enumerate()
+        .map(|(i, (old_points, new_points))| {
+            circuit_g2_mul::generate_instance(&G2_Instance {
+                from_index: i * G2_LENGTH + 1,
+                pubkey,
+                old_points: old_points.to_vec(),
+                new_points: new_points.to_vec(),
+            })
+        })
+        .collect();
+
+    (g1_instances, g2_instances)
+}
+
+// The bare proofs, once every bundle is found to be made for the keys of
+// degree `k` and for the instances of the contributions.
+fn unbundle(
+    old_contributions: &BatchContribution,
+    new_contributions: &BatchContribution,
+    bundles: &BundledProof,
+    k: u32,
+) -> Result<Proof, BundleError> {
+    let check = |bundles: &[ProofBundle], circuit: BundleCircuit, instances: Vec<Vec<Fr>>| {
+        if bundles.len() != instances.len() {
+            return Err(BundleError::Mismatch {
+                field: "proof count",
+                expected: instances.len().to_string(),
+                found: bundles.len().to_string(),
+            });
+        }
+        bundles
+            .iter()
+            .zip(instances)
+            .map(|(bundle, instances)| {
+                bundle.check(circuit, InstanceCommitment::Plain, k)?;
+                if bundle.instance_values()? != instances {
+                    return Err(BundleError::InstancesMismatch);
+                }
+                bundle.proof_bytes()
+            })
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    if bundles.0.len() != new_contributions.contributions.len() {
+        return Err(BundleError::Mismatch {
+            field: "contribution count",
+            expected: new_contributions.contributions.len().to_string(),
+            found: bundles.0.len().to_string(),
+        });
+    }
+    let mut proofs = vec![];
+    for ((g1_bundles, g2_bundles), (old_contribution, new_contribution)) in bundles.0.iter().zip(
+        old_contributions
+            .contributions
+            .iter()
+            .zip(new_contributions.contributions.iter()),
+    ) {
+        let (g1_instances, g2_instances) =
+            contribution_instances(old_contribution, new_contribution);
+        proofs.push((
+            check(g1_bundles, BundleCircuit::G1Mul, g1_instances)?,
+            check(g2_bundles, BundleCircuit::G2Mul, g2_instances)?,
+        ));
+    }
+
+    Ok(Proof(proofs))
+}
+
+#[test]
+fn test_unbundle_checks_bundles_against_contributions() {
+    use crate::serialization::PowersOfTau;
+    use kzg_ceremony_circuit::halo2_proofs::arithmetic::Field;
+
+    let contribution = |tau: u64| {
+        let tau = Scalar::from(tau);
+        let powers = |n: usize| {
+            (0..n as u64)
+                .map(|i| tau.pow_vartime(&[i]))
+                .collect::<Vec<_>>()
+        };
+        Contribution {
+            num_g1_powers: G1_LENGTH as u32,
+            num_g2_powers: G2_LENGTH as u32 + 1,
+            powers_of_tau: PowersOfTau {
+                g1_powers: powers(G1_LENGTH)
+                    .iter()
+                    .map(|x| (bls12_381::G1Affine::generator() * x).to_affine())
+                    .collect(),
+                g2_powers: powers(G2_LENGTH + 1)
+                    .iter()
+                    .map(|x| (bls12_381::G2Affine::generator() * x).to_affine())
+                    .collect(),
+            },
+            pot_pubkey: (bls12_381::G2Affine::generator() * tau).to_affine(),
+        }
+    };
+    let old = BatchContribution {
+        contributions: vec![contribution(1)],
+    };
+    let new = BatchContribution {
+        contributions: vec![contribution(7)],
+    };
+
+    let (g1_instances, g2_instances) =
+        contribution_instances(&old.contributions[0], &new.contributions[0]);
+    let bundles = BundledProof(vec![(
+        vec![bundle(BundleCircuit::G1Mul, 22, &[1], &g1_instances[0])],
+        vec![bundle(BundleCircuit::G2Mul, 22, &[2], &g2_instances[0])],
+    )]);
+    let proof = unbundle(&old, &new, &bundles, 22).unwrap();
+    assert_eq!(proof.0, vec![(vec![vec![1]], vec![vec![2]])]);
+
+    assert!(matches!(
+        unbundle(&old, &new, &bundles, 23),
+        Err(BundleError::Mismatch { field: "k", .. })
+    ));
+    // the bundles of another contribution
+    assert!(matches!(
+        unbundle(&new, &new, &bundles, 22),
+        Err(BundleError::InstancesMismatch)
+    ));
+    let mut swapped = bundles.clone();
+    std::mem::swap(&mut swapped.0[0].0, &mut swapped.0[0].1);
+    assert!(matches!(
+        unbundle(&old, &new, &swapped, 22),
+        Err(BundleError::Mismatch {
+            field: "circuit",
+            ..
+        })
+    ));
+}
@@ -0,0 +1,410 @@
+/*
+  Locating, downloading and caching the KZG params. Missing params are
+  fetched from the perpetual powers of tau ceremony, as converted to the
+  halo2 format by han0110/halo2-kzg-srs, checked, and kept under the cache
+  directory for later runs.
+
+  Params from DEFAULT_PARAMS_URL are checked against the sha256 pinned for
+  their degree in DEFAULT_PARAMS_DIGESTS, degrees without one are refused
+  unless the caller gives a digest. Other sources are only checked against a
+  digest given by the caller.
+
+  Parsing alone accepts any points, so the powers are also spot checked
+  against the G2 elements, e(g_{i+1}, [1]_2) == e(g_i, [s]_2), and the
+  Lagrange basis against g_0, before any key is built from them.
+
+  Large params are better mapped than read, `map_params` leaves the file in
+  the page cache instead of copying it to the heap next to the parsed points.
+  halo2 owns the parsed points, so those still take their full size in RAM.
+*/
+
+use kzg_ceremony_circuit::halo2_proofs::arithmetic::Engine;
+use kzg_ceremony_circuit::halo2_proofs::pairing::bn256::{self, Bn256};
+use kzg_ceremony_circuit::halo2_proofs::pairing::group::{Curve, Group};
+use kzg_ceremony_circuit::halo2_proofs::poly::commitment::{Params, ParamsVerifier};
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// `{k}` is replaced by the degree.
+pub const DEFAULT_PARAMS_URL: &str =
+    "https://trusted-setup-halo2kzg.s3.eu-central-1.amazonaws.com/perpetual-powers-of-tau-raw-{k}";
+// (k, hex sha256) of the params served from DEFAULT_PARAMS_URL. A degree is
+// only added once its file has been hashed from the bucket.
+pub const DEFAULT_PARAMS_DIGESTS: &[(u32, &str)] = &[];
+pub const PARAMS_DIR_VAR: &str = "BLS_HALO2_PARAMS_DIR";
+pub const PARAMS_URL_VAR: &str = "BLS_HALO2_PARAMS_URL";
+// Consecutive powers checked by `check_params_integrity`, spread over g.
+pub const PARAMS_SPOT_CHECKS: usize = 16;
+
+#[derive(Debug)]
+pub enum ParamsError {
+    Io(io::Error),
+    Download { url: String, reason: String },
+    // The params are for another circuit size.
+    WrongDegree { expected: u32, found: u32 },
+    // Truncated or not params at all.
+    Malformed,
+    // g_{index + 1} is not s * g_index for the s of the G2 elements.
+    InconsistentPowers { index: usize },
+    // The Lagrange basis is not the one of g.
+    InconsistentLagrange,
+    DigestMismatch { expected: String, found: String },
+    // No digest is pinned for params of this degree from the default source.
+    UnpinnedDegree { k: u32 },
+    // Neither the cache directory variable nor HOME is set.
+    NoCacheDir,
+}
+
+impl Display for ParamsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParamsError::Io(e) => write!(f, "{}", e),
+            ParamsError::Download { url, reason } => {
+                write!(f, "downloading {} failed: {}", url, reason)
+            }
+            ParamsError::WrongDegree { expected, found } => {
+                write!(f, "params are for k = {}, expected k = {}", found, expected)
+            }
+            ParamsError::Malformed => write!(f, "params are malformed"),
+            ParamsError::InconsistentPowers { index } => {
+                write!(
+                    f,
+                    "params powers {} and {} are inconsistent",
+                    index,
+                    index + 1
+                )
+            }
+            ParamsError::InconsistentLagrange => {
+                write!(f, "params Lagrange basis is inconsistent")
+            }
+            ParamsError::DigestMismatch { expected, found } => {
+                write!(f, "params sha256 is {}, expected {}", found, expected)
+            }
+            ParamsError::UnpinnedDegree { k } => {
+                write!(
+                    f,
+                    "no sha256 pinned for the default params of k = {}, give one",
+                    k
+                )
+            }
+            ParamsError::NoCacheDir => {
+                write!(f, "no params directory, set {} or HOME", PARAMS_DIR_VAR)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParamsError {}
+
+impl From<io::Error> for ParamsError {
+    fn from(e: io::Error) -> Self {
+        ParamsError::Io(e)
+    }
+}
+
+pub fn check_params(bytes: &[u8], k: u32) -> Result<(), ParamsError> {
+    check_params_degree(bytes, k)?;
+
+    let params = Params::<bn256::G1Affine>::read(bytes).map_err(|_| ParamsError::Malformed)?;
+    check_params_integrity(&params)
+}
+
+// The degree is the leading little-endian u32 of the serialized params, only
+// the first page of mapped params is read.
+pub fn check_params_degree(bytes: &[u8], k: u32) -> Result<(), ParamsError> {
+    let found = bytes
+        .get(..4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(ParamsError::Malformed)?;
+    if found != k {
+        return Err(ParamsError::WrongDegree { expected: k, found });
+    }
+    Ok(())
+}
+
+pub fn check_params_integrity(params: &Params<bn256::G1Affine>) -> Result<(), ParamsError> {
+    let n = params.n as usize;
+    if n < 2 || params.g.len() != n || params.g_lagrange.len() != n {
+        return Err(ParamsError::Malformed);
+    }
+    let verifier: ParamsVerifier<Bn256> = params.verifier(0).map_err(|_| ParamsError::Malformed)?;
+
+    let mut indices = (0..PARAMS_SPOT_CHECKS)
+        .map(|j| j * (n - 1) / PARAMS_SPOT_CHECKS)
+        .collect::<Vec<_>>();
+    indices.push(n - 2);
+    for i in indices {
+        if Bn256::pairing(&params.g[i + 1], &verifier.g2)
+            != Bn256::pairing(&params.g[i], &verifier.s_g2)
+        {
+            return Err(ParamsError::InconsistentPowers { index: i });
+        }
+    }
+
+    // The Lagrange polynomials sum to one.
+    let sum = params
+        .g_lagrange
+        .iter()
+        .fold(bn256::G1::identity(), |acc, p| acc + p);
+    if sum.to_affine() != params.g[0] {
+        return Err(ParamsError::InconsistentLagrange);
+    }
+
+    Ok(())
+}
+
+// Read-only map of the params file, usable wherever the params bytes are.
+// The file must not be modified while mapped.
+pub fn map_params(path: impl AsRef<Path>) -> Result<Mmap, ParamsError> {
+    let file = fs::File::open(path)?;
+    // Safety: the map is read-only and the params files are only replaced by
+    // rename, which leaves the mapped file untouched.
+    let map = unsafe { Mmap::map(&file)? };
+    Ok(map)
+}
+
+// `expected` is the hex sha256 of the serialized params.
+pub fn check_params_digest(bytes: &[u8], expected: &str) -> Result<(), ParamsError> {
+    let found = hex::encode(Sha256::digest(bytes));
+    if !found.eq_ignore_ascii_case(expected) {
+        return Err(ParamsError::DigestMismatch {
+            expected: expected.to_string(),
+            found,
+        });
+    }
+    Ok(())
+}
+
+pub fn default_params_digest(k: u32) -> Option<&'static str> {
+    DEFAULT_PARAMS_DIGESTS
+        .iter()
+        .find(|(degree, _)| *degree == k)
+        .map(|(_, digest)| *digest)
+}
+
+#[derive(Clone, Debug)]
+pub struct ParamsCache {
+    dir: PathBuf,
+    url: String,
+}
+
+impl ParamsCache {
+    pub fn new(dir: impl Into<PathBuf>, url: impl Into<String>) -> Self {
+        Self {
+            dir: dir.into(),
+            url: url.into(),
+        }
+    }
+
+    // $BLS_HALO2_PARAMS_DIR, else ~/.cache/bls-halo2, and $BLS_HALO2_PARAMS_URL,
+    // else DEFAULT_PARAMS_URL.
+    pub fn from_env() -> Result<Self, ParamsError> {
+        let dir = match env::var_os(PARAMS_DIR_VAR) {
+            Some(dir) => PathBuf::from(dir),
+            None => env::var_os("HOME")
+                .map(|home| Path::new(&home).join(".cache").join("bls-halo2"))
+                .ok_or(ParamsError::NoCacheDir)?,
+        };
+        let url = env::var(PARAMS_URL_VAR).unwrap_or_else(|_| DEFAULT_PARAMS_URL.to_string());
+
+        Ok(Self::new(dir, url))
+    }
+
+    pub fn path(&self, k: u32) -> PathBuf {
+        self.dir.join(format!("params-{}.bin", k))
+    }
+
+    // Path of checked params of degree `k`, downloaded first if not cached.
+    // Downloads go through a temporary file, so an interrupted one never
+    // leaves a truncated file behind.
+    pub fn fetch(&self, k: u32) -> Result<PathBuf, ParamsError> {
+        self.fetch_with_digest(k, None)
+    }
+
+    // As `fetch`, with the params also checked against the hex sha256
+    // `digest`, a download before it is renamed into place. Without one,
+    // params from DEFAULT_PARAMS_URL are checked against the pinned digest.
+    pub fn fetch_with_digest(&self, k: u32, digest: Option<&str>) -> Result<PathBuf, ParamsError> {
+        let digest = match digest {
+            Some(digest) => Some(digest),
+            None if self.url == DEFAULT_PARAMS_URL => {
+                Some(default_params_digest(k).ok_or(ParamsError::UnpinnedDegree { k })?)
+            }
+            None => None,
+        };
+        let check = |bytes: &[u8]| {
+            if let Some(digest) = digest {
+                check_params_digest(bytes, digest)?;
+            }
+            check_params(bytes, k)
+        };
+
+        let path = self.path(k);
+        if self.map_cached(k, digest)?.is_some() {
+            return Ok(path);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let partial = path.with_extension("part");
+        let url = self.url.replace("{k}", &k.to_string());
+        let res = download(&url, &partial).and_then(|_| check(&fs::read(&partial)?));
+        if let Err(e) = res {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+
+        fs::rename(&partial, &path)?;
+        Ok(path)
+    }
+
+    pub fn load(&self, k: u32) -> Result<Vec<u8>, ParamsError> {
+        Ok(fs::read(self.fetch(k)?)?)
+    }
+
+    pub fn map(&self, k: u32) -> Result<Mmap, ParamsError> {
+        match self.map_cached(k, None)? {
+            Some(map) => Ok(map),
+            None => map_params(self.fetch(k)?),
+        }
+    }
+
+    // Cached params went through every check before they were renamed into
+    // place, only their degree is checked again, without reading the file.
+    fn map_cached(&self, k: u32, digest: Option<&str>) -> Result<Option<Mmap>, ParamsError> {
+        let path = self.path(k);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let map = map_params(&path)?;
+        check_params_degree(&map, k)?;
+        if let Some(digest) = digest {
+            check_params_digest(&map, digest)?;
+        }
+        Ok(Some(map))
+    }
+}
+
+fn download(url: &str, path: &Path) -> Result<(), ParamsError> {
+    let response = ureq::get(url).call().map_err(|e| ParamsError::Download {
+        url: url.to_string(),
+        reason: e.to_string(),
+    })?;
+
+    let mut file = fs::File::create(path)?;
+    io::copy(&mut response.into_reader(), &mut file)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[test]
+fn test_params_cache_uses_checked_files() {
+    use kzg_ceremony_circuit::halo2_proofs::pairing::bn256::Bn256;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    let dir = env::temp_dir().join(format!("bls-halo2-params-{}", std::process::id()));
+    // nothing listens there, any download attempt fails
+    let cache = ParamsCache::new(&dir, "http://127.0.0.1:1/params-{k}");
+
+    let mut params = vec![];
+    Params::<bn256::G1Affine>::unsafe_setup::<Bn256>(4)
+        .write(&mut params)
+        .unwrap();
+    assert!(check_params(&params, 4).is_ok());
+    assert!(matches!(
+        check_params(&params, 5),
+        Err(ParamsError::WrongDegree {
+            expected: 5,
+            found: 4
+        })
+    ));
+    assert!(matches!(
+        check_params(&params[..params.len() / 2], 4),
+        Err(ParamsError::Malformed)
+    ));
+    let digest = hex::encode(Sha256::digest(&params));
+    assert!(check_params_digest(&params, &digest).is_ok());
+    assert!(matches!(
+        check_params_digest(&params[1..], &digest),
+        Err(ParamsError::DigestMismatch { .. })
+    ));
+
+    // a power that is not s times the previous one
+    let mut tampered = Params::<bn256::G1Affine>::read(&params[..]).unwrap();
+    tampered.g[5] = (tampered.g[5] + tampered.g[0]).to_affine();
+    assert!(matches!(
+        check_params_integrity(&tampered),
+        Err(ParamsError::InconsistentPowers { index: 4 })
+    ));
+    let mut tampered = Params::<bn256::G1Affine>::read(&params[..]).unwrap();
+    tampered.g_lagrange[3] = tampered.g[0];
+    assert!(matches!(
+        check_params_integrity(&tampered),
+        Err(ParamsError::InconsistentLagrange)
+    ));
+
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(cache.path(4), &params).unwrap();
+    assert_eq!(cache.load(4).unwrap(), params);
+    assert_eq!(&cache.map(4).unwrap()[..], &params[..]);
+    fs::write(cache.path(5), &params).unwrap();
+    assert!(matches!(
+        cache.map(5),
+        Err(ParamsError::WrongDegree {
+            expected: 5,
+            found: 4
+        })
+    ));
+    fs::remove_file(cache.path(5)).unwrap();
+    assert!(matches!(cache.fetch(5), Err(ParamsError::Download { .. })));
+    assert!(!cache.path(5).with_extension("part").exists());
+    // the default source is never trusted without a digest
+    assert!(matches!(
+        ParamsCache::new(&dir, DEFAULT_PARAMS_URL).fetch(0),
+        Err(ParamsError::UnpinnedDegree { k: 0 })
+    ));
+    assert!(matches!(
+        cache.fetch_with_digest(4, Some(&"00".repeat(32))),
+        Err(ParamsError::DigestMismatch { .. })
+    ));
+
+    // a download not matching the digest never reaches the cache
+    fs::remove_file(cache.path(4)).unwrap();
+    let serve = |body: Vec<u8>| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/params-{{k}}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0u8; 4096]);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            let _ = stream.write_all(&body);
+        });
+        ParamsCache::new(&dir, url)
+    };
+    assert!(matches!(
+        serve(params.clone()).fetch_with_digest(4, Some(&"00".repeat(32))),
+        Err(ParamsError::DigestMismatch { .. })
+    ));
+    assert!(!cache.path(4).exists());
+    assert!(!cache.path(4).with_extension("part").exists());
+    assert_eq!(
+        serve(params.clone())
+            .fetch_with_digest(4, Some(&digest))
+            .unwrap(),
+        cache.path(4)
+    );
+    assert_eq!(fs::read(cache.path(4)).unwrap(), params);
+
+    fs::remove_dir_all(&dir).unwrap();
+}